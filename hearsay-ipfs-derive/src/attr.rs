@@ -0,0 +1,50 @@
+use syn::{Attribute, Error, LitStr};
+
+/// Options from `#[ipld(...)]` attributes.
+#[derive(Default)]
+pub struct Attrs {
+    pub repr: Option<LitStr>,
+    pub tag: Option<LitStr>,
+    pub rename: Option<LitStr>,
+    pub kind: Option<LitStr>,
+    pub optional: bool,
+}
+
+impl Attrs {
+    /// Parse the `#[ipld(...)]` attributes, rejecting options not in `allowed`.
+    pub fn parse(attrs: &[Attribute], allowed: &[&str]) -> syn::Result<Self> {
+        let mut out = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("ipld")) {
+            attr.parse_nested_meta(|meta| {
+                let Some(name) = meta.path.get_ident().map(|i| i.to_string()) else {
+                    return Err(meta.error("expected an identifier"));
+                };
+                if !allowed.contains(&name.as_str()) {
+                    return Err(meta.error(format!("`{name}` is not supported here")));
+                }
+                match name.as_str() {
+                    "repr" => out.repr = Some(meta.value()?.parse()?),
+                    "tag" => out.tag = Some(meta.value()?.parse()?),
+                    "rename" => out.rename = Some(meta.value()?.parse()?),
+                    "kind" => out.kind = Some(meta.value()?.parse()?),
+                    "optional" => out.optional = true,
+                    _ => return Err(meta.error(format!("unknown option `{name}`"))),
+                }
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+
+    /// The `repr` option, checked against the representations in `allowed`.
+    pub fn repr(&self, allowed: &[&str]) -> syn::Result<Option<String>> {
+        let Some(repr) = &self.repr else {
+            return Ok(None);
+        };
+        let value = repr.value();
+        if !allowed.contains(&value.as_str()) {
+            return Err(Error::new(repr.span(), format!("expected one of {allowed:?}")));
+        }
+        Ok(Some(value))
+    }
+}
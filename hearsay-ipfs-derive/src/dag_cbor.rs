@@ -0,0 +1,512 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_quote, spanned::Spanned, Data, DataEnum, DeriveInput, Error, Fields, GenericParam, Generics, Ident, LitStr, Member, Path, Type, Variant};
+
+use crate::attr::Attrs;
+
+/// Private helpers of the hearsay-ipfs crate used by generated code.
+fn private() -> TokenStream {
+    quote!(::hearsay_ipfs::ipld::__dag_cbor)
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let (encode, decode) = match &input.data {
+        Data::Struct(data) => {
+            let attrs = Attrs::parse(&input.attrs, &["repr"])?;
+            let repr = attrs.repr(&["map", "tuple"])?;
+            let shape = Shape::new(&data.fields, repr.as_deref())?;
+            let path = parse_quote!(Self);
+            let pattern = shape.pattern(&path);
+            let encode = shape.encode(None);
+            (quote!(let #pattern = self; #encode), shape.decode(&path, None))
+        },
+        Data::Enum(data) => expand_enum(input, data)?,
+        Data::Union(_) => return Err(Error::new(input.span(), "unions are not supported")),
+    };
+
+    let p = private();
+    let name = &input.ident;
+    let encode_generics = bound(&input.generics, quote!(#p::Encode<#p::DagCbor>));
+    let (impl_generics, ty_generics, where_clause) = encode_generics.split_for_impl();
+    let encode = quote! {
+        impl #impl_generics #p::Encode<#p::DagCbor> for #name #ty_generics #where_clause {
+            fn encode<__W: ::std::io::Write>(&self, __c: &#p::DagCbor, __w: &mut __W) -> Result<(), #p::CodecError> {
+                #encode
+            }
+        }
+    };
+    let decode_generics = bound(&input.generics, quote!(#p::Decode<#p::DagCbor>));
+    let (impl_generics, ty_generics, where_clause) = decode_generics.split_for_impl();
+    let decode = quote! {
+        impl #impl_generics #p::Decode<#p::DagCbor> for #name #ty_generics #where_clause {
+            fn decode<__R: ::std::io::Read + ::std::io::Seek>(__c: &#p::DagCbor, __r: &mut __R) -> Result<Self, #p::CodecError> {
+                Self::decode_with(__c, __r, &mut #p::Budget::new(__c))
+            }
+
+            fn decode_with<__R: ::std::io::Read + ::std::io::Seek>(__c: &#p::DagCbor, __r: &mut __R, __b: &mut #p::Budget) -> Result<Self, #p::CodecError> {
+                #decode
+            }
+        }
+    };
+    Ok(quote!(#encode #decode))
+}
+
+/// Require `bound` on every type parameter.
+fn bound(generics: &Generics, bound: TokenStream) -> Generics {
+    let mut generics = generics.clone();
+    let params = generics.params.iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(t.ident.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bound));
+    }
+    generics
+}
+
+fn expand_enum(input: &DeriveInput, data: &DataEnum) -> syn::Result<(TokenStream, TokenStream)> {
+    let attrs = Attrs::parse(&input.attrs, &["repr", "tag"])?;
+    let repr = attrs.repr(&["keyed", "kinded", "inline"])?;
+    let p = private();
+    let mut encode_arms = vec![];
+    let mut decode_arms = vec![];
+
+    match repr.as_deref().unwrap_or("keyed") {
+        "keyed" => {
+            if let Some(tag) = &attrs.tag {
+                return Err(Error::new(tag.span(), "`tag` is only supported by inline unions"));
+            }
+            let mut keys = vec![];
+            for variant in &data.variants {
+                let attrs = Attrs::parse(&variant.attrs, &["rename", "repr"])?;
+                let key = variant_key(variant, &attrs, &mut keys)?;
+                let shape = Shape::new(&variant.fields, attrs.repr(&["map", "tuple"])?.as_deref())?;
+                let path = variant_path(variant);
+                let pattern = shape.pattern(&path);
+                let encode = shape.encode(None);
+                encode_arms.push(quote! {
+                    #pattern => {
+                        #p::write_map(__w, 1)?;
+                        #p::write_str(__w, #key)?;
+                        #encode
+                    },
+                });
+                let decode = shape.decode(&path, None);
+                decode_arms.push(quote!(#key => { #decode },));
+            }
+            Ok((
+                quote! {
+                    match self {
+                        #(#encode_arms)*
+                    }
+                },
+                quote! {
+                    if #p::read_map_len(__r, __b)? != 1 {
+                        return Err(#p::CodecError::MalformedData("keyed union must have a single entry"));
+                    }
+                    let __value = match #p::read_key(__r, __b)?.as_str() {
+                        #(#decode_arms)*
+                        _ => Err(#p::CodecError::MalformedData("unknown union variant")),
+                    }?;
+                    #p::end(__b);
+                    Ok(__value)
+                },
+            ))
+        },
+        "kinded" => {
+            if let Some(tag) = &attrs.tag {
+                return Err(Error::new(tag.span(), "`tag` is only supported by inline unions"));
+            }
+            let mut kinds = vec![];
+            for variant in &data.variants {
+                let attrs = Attrs::parse(&variant.attrs, &["kind"])?;
+                let path = variant_path(variant);
+                let (kind, encode, decode) = match &variant.fields {
+                    Fields::Unit => (
+                        Kind::Null,
+                        quote!(#path => #p::write_null(__w),),
+                        quote!({ #p::read_null(__r)?; Ok(#path) }),
+                    ),
+                    Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                        let ty = &f.unnamed[0].ty;
+                        let kind = match &attrs.kind {
+                            Some(kind) => Kind::parse(kind)?,
+                            None => Kind::infer(ty).ok_or_else(|| Error::new(ty.span(), "add #[ipld(kind = \"...\")] to tell the kind of this type"))?,
+                        };
+                        (
+                            kind,
+                            quote!(#path(__f0) => <#ty as #p::Encode<#p::DagCbor>>::encode(__f0, __c, __w),),
+                            quote!(Ok(#path(<#ty as #p::Decode<#p::DagCbor>>::decode_with(__c, __r, __b)?))),
+                        )
+                    },
+                    _ => return Err(Error::new(variant.span(), "kinded union variants must be unit or have a single unnamed field")),
+                };
+                if kinds.contains(&kind) {
+                    return Err(Error::new(variant.span(), "kinded union has two variants of the same kind"));
+                }
+                let tokens = kind.tokens();
+                kinds.push(kind);
+                encode_arms.push(encode);
+                decode_arms.push(quote! {
+                    if matches!(kind, #tokens) {
+                        return #decode;
+                    }
+                });
+            }
+            Ok((
+                quote! {
+                    match self {
+                        #(#encode_arms)*
+                    }
+                },
+                quote! {
+                    let kind = #p::peek_kind(__r, __b)?;
+                    #(#decode_arms)*
+                    Err(#p::CodecError::MalformedData("unexpected kind for kinded union"))
+                },
+            ))
+        },
+        _ => {
+            let Some(tag) = attrs.tag.as_ref().map(LitStr::value) else {
+                return Err(Error::new(input.span(), "inline unions need #[ipld(tag = \"...\")]"));
+            };
+            let mut keys = vec![];
+            for variant in &data.variants {
+                let attrs = Attrs::parse(&variant.attrs, &["rename"])?;
+                let key = variant_key(variant, &attrs, &mut keys)?;
+                // Unit variants are a map holding only the tag.
+                let shape = match &variant.fields {
+                    Fields::Named(_) | Fields::Unit => Shape::Map(Shape::new(&variant.fields, None)?.into_fields()),
+                    Fields::Unnamed(_) => return Err(Error::new(variant.span(), "inline union variants must be unit or have named fields")),
+                };
+                if let Some(field) = shape.fields().iter().find(|f| f.key == tag) {
+                    return Err(Error::new(field.ty.span(), "field has the same key as the union tag"));
+                }
+                let path = variant_path(variant);
+                let pattern = shape.pattern(&path);
+                let encode = shape.encode(Some((&tag, &key)));
+                encode_arms.push(quote!(#pattern => { #encode },));
+                let decode = shape.decode(&path, Some(&tag));
+                decode_arms.push(quote!(#key => { #decode },));
+            }
+            Ok((
+                quote! {
+                    match self {
+                        #(#encode_arms)*
+                    }
+                },
+                quote! {
+                    match #p::find_tag(__c, __r, #tag, __b)?.as_str() {
+                        #(#decode_arms)*
+                        _ => Err(#p::CodecError::MalformedData("unknown union variant")),
+                    }
+                },
+            ))
+        },
+    }
+}
+
+fn variant_path(variant: &Variant) -> Path {
+    let ident = &variant.ident;
+    parse_quote!(Self::#ident)
+}
+
+/// Key of a keyed or inline union variant, rejecting duplicates.
+fn variant_key(variant: &Variant, attrs: &Attrs, seen: &mut Vec<String>) -> syn::Result<String> {
+    let key = attrs.rename.as_ref().map_or_else(|| variant.ident.to_string(), LitStr::value);
+    if seen.contains(&key) {
+        return Err(Error::new(variant.span(), format!("duplicate variant key `{key}`")));
+    }
+    seen.push(key.clone());
+    Ok(key)
+}
+
+/// Data model kind of a kinded union variant.
+#[derive(PartialEq)]
+enum Kind {
+    Null,
+    Bool,
+    Integer,
+    Float,
+    String,
+    Bytes,
+    List,
+    Map,
+    Link,
+}
+
+impl Kind {
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        Ok(match lit.value().as_str() {
+            "null" => Self::Null,
+            "bool" => Self::Bool,
+            "int" => Self::Integer,
+            "float" => Self::Float,
+            "string" => Self::String,
+            "bytes" => Self::Bytes,
+            "list" => Self::List,
+            "map" => Self::Map,
+            "link" => Self::Link,
+            _ => return Err(Error::new(lit.span(), "expected one of null, bool, int, float, string, bytes, list, map or link")),
+        })
+    }
+
+    /// Kind of well known types, by the last segment of their path.
+    fn infer(ty: &Type) -> Option<Self> {
+        let Type::Path(path) = ty else {
+            return None;
+        };
+        Some(match path.path.segments.last()?.ident.to_string().as_str() {
+            "bool" => Self::Bool,
+            "i8" | "i16" | "i32" | "i64" | "i128" | "u8" | "u16" | "u32" | "u64" => Self::Integer,
+            "f64" => Self::Float,
+            "String" => Self::String,
+            "Bytes" => Self::Bytes,
+            "Vec" => Self::List,
+            "BTreeMap" => Self::Map,
+            "Cid" => Self::Link,
+            _ => return None,
+        })
+    }
+
+    fn tokens(&self) -> TokenStream {
+        let p = private();
+        let kind = match self {
+            Self::Null => "Null",
+            Self::Bool => "Bool",
+            Self::Integer => "Integer",
+            Self::Float => "Float",
+            Self::String => "String",
+            Self::Bytes => "Bytes",
+            Self::List => "List",
+            Self::Map => "Map",
+            Self::Link => "Link",
+        };
+        let kind = Ident::new(kind, Span::call_site());
+        quote!(#p::IpldKind::#kind)
+    }
+}
+
+struct Field<'a> {
+    member: Member,
+    /// Local the field is bound to while encoding and decoding.
+    binding: Ident,
+    key: String,
+    ty: &'a Type,
+    optional: bool,
+}
+
+/// Wire representation of a struct or enum variant.
+enum Shape<'a> {
+    Unit,
+    /// A single unnamed field, represented as the field itself.
+    Newtype(Field<'a>),
+    Map(Vec<Field<'a>>),
+    Tuple(Vec<Field<'a>>),
+}
+
+impl<'a> Shape<'a> {
+    fn new(fields: &'a Fields, repr: Option<&str>) -> syn::Result<Self> {
+        let named = matches!(fields, Fields::Named(_));
+        let mut out = vec![];
+        for (i, field) in fields.iter().enumerate() {
+            let attrs = Attrs::parse(&field.attrs, &["rename", "optional"])?;
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::from(i),
+            };
+            let key = match (&attrs.rename, &field.ident) {
+                (Some(rename), _) => rename.value(),
+                (None, Some(ident)) => ident.to_string(),
+                (None, None) => i.to_string(),
+            };
+            if attrs.optional && !is_option(&field.ty) {
+                return Err(Error::new(field.ty.span(), "optional fields must be an Option"));
+            }
+            if out.iter().any(|f: &Field| f.key == key) {
+                return Err(Error::new(field.span(), format!("duplicate field key `{key}`")));
+            }
+            out.push(Field {
+                member,
+                binding: format_ident!("__f{}", i),
+                key,
+                ty: &field.ty,
+                optional: attrs.optional,
+            });
+        }
+        let shape = match (fields, repr) {
+            (Fields::Unit, _) => Self::Unit,
+            (Fields::Unnamed(_), None) if out.len() == 1 => Self::Newtype(out.remove(0)),
+            (_, Some("tuple")) => Self::Tuple(out),
+            (_, Some("map")) => Self::Map(out),
+            _ if named => Self::Map(out),
+            _ => Self::Tuple(out),
+        };
+        if let Self::Tuple(fields) = &shape {
+            if let Some(field) = fields.iter().find(|f| f.optional) {
+                return Err(Error::new(field.ty.span(), "optional fields are only supported by map representations"));
+            }
+        }
+        Ok(shape)
+    }
+
+    fn fields(&self) -> &[Field<'a>] {
+        match self {
+            Self::Unit => &[],
+            Self::Newtype(field) => std::slice::from_ref(field),
+            Self::Map(fields) | Self::Tuple(fields) => fields,
+        }
+    }
+
+    fn into_fields(self) -> Vec<Field<'a>> {
+        match self {
+            Self::Unit => vec![],
+            Self::Newtype(field) => vec![field],
+            Self::Map(fields) | Self::Tuple(fields) => fields,
+        }
+    }
+
+    /// Pattern binding every field by reference.
+    fn pattern(&self, path: &Path) -> TokenStream {
+        if let Self::Unit = self {
+            return quote!(#path);
+        }
+        let members = self.fields().iter().map(|f| &f.member);
+        let bindings = self.fields().iter().map(|f| &f.binding);
+        quote!(#path { #(#members: #bindings),* })
+    }
+
+    /// Write the fields bound by [Shape::pattern], plus a `(tag, variant)` entry for inline unions.
+    /// Evaluates to `Result<(), CodecError>`.
+    fn encode(&self, tag: Option<(&str, &str)>) -> TokenStream {
+        let p = private();
+        let body = match self {
+            Self::Unit => return quote!(#p::write_null(__w)),
+            Self::Newtype(Field { binding, ty, .. }) => return quote! {
+                <#ty as #p::Encode<#p::DagCbor>>::encode(#binding, __c, __w)
+            },
+            Self::Tuple(fields) => {
+                let len = fields.len() as u64;
+                let fields = fields.iter().map(|Field { binding, ty, .. }| quote! {
+                    <#ty as #p::Encode<#p::DagCbor>>::encode(#binding, __c, __w)?;
+                });
+                quote! {
+                    #p::write_list(__w, #len)?;
+                    #(#fields)*
+                }
+            },
+            Self::Map(fields) => {
+                let required = (fields.iter().filter(|f| !f.optional).count() + usize::from(tag.is_some())) as u64;
+                let optional = fields.iter().filter(|f| f.optional).map(|f| {
+                    let binding = &f.binding;
+                    quote!(+ u64::from(#binding.is_some()))
+                });
+                let mut entries = fields.iter()
+                    .map(|Field { binding, key, ty, optional, .. }| {
+                        let entry = quote! {
+                            #p::write_str(__w, #key)?;
+                            <#ty as #p::Encode<#p::DagCbor>>::encode(#binding, __c, __w)?;
+                        };
+                        let entry = match optional {
+                            true => quote!(if #binding.is_some() { #entry }),
+                            false => entry,
+                        };
+                        (key.as_str(), entry)
+                    })
+                    .collect::<Vec<_>>();
+                if let Some((tag, variant)) = tag {
+                    entries.push((tag, quote! {
+                        #p::write_str(__w, #tag)?;
+                        #p::write_str(__w, #variant)?;
+                    }));
+                }
+                // Keys are sorted by length first, see [RFC 7049](https://datatracker.ietf.org/doc/html/rfc7049#section-3.9).
+                entries.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then(a.cmp(b)));
+                let entries = entries.into_iter().map(|(_, entry)| entry);
+                quote! {
+                    #p::write_map(__w, #required #(#optional)*)?;
+                    #(#entries)*
+                }
+            },
+        };
+        quote! {
+            #body
+            Ok(())
+        }
+    }
+
+    /// Read the fields, skipping the `tag` key of inline unions.
+    /// Evaluates to `Result<Self, CodecError>`.
+    fn decode(&self, path: &Path, tag: Option<&str>) -> TokenStream {
+        let p = private();
+        match self {
+            Self::Unit => quote! {
+                #p::read_null(__r)?;
+                Ok(#path)
+            },
+            Self::Newtype(Field { member, ty, .. }) => quote! {
+                Ok(#path { #member: <#ty as #p::Decode<#p::DagCbor>>::decode_with(__c, __r, __b)? })
+            },
+            Self::Tuple(fields) => {
+                let len = fields.len() as u64;
+                let fields = fields.iter().map(|Field { member, ty, .. }| quote! {
+                    #member: <#ty as #p::Decode<#p::DagCbor>>::decode_with(__c, __r, __b)?
+                });
+                quote! {
+                    if #p::read_list_len(__r, __b)? != #len {
+                        return Err(#p::CodecError::MalformedData("unexpected tuple length"));
+                    }
+                    let __value = #path { #(#fields),* };
+                    #p::end(__b);
+                    Ok(__value)
+                }
+            },
+            Self::Map(fields) => {
+                let decls = fields.iter().map(|Field { binding, ty, .. }| quote! {
+                    let mut #binding: Option<#ty> = None;
+                });
+                let arms = fields.iter().map(|Field { binding, key, ty, .. }| quote! {
+                    #key => {
+                        if #binding.is_some() {
+                            return Err(#p::CodecError::MalformedData("duplicate map keys"));
+                        }
+                        #binding = Some(<#ty as #p::Decode<#p::DagCbor>>::decode_with(__c, __r, __b)?);
+                    },
+                });
+                let tag = tag.map(|tag| quote!(#tag => #p::skip(__r, __b)?,));
+                let build = fields.iter().map(|Field { member, binding, key, optional, .. }| match optional {
+                    true => quote!(#member: #binding.unwrap_or_default()),
+                    false => {
+                        let missing = format!("missing field `{key}`");
+                        quote!(#member: #binding.ok_or(#p::CodecError::MalformedData(#missing))?)
+                    },
+                });
+                quote! {
+                    #(#decls)*
+                    let mut __prev = None;
+                    for _ in 0..#p::read_map_len(__r, __b)? {
+                        let __key = #p::read_key(__r, __b)?;
+                        #p::check_key(__b, &mut __prev, &__key)?;
+                        match __key.as_str() {
+                            #(#arms)*
+                            #tag
+                            _ => return Err(#p::CodecError::MalformedData("unknown map key")),
+                        }
+                    }
+                    #p::end(__b);
+                    Ok(#path { #(#build),* })
+                }
+            },
+        }
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().is_some_and(|s| s.ident == "Option"),
+        _ => false,
+    }
+}
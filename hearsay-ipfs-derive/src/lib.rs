@@ -0,0 +1,29 @@
+//! Derive macros for `hearsay_ipfs::ipld` codecs.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod attr;
+mod dag_cbor;
+
+/// Derive `Encode<DagCbor>` and `Decode<DagCbor>`, reading and writing the wire format directly
+/// instead of going through an `Ipld` tree.
+///
+/// Representations follow [IPLD Schemas](https://ipld.io/docs/schemas/features/representation-strategies/):
+/// - structs with named fields are maps, with keys in canonical DAG-CBOR order.
+///   `#[ipld(repr = "tuple")]` makes them lists, as are structs with unnamed fields.
+///   A single unnamed field is represented as the field itself.
+/// - enums are keyed unions by default, a single entry map from variant name to content.
+///   `#[ipld(repr = "kinded")]` tells variants apart by the kind of their content,
+///   `#[ipld(repr = "inline", tag = "type")]` flattens struct variants into a map with a discriminant key.
+///
+/// Fields and variants take `#[ipld(rename = "name")]`.
+/// `Option` fields of map structs take `#[ipld(optional)]` to leave out the key when [None], rather than writing null.
+/// Kinded variants take `#[ipld(kind = "map")]` when the kind can not be told from the type name.
+#[proc_macro_derive(DagCbor, attributes(ipld))]
+pub fn derive_dag_cbor(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    dag_cbor::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -1,9 +1,182 @@
-use std::{fmt::Debug, hash::Hash, io};
+use std::{fmt::Debug, hash::Hash};
 
 use bytes::Bytes;
-use cid::Cid;
+use cid::{multihash::Multihash, Cid, Version};
 use multihash_codetable::Code;
-use multihash_derive::MultihashDigest;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{hasher::HasherRegistry, ipld::{Codec, CodecError, Encode}};
+
+#[derive(Debug, Error)]
+pub enum BlockError {
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    Cid(#[from] cid::Error),
+    #[error("CID does not match the block data")]
+    InvalidCid,
+    #[error("multihash code {0:#x} is not supported")]
+    UnsupportedHash(u64),
+    #[error("invalid CID prefix: {0}")]
+    InvalidPrefix(&'static str),
+    #[error("digest of {0} bytes exceeds the 64 byte multihash limit")]
+    DigestTooLarge(usize),
+    #[error("{0} bytes is too large to inline in an identity CID")]
+    InlineTooLarge(usize),
+    #[error("block of {size} bytes exceeds the {limit} byte limit")]
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+}
+
+/// Multihash code of the identity "hash", where the digest is the data itself.
+/// See <https://github.com/multiformats/multicodec/blob/master/table.csv>.
+pub const IDENTITY: u64 = 0x00;
+
+/// Largest payload that fits in an identity [Cid].
+pub const MAX_INLINE_SIZE: usize = 64;
+
+/// True if the [Cid] carries its data inline with the identity multihash.
+pub fn is_inline(cid: &Cid) -> bool {
+    cid.hash().code() == IDENTITY
+}
+
+/// Settings used to derive a [Cid] from block data.
+/// See <https://github.com/multiformats/cid#how-does-it-work>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CidPrefix {
+    pub version: Version,
+    /// Multicodec of the block data.
+    pub codec: u64,
+    /// Multihash code of the hash function.
+    pub mh_type: u64,
+    /// Truncate the digest to this many bytes, `None` keeps the full digest.
+    pub mh_len: Option<u8>,
+}
+
+impl CidPrefix {
+    /// Multicodec of DAG-PB, the only codec allowed in a CIDv0.
+    const DAG_PB: u64 = 0x70;
+
+    /// CIDv0 prefix: DAG-PB and a full length SHA2-256 digest.
+    pub const V0: CidPrefix = CidPrefix {
+        version: Version::V0,
+        codec: Self::DAG_PB,
+        mh_type: 0x12,
+        mh_len: None,
+    };
+
+    /// CIDv1 prefix for the given codec and hash function.
+    pub fn v1(codec: u64, hash: Code) -> Self {
+        Self {
+            version: Version::V1,
+            codec,
+            mh_type: hash.into(),
+            mh_len: None,
+        }
+    }
+
+    /// CIDv1 prefix that inlines the data with the identity multihash.
+    pub fn identity(codec: u64) -> Self {
+        Self {
+            version: Version::V1,
+            codec,
+            mh_type: IDENTITY,
+            mh_len: None,
+        }
+    }
+
+    /// Use the [Codec] multicodec code.
+    pub fn with_codec<C: Codec>(mut self, _c: &C) -> Self {
+        self.codec = C::CODE;
+        self
+    }
+
+    /// Truncate digests to `len` bytes.
+    pub fn with_mh_len(mut self, len: u8) -> Self {
+        self.mh_len = Some(len);
+        self
+    }
+
+    /// Prefix that reproduces the given [Cid].
+    pub fn from_cid(cid: &Cid) -> Self {
+        Self {
+            version: cid.version(),
+            codec: cid.codec(),
+            mh_type: cid.hash().code(),
+            mh_len: Some(cid.hash().size()),
+        }
+    }
+
+    /// Hash data into a [Multihash] with this prefix's settings, using the built-in hashers.
+    pub fn digest(&self, data: &[u8]) -> Result<Multihash<64>, BlockError> {
+        self.digest_with(HasherRegistry::builtin(), data)
+    }
+
+    /// Hash data into a [Multihash] with this prefix's settings.
+    pub fn digest_with(&self, hashers: &HasherRegistry, data: &[u8]) -> Result<Multihash<64>, BlockError> {
+        if self.mh_type == IDENTITY && self.mh_len.is_some_and(|len| len as usize != data.len()) {
+            return Err(BlockError::InvalidPrefix("identity digest length must match the data"));
+        }
+        let hash = hashers.digest(self.mh_type, data)?;
+        Ok(match self.mh_len {
+            Some(len) if len > hash.size() => return Err(BlockError::InvalidPrefix("digest length exceeds hash size")),
+            Some(len) => hash.truncate(len),
+            None => hash,
+        })
+    }
+
+    /// Compute the [Cid] of data, using the built-in hashers.
+    pub fn to_cid(&self, data: &[u8]) -> Result<Cid, BlockError> {
+        self.to_cid_with(HasherRegistry::builtin(), data)
+    }
+
+    /// Compute the [Cid] of data.
+    pub fn to_cid_with(&self, hashers: &HasherRegistry, data: &[u8]) -> Result<Cid, BlockError> {
+        let hash = self.digest_with(hashers, data)?;
+        Ok(match self.version {
+            Version::V0 => {
+                if self.codec != Self::DAG_PB {
+                    return Err(BlockError::InvalidPrefix("CIDv0 requires the DAG-PB codec"));
+                }
+                Cid::new_v0(hash)?
+            },
+            Version::V1 => Cid::new_v1(self.codec, hash),
+        })
+    }
+}
+
+/// Block size limits, enforced wherever blocks enter the [crate::repo::Repository].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockLimits {
+    /// Blocks larger than this are accepted with a warning.
+    pub soft: usize,
+    /// Blocks larger than this are rejected.
+    pub hard: usize,
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self {
+            soft: 1 << 20,
+            hard: 2 << 20,
+        }
+    }
+}
+
+impl BlockLimits {
+    pub fn check(&self, size: usize) -> Result<(), BlockError> {
+        if size > self.hard {
+            return Err(BlockError::TooLarge { size, limit: self.hard });
+        }
+        if size > self.soft {
+            warn!("block of {} bytes exceeds the {} byte soft limit", size, self.soft);
+        }
+        Ok(())
+    }
+}
 
 #[derive(Clone, Eq)]
 pub struct Block {
@@ -12,14 +185,71 @@ pub struct Block {
 }
 
 impl Block {
-    pub fn new(cid: Cid, data: Bytes) -> Result<Self, io::Error> {
+    /// Checked [Block] from a precomputed [Cid], using the built-in hashers.
+    pub fn new(cid: Cid, data: Bytes) -> Result<Self, BlockError> {
+        Self::new_with(HasherRegistry::builtin(), cid, data)
+    }
+
+    /// Checked [Block] from a precomputed [Cid].
+    pub fn new_with(hashers: &HasherRegistry, cid: Cid, data: Bytes) -> Result<Self, BlockError> {
         let block = Block { cid, data };
-        if !block.verify() {
-            return Err(io::ErrorKind::InvalidData.into());
+        if !block.verify_with(hashers) {
+            return Err(BlockError::InvalidCid);
         }
         Ok(block)
     }
 
+    /// Unchecked [Block], for block stores handing back what they hold. Stores cannot know every hasher,
+    /// so [Repository::get_block](crate::repo::Repository::get_block) verifies it on read.
+    pub(crate) fn new_unchecked(cid: Cid, data: Bytes) -> Self {
+        Block { cid, data }
+    }
+
+    /// [Block] from raw data, addressed by a [Cid] built from the prefix.
+    pub fn from_data(prefix: &CidPrefix, data: impl Into<Bytes>) -> Result<Self, BlockError> {
+        Self::from_data_with(HasherRegistry::builtin(), prefix, data)
+    }
+
+    /// [Block] from raw data, addressed by a [Cid] built from the prefix.
+    pub fn from_data_with(hashers: &HasherRegistry, prefix: &CidPrefix, data: impl Into<Bytes>) -> Result<Self, BlockError> {
+        let data = data.into();
+        let cid = prefix.to_cid_with(hashers, &data)?;
+        Ok(Block { cid, data })
+    }
+
+    /// [Block] holding the data inlined in its identity [Cid].
+    pub fn inline(cid: Cid) -> Result<Self, BlockError> {
+        if !is_inline(&cid) {
+            return Err(BlockError::InvalidPrefix("CID does not use the identity multihash"));
+        }
+        let data = Bytes::copy_from_slice(cid.hash().digest());
+        Ok(Block { cid, data })
+    }
+
+    /// Encode a value with the [Codec] into a CIDv1 [Block].
+    pub fn encode<C, T>(c: &C, hash: Code, value: &T) -> Result<Self, BlockError>
+    where
+        C: Codec,
+        T: Encode<C> + ?Sized,
+    {
+        Self::encode_with_prefix(c, &CidPrefix::v1(C::CODE, hash), value)
+    }
+
+    /// Encode a value with the [Codec] into a [Block] addressed by the prefix.
+    /// The prefix codec must match the [Codec].
+    pub fn encode_with_prefix<C, T>(c: &C, prefix: &CidPrefix, value: &T) -> Result<Self, BlockError>
+    where
+        C: Codec,
+        T: Encode<C> + ?Sized,
+    {
+        if prefix.codec != C::CODE {
+            return Err(BlockError::InvalidPrefix("codec does not match the prefix"));
+        }
+        let mut data = vec![];
+        value.encode(c, &mut data)?;
+        Self::from_data(prefix, data)
+    }
+
     pub fn cid(&self) -> &Cid {
         &self.cid
     }
@@ -32,12 +262,17 @@ impl Block {
         &self.data
     }
 
+    /// Check the data against the [Cid], using the built-in hashers.
     pub fn verify(&self) -> bool {
-        let hash = match Code::try_from(self.cid.hash().code()) {
-            Ok(code) => code.digest(&self.data),
-            Err(_) => return false,
-        };
-        hash.digest() == self.cid.hash().digest()
+        self.verify_with(HasherRegistry::builtin())
+    }
+
+    /// Check the data against the [Cid].
+    pub fn verify_with(&self, hashers: &HasherRegistry) -> bool {
+        match CidPrefix::from_cid(&self.cid).digest_with(hashers, &self.data) {
+            Ok(hash) => hash.digest() == self.cid.hash().digest(),
+            Err(_) => false,
+        }
     }
 }
 
@@ -61,3 +296,76 @@ impl Hash for Block {
         Hash::hash(&self.cid, state)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ipld::{DagCbor, Ipld, RawData};
+
+    use super::*;
+
+    #[test]
+    fn test_block_encode() {
+        let value = Ipld::String(String::from("banana"));
+        let block = Block::encode(&DagCbor::default(), Code::Sha2_256, &value).unwrap();
+        assert_eq!(block.cid().version(), Version::V1);
+        assert_eq!(block.cid().codec(), 0x71);
+        assert_eq!(block.cid().hash().code(), 0x12);
+        assert_eq!(block.data(), b"\x66banana");
+        assert!(block.verify());
+        assert_eq!(Block::new(*block.cid(), block.inner().clone()).unwrap(), block);
+
+        let prefix = CidPrefix::v1(0x71, Code::Sha2_256).with_codec(&RawData);
+        assert!(Block::encode_with_prefix(&DagCbor::default(), &prefix, &value).is_err());
+    }
+
+    #[test]
+    fn test_block_prefix() {
+        let data = Bytes::from_static(b"banana");
+        let v0 = Block::from_data(&CidPrefix::V0, data.clone()).unwrap();
+        assert_eq!(v0.cid().version(), Version::V0);
+        assert_eq!(v0.cid().to_string(), "QmaVYqVRMAZiAbKe9JSR2GCmrSFNVCstVyKLoPkCPh6XND");
+        assert_eq!(CidPrefix::from_cid(v0.cid()).to_cid(&data).unwrap(), *v0.cid());
+        assert!(CidPrefix { codec: 0x55, ..CidPrefix::V0 }.to_cid(&data).is_err());
+
+        let short = CidPrefix::v1(0x55, Code::Sha2_256).with_mh_len(20);
+        let block = Block::from_data(&short, data.clone()).unwrap();
+        assert_eq!(block.cid().hash().size(), 20);
+        assert!(block.verify());
+        assert!(Block::new(*block.cid(), Bytes::from_static(b"apple")).is_err());
+    }
+
+    #[test]
+    fn test_block_custom_hasher() {
+        let mut hashers = HasherRegistry::default();
+        hashers.register(0x300000, crate::hasher::tests::xor_fold);
+        let prefix = CidPrefix { mh_type: 0x300000, ..CidPrefix::v1(0x55, Code::Sha2_256) };
+        assert!(matches!(Block::from_data(&prefix, Bytes::from_static(b"banana")), Err(BlockError::UnsupportedHash(0x300000))));
+
+        let block = Block::from_data_with(&hashers, &prefix, Bytes::from_static(b"banana")).unwrap();
+        assert_eq!(block.cid().hash().digest(), b"banana\0\0");
+        assert!(block.verify_with(&hashers));
+        assert!(!block.verify());
+        assert!(Block::new_with(&hashers, *block.cid(), block.inner().clone()).is_ok());
+        assert!(Block::new_with(&hashers, *block.cid(), Bytes::from_static(b"apple")).is_err());
+    }
+
+    #[test]
+    fn test_block_limits() {
+        let limits = BlockLimits { soft: 4, hard: 8 };
+        assert!(limits.check(4).is_ok());
+        assert!(limits.check(8).is_ok());
+        assert!(matches!(limits.check(9), Err(BlockError::TooLarge { size: 9, limit: 8 })));
+    }
+
+    #[test]
+    fn test_block_identity() {
+        let data = Bytes::from_static(b"banana");
+        let block = Block::from_data(&CidPrefix::identity(0x55), data.clone()).unwrap();
+        assert!(is_inline(block.cid()));
+        assert_eq!(block.cid().hash().digest(), b"banana");
+        assert!(block.verify());
+        assert_eq!(Block::inline(*block.cid()).unwrap().inner(), &data);
+        assert!(Block::new(*block.cid(), Bytes::from_static(b"banan")).is_err());
+        assert!(Block::from_data(&CidPrefix::identity(0x55), vec![0u8; MAX_INLINE_SIZE + 1]).is_err());
+    }
+}
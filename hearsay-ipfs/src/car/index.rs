@@ -0,0 +1,230 @@
+//! CARv2 indexes, mapping multihash digests to the offsets of their sections in the data payload.
+//! See <https://ipld.io/specs/transport/car/carv2/#index-format>.
+
+use std::collections::BTreeMap;
+
+use cid::multihash::Multihash;
+
+use super::CarError;
+
+/// Format of a [CarIndex], identified by its multicodec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexCodec {
+    /// Digests without their multihash codes.
+    IndexSorted,
+    /// Digests grouped by multihash code.
+    #[default]
+    MultihashIndexSorted,
+}
+
+impl IndexCodec {
+    pub fn code(self) -> u64 {
+        match self {
+            Self::IndexSorted => 0x0400,
+            Self::MultihashIndexSorted => 0x0401,
+        }
+    }
+
+    pub fn from_code(code: u64) -> Result<Self, CarError> {
+        match code {
+            0x0400 => Ok(Self::IndexSorted),
+            0x0401 => Ok(Self::MultihashIndexSorted),
+            _ => Err(CarError::Malformed("index codec is not supported")),
+        }
+    }
+}
+
+/// Digests bucketed by width, each bucket holding entries of the digest followed by a little-endian u64 offset,
+/// sorted by digest so lookups are a binary search.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Buckets(BTreeMap<usize, Vec<u8>>);
+
+impl Buckets {
+    fn build<'a>(entries: impl Iterator<Item = (&'a [u8], u64)>) -> Self {
+        let mut sorted = BTreeMap::<usize, Vec<_>>::new();
+        for (digest, offset) in entries {
+            sorted.entry(digest.len() + 8).or_default().push((digest, offset));
+        }
+        Self(sorted.into_iter().map(|(width, mut entries)| {
+            entries.sort();
+            let bucket = entries.into_iter()
+                .flat_map(|(digest, offset)| digest.iter().copied().chain(offset.to_le_bytes()))
+                .collect();
+            (width, bucket)
+        }).collect())
+    }
+
+    fn get(&self, digest: &[u8]) -> Option<u64> {
+        let width = digest.len() + 8;
+        let bucket = self.0.get(&width)?;
+        let (mut lo, mut hi) = (0, bucket.len() / width);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let entry = &bucket[mid * width..][..width];
+            match entry[..width - 8].cmp(digest) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(u64::from_le_bytes(entry[width - 8..].try_into().unwrap())),
+            }
+        }
+        None
+    }
+
+    fn read(r: &mut &[u8]) -> Result<Self, CarError> {
+        let mut buckets = BTreeMap::new();
+        for _ in 0..read_u32(r)? {
+            let width = read_u32(r)? as usize;
+            let len = read_u64(r)?;
+            // Digests are at most 64 bytes, as in a Cid, and empty for an empty identity block.
+            if !(8..=72).contains(&width) || len % width as u64 != 0 || len > r.len() as u64 {
+                return Err(CarError::Malformed("invalid index bucket"));
+            }
+            let (bucket, rest) = r.split_at(len as usize);
+            *r = rest;
+            let sorted = bucket.chunks_exact(width)
+                .zip(bucket.chunks_exact(width).skip(1))
+                .all(|(a, b)| a[..width - 8] <= b[..width - 8]);
+            if !sorted || buckets.insert(width, bucket.to_vec()).is_some() {
+                return Err(CarError::Malformed("invalid index bucket"));
+            }
+        }
+        Ok(Self(buckets))
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend((self.0.len() as u32).to_le_bytes());
+        for (width, bucket) in &self.0 {
+            buf.extend((*width as u32).to_le_bytes());
+            buf.extend((bucket.len() as u64).to_le_bytes());
+            buf.extend(bucket);
+        }
+    }
+}
+
+/// Index of a CARv2, from multihashes to the offsets of their sections from the start of the data payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CarIndex {
+    IndexSorted(Buckets),
+    MultihashIndexSorted(BTreeMap<u64, Buckets>),
+}
+
+impl CarIndex {
+    pub fn build(codec: IndexCodec, entries: impl IntoIterator<Item = (Multihash<64>, u64)>) -> Self {
+        let entries = entries.into_iter().collect::<Vec<_>>();
+        match codec {
+            IndexCodec::IndexSorted => Self::IndexSorted(Buckets::build(entries.iter().map(|(mh, offset)| (mh.digest(), *offset)))),
+            IndexCodec::MultihashIndexSorted => {
+                let mut codes = BTreeMap::<u64, Vec<_>>::new();
+                for (mh, offset) in &entries {
+                    codes.entry(mh.code()).or_default().push((mh.digest(), *offset));
+                }
+                Self::MultihashIndexSorted(codes.into_iter().map(|(code, entries)| (code, Buckets::build(entries.into_iter()))).collect())
+            },
+        }
+    }
+
+    pub fn codec(&self) -> IndexCodec {
+        match self {
+            Self::IndexSorted(_) => IndexCodec::IndexSorted,
+            Self::MultihashIndexSorted(_) => IndexCodec::MultihashIndexSorted,
+        }
+    }
+
+    /// Offset of the section holding the multihash. [CarIndex::IndexSorted] matches on the digest alone.
+    pub fn get(&self, mh: &Multihash<64>) -> Option<u64> {
+        match self {
+            Self::IndexSorted(buckets) => buckets.get(mh.digest()),
+            Self::MultihashIndexSorted(codes) => codes.get(&mh.code())?.get(mh.digest()),
+        }
+    }
+
+    /// Read an index, following its codec.
+    pub fn from_bytes(codec: IndexCodec, mut bytes: &[u8]) -> Result<Self, CarError> {
+        let r = &mut bytes;
+        let index = match codec {
+            IndexCodec::IndexSorted => Self::IndexSorted(Buckets::read(r)?),
+            IndexCodec::MultihashIndexSorted => {
+                let mut codes = BTreeMap::new();
+                for _ in 0..read_u32(r)? {
+                    let code = read_u64(r)?;
+                    if codes.insert(code, Buckets::read(r)?).is_some() {
+                        return Err(CarError::Malformed("duplicate multihash code in index"));
+                    }
+                }
+                Self::MultihashIndexSorted(codes)
+            },
+        };
+        if !r.is_empty() {
+            return Err(CarError::Malformed("trailing data after index"));
+        }
+        Ok(index)
+    }
+
+    /// Write the index, without its codec.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            Self::IndexSorted(buckets) => buckets.write(&mut buf),
+            Self::MultihashIndexSorted(codes) => {
+                buf.extend((codes.len() as u32).to_le_bytes());
+                for (code, buckets) in codes {
+                    buf.extend(code.to_le_bytes());
+                    buckets.write(&mut buf);
+                }
+            },
+        }
+        buf
+    }
+}
+
+fn read_u32(r: &mut &[u8]) -> Result<u32, CarError> {
+    let (bytes, rest) = r.split_first_chunk().ok_or(CarError::Malformed("index is truncated"))?;
+    *r = rest;
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+fn read_u64(r: &mut &[u8]) -> Result<u64, CarError> {
+    let (bytes, rest) = r.split_first_chunk().ok_or(CarError::Malformed("index is truncated"))?;
+    *r = rest;
+    Ok(u64::from_le_bytes(*bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use multihash_codetable::{Code, MultihashDigest};
+
+    use super::*;
+
+    #[test]
+    fn test_car_index() {
+        let a = Code::Sha2_256.digest(b"a");
+        let b = Code::Sha2_256.digest(b"b");
+        let c = Code::Sha2_512.digest(b"c");
+        let identity = Multihash::<64>::wrap(0x00, b"d").unwrap();
+        for codec in [IndexCodec::IndexSorted, IndexCodec::MultihashIndexSorted] {
+            let index = CarIndex::build(codec, [(b, 10), (a, 20), (c, 30), (identity, 40)]);
+            assert_eq!(index.codec(), codec);
+            assert_eq!([a, b, c, identity].map(|mh| index.get(&mh)), [Some(20), Some(10), Some(30), Some(40)]);
+            assert_eq!(index.get(&Code::Sha2_256.digest(b"e")), None);
+            assert_eq!(CarIndex::from_bytes(codec, &index.to_bytes()).unwrap(), index);
+        }
+        // The empty identity block has an empty digest.
+        let empty = Multihash::<64>::wrap(0x00, b"").unwrap();
+        for codec in [IndexCodec::IndexSorted, IndexCodec::MultihashIndexSorted] {
+            let index = CarIndex::from_bytes(codec, &CarIndex::build(codec, [(a, 20), (empty, 50)]).to_bytes()).unwrap();
+            assert_eq!(index.get(&empty), Some(50));
+        }
+        // IndexSorted matches on the digest alone.
+        let other = Multihash::<64>::wrap(0x1e, a.digest()).unwrap();
+        assert_eq!(CarIndex::build(IndexCodec::IndexSorted, [(a, 20)]).get(&other), Some(20));
+        assert_eq!(CarIndex::build(IndexCodec::MultihashIndexSorted, [(a, 20)]).get(&other), None);
+
+        // unsorted bucket
+        let mut bytes = CarIndex::build(IndexCodec::IndexSorted, [(a, 20), (b, 10)]).to_bytes();
+        let width = a.digest().len() + 8;
+        let (first, second) = bytes[16..].split_at_mut(width);
+        first.swap_with_slice(second);
+        assert!(CarIndex::from_bytes(IndexCodec::IndexSorted, &bytes).is_err());
+        assert!(CarIndex::from_bytes(IndexCodec::IndexSorted, &bytes[..bytes.len() - 1]).is_err());
+    }
+}
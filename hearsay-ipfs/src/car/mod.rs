@@ -0,0 +1,181 @@
+//! Content Addressable aRchives, for moving blocks between nodes as a single file.
+//! See <https://ipld.io/specs/transport/car/carv1/> and <https://ipld.io/specs/transport/car/carv2/>.
+
+use std::io;
+
+use cid::Cid;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{ipld::{Codec, CodecError, DagCbor, Ipld}, Block, BlockError};
+
+mod index;
+mod reader;
+mod writer;
+pub use index::{CarIndex, IndexCodec};
+pub use reader::CarReader;
+pub use writer::{CarV2Writer, CarWriter};
+
+#[derive(Debug, Error)]
+pub enum CarError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    Block(#[from] BlockError),
+    #[error("CAR version {0} is not supported")]
+    UnsupportedVersion(u64),
+    #[error("invalid CAR: {0}")]
+    Malformed(&'static str),
+    #[error("CAR section of {len} bytes exceeds the {limit} byte limit")]
+    TooLarge {
+        len: u64,
+        limit: u64,
+    },
+}
+
+/// Header at the start of a CARv1, naming the roots of the DAGs it holds.
+#[derive(Clone, Debug, PartialEq, DagCbor)]
+pub struct CarHeader {
+    pub version: u64,
+    pub roots: Vec<Cid>,
+}
+
+impl CarHeader {
+    pub fn v1(roots: Vec<Cid>) -> Self {
+        Self { version: 1, roots }
+    }
+}
+
+/// Header following the [PRAGMA] of a CARv2, locating the CARv1 data payload and the index.
+/// Offsets are from the start of the CARv2.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CarV2Header {
+    /// Every block, including inline ones, is in the index.
+    pub fully_indexed: bool,
+    pub data_offset: u64,
+    pub data_size: u64,
+    /// Zero when there is no index.
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    pub const LEN: usize = 40;
+
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        Self {
+            // The characteristics bitfield is two little-endian u64s, and "fully indexed" is the top bit of the first.
+            fully_indexed: bytes[7] & 0x80 != 0,
+            data_offset: u64_at(16),
+            data_size: u64_at(24),
+            index_offset: u64_at(32),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        if self.fully_indexed {
+            bytes[7] = 0x80;
+        }
+        bytes[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes
+    }
+}
+
+/// Fixed start of a CARv2: a length-prefixed CARv1 header of `{"version": 2}`, which CARv1-only readers reject.
+pub const PRAGMA: [u8; 11] = [0x0a, 0xa1, 0x67, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0x02];
+
+/// Upper bound on the length of a header.
+const MAX_HEADER_LEN: u64 = 1 << 20;
+
+/// Upper bound on the length of a [Cid] in a section, over the block data.
+const MAX_CID_LEN: u64 = 128;
+
+/// Read an [unsigned varint](https://github.com/multiformats/unsigned-varint), or `None` at the end of the data.
+pub(crate) async fn read_varint<R: AsyncRead + Unpin>(r: &mut R) -> Result<Option<u64>, CarError> {
+    let mut value = 0u64;
+    // The spec caps varints at 9 bytes.
+    for i in 0..9 {
+        let mut byte = [0u8];
+        if r.read(&mut byte).await.map_err(|e| CarError::Io(e))? == 0 {
+            return match i {
+                0 => Ok(None),
+                _ => Err(CarError::Io(io::ErrorKind::UnexpectedEof.into())),
+            };
+        }
+        value |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            if byte[0] == 0 && i > 0 {
+                return Err(CarError::Malformed("varint is not minimal"));
+            }
+            return Ok(Some(value));
+        }
+    }
+    Err(CarError::Malformed("varint is too long"))
+}
+
+async fn write_varint<W: AsyncWrite + Unpin>(w: &mut W, mut value: u64) -> Result<(), CarError> {
+    let mut buf = Vec::with_capacity(9);
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+    w.write_all(&buf).await.map_err(|e| CarError::Io(e))
+}
+
+/// Decode a CARv1 header, which must be version 1.
+/// The version is checked first, as other versions need not have roots.
+fn decode_header(buf: &[u8]) -> Result<CarHeader, CarError> {
+    let c = DagCbor::default();
+    let Ipld::Map(map) = c.decode_from_slice(buf)? else {
+        return Err(CarError::Malformed("header is not a map"));
+    };
+    match map.get("version") {
+        Some(Ipld::Integer(1)) => {},
+        Some(&Ipld::Integer(version)) => return Err(CarError::UnsupportedVersion(version.try_into().unwrap_or(u64::MAX))),
+        _ => return Err(CarError::Malformed("header has no version")),
+    }
+    Ok(c.decode_from_slice(buf)?)
+}
+
+/// Write a length-prefixed section, returning its length including the prefix.
+async fn write_section<W: AsyncWrite + Unpin>(w: &mut W, parts: &[&[u8]]) -> Result<u64, CarError> {
+    let len = parts.iter().map(|p| p.len() as u64).sum::<u64>();
+    let mut prefix = Vec::with_capacity(9);
+    write_varint(&mut prefix, len).await?;
+    w.write_all(&prefix).await.map_err(|e| CarError::Io(e))?;
+    for part in parts {
+        w.write_all(part).await.map_err(|e| CarError::Io(e))?;
+    }
+    Ok(prefix.len() as u64 + len)
+}
+
+async fn write_header<W: AsyncWrite + Unpin>(w: &mut W, header: &CarHeader) -> Result<u64, CarError> {
+    let buf = DagCbor::default().encode_to_vec(header)?;
+    write_section(w, &[&buf]).await
+}
+
+async fn write_block<W: AsyncWrite + Unpin>(w: &mut W, block: &Block) -> Result<u64, CarError> {
+    write_section(w, &[&block.cid().to_bytes(), block.data()]).await
+}
+
+/// Read a length-prefixed section into a buffer, or `None` at the end of the data.
+pub(crate) async fn read_section<R: AsyncRead + Unpin>(r: &mut R, limit: u64) -> Result<Option<Vec<u8>>, CarError> {
+    let Some(len) = read_varint(r).await? else {
+        return Ok(None);
+    };
+    if len > limit {
+        return Err(CarError::TooLarge { len, limit });
+    }
+    let mut buf = vec![];
+    let read = r.take(len).read_to_end(&mut buf).await.map_err(|e| CarError::Io(e))?;
+    if read as u64 != len {
+        return Err(CarError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(Some(buf))
+}
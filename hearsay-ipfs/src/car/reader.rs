@@ -0,0 +1,76 @@
+use bytes::Bytes;
+use cid::Cid;
+use tokio::io::{self, AsyncRead, AsyncReadExt, Take};
+
+use crate::{hasher::HasherRegistry, Block, BlockLimits};
+
+use super::{decode_header, read_section, CarError, CarHeader, CarV2Header, MAX_CID_LEN, MAX_HEADER_LEN, PRAGMA};
+
+/// Streaming CAR reader, yielding blocks checked against their [Cid]s.
+/// A CARv2 is read through its data payload, and its index is ignored.
+pub struct CarReader<R> {
+    /// Limited to the data payload of a CARv2.
+    r: Take<R>,
+    header: CarHeader,
+    v2_header: Option<CarV2Header>,
+    hashers: HasherRegistry,
+    limits: BlockLimits,
+}
+
+impl<R: AsyncRead + Unpin> CarReader<R> {
+    /// Read the header, to check blocks with the built-in hashers and the default [BlockLimits].
+    pub async fn new(r: R) -> Result<Self, CarError> {
+        Self::new_with(HasherRegistry::builtin().clone(), BlockLimits::default(), r).await
+    }
+
+    /// Read the header. Blocks over the hard [BlockLimits] are rejected.
+    pub async fn new_with(hashers: HasherRegistry, limits: BlockLimits, r: R) -> Result<Self, CarError> {
+        let mut r = r.take(u64::MAX);
+        let mut buf = read_section(&mut r, MAX_HEADER_LEN).await?.ok_or(CarError::Malformed("missing header"))?;
+        let mut v2_header = None;
+        if buf == PRAGMA[1..] {
+            let mut bytes = [0u8; CarV2Header::LEN];
+            r.read_exact(&mut bytes).await.map_err(|e| CarError::Io(e))?;
+            let header = CarV2Header::from_bytes(&bytes);
+            let skip = header.data_offset.checked_sub((PRAGMA.len() + CarV2Header::LEN) as u64)
+                .ok_or(CarError::Malformed("data payload overlaps the CARv2 header"))?;
+            io::copy(&mut (&mut r).take(skip), &mut io::sink()).await.map_err(|e| CarError::Io(e))?;
+            r.set_limit(header.data_size);
+            buf = read_section(&mut r, MAX_HEADER_LEN).await?.ok_or(CarError::Malformed("missing header"))?;
+            v2_header = Some(header);
+        }
+        let header = decode_header(&buf)?;
+        Ok(Self { r, header, v2_header, hashers, limits })
+    }
+
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// Header of a CARv2, or `None` for a CARv1.
+    pub fn v2_header(&self) -> Option<&CarV2Header> {
+        self.v2_header.as_ref()
+    }
+
+    pub fn roots(&self) -> &[Cid] {
+        &self.header.roots
+    }
+
+    pub fn into_inner(self) -> R {
+        self.r.into_inner()
+    }
+
+    /// Read and check the next block, or `None` at the end of the archive.
+    pub async fn next_block(&mut self) -> Result<Option<Block>, CarError> {
+        let limit = self.limits.hard as u64 + MAX_CID_LEN;
+        let Some(buf) = read_section(&mut self.r, limit).await? else {
+            return Ok(None);
+        };
+        let mut rest = &buf[..];
+        let cid = Cid::read_bytes(&mut rest).map_err(|e| CarError::Block(e.into()))?;
+        self.limits.check(rest.len())?;
+        let offset = buf.len() - rest.len();
+        let data = Bytes::from(buf).slice(offset..);
+        Ok(Some(Block::new_with(&self.hashers, cid, data)?))
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::SeekFrom;
+
+use cid::multihash::Multihash;
+use tokio::io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Block;
+
+use super::{write_block, write_header, write_varint, CarError, CarHeader, CarIndex, CarV2Header, IndexCodec, PRAGMA};
+
+/// CARv1 writer. Blocks are written in the order given.
+pub struct CarWriter<W> {
+    w: W,
+}
+
+impl<W: AsyncWrite + Unpin> CarWriter<W> {
+    /// Write the header.
+    pub async fn new(header: &CarHeader, mut w: W) -> Result<Self, CarError> {
+        write_header(&mut w, header).await?;
+        Ok(Self { w })
+    }
+
+    pub async fn write(&mut self, block: &Block) -> Result<(), CarError> {
+        write_block(&mut self.w, block).await?;
+        Ok(())
+    }
+
+    /// Flush and return the inner writer.
+    pub async fn finish(mut self) -> Result<W, CarError> {
+        self.w.flush().await.map_err(|e| CarError::Io(e))?;
+        Ok(self.w)
+    }
+}
+
+/// CARv2 writer, indexing every block written.
+/// The [CarV2Header] is only known once the data payload is complete, so [finish](Self::finish) seeks back to write it.
+pub struct CarV2Writer<W> {
+    w: W,
+    /// Position of the [PRAGMA] in `w`.
+    start: u64,
+    /// Bytes of data payload written so far.
+    size: u64,
+    codec: IndexCodec,
+    entries: Vec<(Multihash<64>, u64)>,
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> CarV2Writer<W> {
+    /// Write the [PRAGMA] and the header of the data payload.
+    pub async fn new(header: &CarHeader, codec: IndexCodec, mut w: W) -> Result<Self, CarError> {
+        let start = w.stream_position().await.map_err(|e| CarError::Io(e))?;
+        w.write_all(&PRAGMA).await.map_err(|e| CarError::Io(e))?;
+        w.write_all(&[0; CarV2Header::LEN]).await.map_err(|e| CarError::Io(e))?;
+        let size = write_header(&mut w, header).await?;
+        Ok(Self { w, start, size, codec, entries: vec![] })
+    }
+
+    pub async fn write(&mut self, block: &Block) -> Result<(), CarError> {
+        self.entries.push((*block.cid().hash(), self.size));
+        self.size += write_block(&mut self.w, block).await?;
+        Ok(())
+    }
+
+    /// Write the index and the [CarV2Header], then flush and return the inner writer positioned at the end.
+    pub async fn finish(mut self) -> Result<W, CarError> {
+        let data_offset = (PRAGMA.len() + CarV2Header::LEN) as u64;
+        let index = CarIndex::build(self.codec, self.entries);
+        write_varint(&mut self.w, self.codec.code()).await?;
+        self.w.write_all(&index.to_bytes()).await.map_err(|e| CarError::Io(e))?;
+        let end = self.w.stream_position().await.map_err(|e| CarError::Io(e))?;
+
+        let header = CarV2Header {
+            fully_indexed: true,
+            data_offset,
+            data_size: self.size,
+            index_offset: data_offset + self.size,
+        };
+        self.w.seek(SeekFrom::Start(self.start + PRAGMA.len() as u64)).await.map_err(|e| CarError::Io(e))?;
+        self.w.write_all(&header.to_bytes()).await.map_err(|e| CarError::Io(e))?;
+        self.w.seek(SeekFrom::Start(end)).await.map_err(|e| CarError::Io(e))?;
+        self.w.flush().await.map_err(|e| CarError::Io(e))?;
+        Ok(self.w)
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt::Debug;
 use libp2p::{futures::channel::mpsc, identity::Keypair, kad, swarm::NetworkBehaviour, Multiaddr, StreamProtocol};
 use tokio_util::sync::CancellationToken;
 
-use crate::{p2p::create_swarm, repo::Repository, task::{IpfsHandler, IpfsTask}, Ipfs};
+use crate::{hasher::HasherRegistry, ipld::CodecRegistry, p2p::create_swarm, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::MemPinStore, RepoLimits, Repository}, task::{IpfsHandler, IpfsTask}, Ipfs};
 
 /// Uninitiallized IPFS configuration.
 pub struct IpfsConfig<C> 
@@ -16,7 +16,13 @@ where
     pub bootstrap: Vec<Multiaddr>,
     pub kad_config: kad::Config,
     /// custom [NetworkBehaviour] 
-    pub custom: Option<C>
+    pub custom: Option<C>,
+    /// block size and decoding limits
+    pub limits: RepoLimits,
+    /// hashers for building and verifying blocks, beyond the built-in ones
+    pub hashers: HasherRegistry,
+    /// codecs for decoding blocks and following their links, beyond the built-in ones
+    pub codecs: CodecRegistry,
 }
 
 impl<C> IpfsConfig<C> 
@@ -30,13 +36,21 @@ where
             bootstrap: vec![],
             kad_config: kad::Config::new(StreamProtocol::new("/test")), // TODO: change protocol name
             custom: None,
+            limits: RepoLimits::default(),
+            hashers: HasherRegistry::default(),
+            codecs: CodecRegistry::default(),
         }
     }
 
     /// Spawns IPFS background task.
     /// Returns [Ipfs] facade. 
     pub async fn start(self) -> Result<Ipfs, Box<dyn std::error::Error>> {
-        let repo: Repository = todo!(); // TODO: init repo
+        // TODO: persistent stores
+        let repo = Repository::builder(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new())
+            .with_limits(self.limits)
+            .with_hashers(self.hashers.clone())
+            .with_codecs(self.codecs.clone())
+            .build();
 
         let swarm = create_swarm(self).await?;
 
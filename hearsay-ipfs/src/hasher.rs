@@ -0,0 +1,112 @@
+//! Multihash hash functions used to build and verify [cid::Cid]s.
+//! See <https://github.com/multiformats/multihash>.
+
+use std::{collections::HashMap, fmt, sync::{Arc, LazyLock}};
+
+use cid::multihash::Multihash;
+use multihash_codetable::{Code, Sha1};
+use multihash_derive::{Hasher, MultihashDigest};
+
+use crate::block::{BlockError, IDENTITY};
+
+/// Multihash code of SHA-1. Left out of the [Code] table as it is broken, but git names objects by it.
+pub const SHA1: u64 = 0x11;
+
+/// A hash function producing multihash digests.
+pub trait MultihashHasher: Send + Sync {
+    /// Hash the data, returning the raw digest (at most 64 bytes).
+    fn digest(&self, data: &[u8]) -> Vec<u8>;
+}
+
+impl<F> MultihashHasher for F
+where
+    F: Fn(&[u8]) -> Vec<u8> + Send + Sync,
+{
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        self(data)
+    }
+}
+
+static BUILTIN: LazyLock<HasherRegistry> = LazyLock::new(HasherRegistry::default);
+
+/// Multihash code to hasher lookup.
+/// Falls back to the identity hash and the [multihash_codetable::Code] table.
+#[derive(Clone, Default)]
+pub struct HasherRegistry {
+    hashers: HashMap<u64, Arc<dyn MultihashHasher>>,
+}
+
+impl HasherRegistry {
+    /// Registry with only the built-in hashers.
+    pub fn builtin() -> &'static HasherRegistry {
+        &BUILTIN
+    }
+
+    /// Add a hasher for the multihash code, replacing any built-in one.
+    pub fn register(&mut self, code: u64, hasher: impl MultihashHasher + 'static) -> &mut Self {
+        self.hashers.insert(code, Arc::new(hasher));
+        self
+    }
+
+    pub fn supports(&self, code: u64) -> bool {
+        code == IDENTITY || code == SHA1 || self.hashers.contains_key(&code) || Code::try_from(code).is_ok()
+    }
+
+    /// Hash the data with the hasher registered for the multihash code.
+    pub fn digest(&self, code: u64, data: &[u8]) -> Result<Multihash<64>, BlockError> {
+        if let Some(hasher) = self.hashers.get(&code) {
+            let digest = hasher.digest(data);
+            return Multihash::wrap(code, &digest).map_err(|_| BlockError::DigestTooLarge(digest.len()));
+        }
+        if code == IDENTITY {
+            return Multihash::wrap(IDENTITY, data).map_err(|_| BlockError::InlineTooLarge(data.len()));
+        }
+        if code == SHA1 {
+            let mut hasher = Sha1::default();
+            hasher.update(data);
+            return Ok(Multihash::wrap(SHA1, hasher.finalize()).unwrap());
+        }
+        match Code::try_from(code) {
+            Ok(code) => Ok(code.digest(data)),
+            Err(_) => Err(BlockError::UnsupportedHash(code)),
+        }
+    }
+}
+
+impl fmt::Debug for HasherRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HasherRegistry")
+            .field("custom", &self.hashers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Toy hash: the data xor-folded into 8 bytes.
+    pub(crate) fn xor_fold(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0u8; 8];
+        data.iter().enumerate().for_each(|(i, b)| out[i % 8] ^= b);
+        out
+    }
+
+    #[test]
+    fn test_hasher_registry() {
+        let mut hashers = HasherRegistry::default();
+        assert!(hashers.supports(0x12));
+        assert!(hashers.supports(IDENTITY));
+        assert!(!hashers.supports(0x300000));
+        assert!(matches!(hashers.digest(0x300000, b"banana"), Err(BlockError::UnsupportedHash(0x300000))));
+
+        hashers.register(0x300000, xor_fold);
+        let hash = hashers.digest(0x300000, b"banana").unwrap();
+        assert_eq!(hash.code(), 0x300000);
+        assert_eq!(hash.digest(), b"banana\0\0");
+        assert_eq!(hashers.digest(0x12, b"banana").unwrap(), Code::Sha2_256.digest(b"banana"));
+
+        hashers.register(0x300001, |_: &[u8]| vec![0; 65]);
+        assert!(matches!(hashers.digest(0x300001, b"banana"), Err(BlockError::DigestTooLarge(65))));
+    }
+}
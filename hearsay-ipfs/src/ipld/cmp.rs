@@ -0,0 +1,149 @@
+//! Canonical total ordering of [Ipld], matching the byte order of the DAG-CBOR encoding.
+
+use std::{cmp::Ordering, hash::{Hash, Hasher}};
+
+use super::Ipld;
+
+impl Ipld {
+    /// Whether both values have the same DAG-CBOR encoding, which is also what `==` checks.
+    ///
+    /// `Integer(1)` and `Float(1.0)` are different, as are `Float(0.0)` and `Float(-0.0)`. Floats
+    /// are compared by their bits, so a NaN is equal to itself even though no codec will encode it.
+    pub fn canonical_eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+
+    /// Sort position by the first byte of the encoding, see [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-3).
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Integer(0..) => 0x00,
+            Self::Integer(_) => 0x20,
+            Self::Bytes(_) => 0x40,
+            Self::String(_) => 0x60,
+            Self::List(_) => 0x80,
+            Self::Map(_) => 0xa0,
+            Self::Link(_) => 0xc0,
+            Self::Bool(false) => 0xf4,
+            Self::Bool(true) => 0xf5,
+            Self::Null => 0xf6,
+            Self::Float(_) => 0xfb,
+        }
+    }
+}
+
+/// Length-first order of strings and bytes, see [RFC 7049](https://datatracker.ietf.org/doc/html/rfc7049#section-3.9).
+fn cmp_bytes(a: &[u8], b: &[u8]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+impl Ord for Ipld {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            // Negative integers are encoded as -1 - n, so their order is reversed.
+            (Self::Integer(a @ 0..), Self::Integer(b @ 0..)) => a.cmp(b),
+            (Self::Integer(a @ ..0), Self::Integer(b @ ..0)) => b.cmp(a),
+            (Self::Float(a), Self::Float(b)) => a.to_bits().cmp(&b.to_bits()),
+            (Self::String(a), Self::String(b)) => cmp_bytes(a.as_bytes(), b.as_bytes()),
+            (Self::Bytes(a), Self::Bytes(b)) => cmp_bytes(a, b),
+            (Self::Link(a), Self::Link(b)) => cmp_bytes(&a.to_bytes(), &b.to_bytes()),
+            (Self::List(a), Self::List(b)) => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+            (Self::Map(a), Self::Map(b)) => a.len().cmp(&b.len()).then_with(|| {
+                let order = |map| {
+                    let mut entries = Vec::from_iter(map);
+                    entries.sort_unstable_by(|(a, _): &(&String, _), (b, _)| cmp_bytes(a.as_bytes(), b.as_bytes()));
+                    entries
+                };
+                let (a, b) = (order(a), order(b));
+                a.iter()
+                    .zip(&b)
+                    .map(|((ka, va), (kb, vb))| cmp_bytes(ka.as_bytes(), kb.as_bytes()).then_with(|| va.cmp(vb)))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            }),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Ipld {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ipld {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Null, Self::Null) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            (Self::Link(a), Self::Link(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Ipld {}
+
+impl Hash for Ipld {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Self::Null | Self::Bool(_) => {},
+            Self::Integer(i) => i.hash(state),
+            Self::Float(f) => f.to_bits().hash(state),
+            Self::String(s) => s.hash(state),
+            Self::Bytes(b) => b.hash(state),
+            Self::List(l) => l.hash(state),
+            Self::Map(m) => m.hash(state),
+            Self::Link(cid) => Hash::hash(cid, state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bytes::Bytes;
+    use cid::Cid;
+
+    use crate::{ipld, ipld::{Codec, DagCbor}};
+
+    #[test]
+    fn test_ipld_order() {
+        let c = DagCbor::default();
+        let mut values = vec![
+            ipld!(null), ipld!(true), ipld!(false), ipld!(0), ipld!(24), ipld!(1000), ipld!(-1), ipld!(-2), ipld!(-1000),
+            ipld!(0.5), ipld!(-0.5), ipld!(1.0), ipld!(""), ipld!("b"), ipld!("aa"), ipld!(b"a"), ipld!(b""),
+            ipld!([]), ipld!([1]), ipld!([0, 1]), ipld!([-1]), ipld!({}), ipld!({ "b": 1 }), ipld!({ "aa": 0 }),
+            ipld!({ "b": 1, "aa": 0 }), ipld!({ "b": 0, "aa": 0 }), ipld!(Cid::default()),
+        ];
+        let mut encoded = values.iter().map(|v| (c.encode_to_vec(v).unwrap(), v.clone())).collect::<Vec<_>>();
+        values.sort();
+        encoded.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(values, encoded.into_iter().map(|(_, v)| v).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ipld_eq_hash() {
+        assert!(!ipld!(1).canonical_eq(&ipld!(1.0)));
+        assert_ne!(ipld!(0.0), ipld!(-0.0));
+        assert_eq!(ipld!(f64::NAN), ipld!(f64::NAN));
+        assert!(ipld!(1) < ipld!(1.0));
+
+        let records = [
+            ipld!({ "name": "banana", "tags": ["a", "b"] }),
+            ipld!({ "tags": ["a", "b"], "name": "banana" }),
+            ipld!({ "name": "banana", "tags": ["b", "a"] }),
+            ipld!(Bytes::from_static(b"banana")),
+            ipld!(b"banana"),
+        ];
+        assert_eq!(records.iter().collect::<HashSet<_>>().len(), 3);
+    }
+}
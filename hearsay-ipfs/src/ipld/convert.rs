@@ -0,0 +1,220 @@
+//! Conversions out of [Ipld], borrowing accessors and indexing.
+
+use std::{any::type_name, collections::BTreeMap, ops::Index};
+
+use bytes::Bytes;
+use cid::Cid;
+
+use super::{Ipld, IpldError, IpldKind};
+
+static NULL: Ipld = Ipld::Null;
+
+fn bad_conversion(expected: IpldKind, found: &Ipld) -> IpldError {
+    IpldError::BadConversion {
+        expected,
+        found: found.kind(),
+    }
+}
+
+/// Implement `TryFrom<Ipld>` for the type and its [Option], where null converts to [None].
+/// `Option<T>` can not be implemented generically, it overlaps the blanket impl for `Option<Ipld>`.
+macro_rules! impl_try_from {
+    ($({$($generics:tt)*})? $t:ty $(where {$($bounds:tt)*})?, $value:ident => $body:expr) => {
+        impl$(<$($generics)*>)? TryFrom<Ipld> for $t $(where $($bounds)*)? {
+            type Error = IpldError;
+            fn try_from($value: Ipld) -> Result<Self, Self::Error> {
+                $body
+            }
+        }
+
+        impl$(<$($generics)*>)? TryFrom<Ipld> for Option<$t> $(where $($bounds)*)? {
+            type Error = IpldError;
+            fn try_from(value: Ipld) -> Result<Self, Self::Error> {
+                match value {
+                    Ipld::Null => Ok(None),
+                    value => <$t>::try_from(value).map(Some),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_try_from_int {
+    ($($t:ty),*) => {
+        $(impl_try_from!($t, value => match value {
+            Ipld::Integer(i) => <$t>::try_from(i).map_err(|_| IpldError::InvalidKind {
+                from: IpldKind::Integer,
+                into: type_name::<$t>(),
+            }),
+            other => Err(bad_conversion(IpldKind::Integer, &other)),
+        });)*
+    };
+}
+
+impl_try_from_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl_try_from!(bool, value => match value {
+    Ipld::Bool(b) => Ok(b),
+    other => Err(bad_conversion(IpldKind::Bool, &other)),
+});
+
+impl_try_from!(f64, value => match value {
+    Ipld::Float(f) => Ok(f),
+    other => Err(bad_conversion(IpldKind::Float, &other)),
+});
+
+impl_try_from!(f32, value => match value {
+    // Only if no precision is lost.
+    Ipld::Float(f) if f64::from(f as f32) == f => Ok(f as f32),
+    Ipld::Float(_) => Err(IpldError::InvalidKind {
+        from: IpldKind::Float,
+        into: type_name::<f32>(),
+    }),
+    other => Err(bad_conversion(IpldKind::Float, &other)),
+});
+
+impl_try_from!(String, value => match value {
+    Ipld::String(s) => Ok(s),
+    other => Err(bad_conversion(IpldKind::String, &other)),
+});
+
+impl_try_from!(Bytes, value => match value {
+    Ipld::Bytes(b) => Ok(b),
+    other => Err(bad_conversion(IpldKind::Bytes, &other)),
+});
+
+impl_try_from!(Cid, value => match value {
+    Ipld::Link(cid) => Ok(cid),
+    other => Err(bad_conversion(IpldKind::Link, &other)),
+});
+
+impl_try_from!({T: TryFrom<Ipld>} Vec<T> where {IpldError: From<T::Error>}, value => match value {
+    Ipld::List(list) => list.into_iter().map(|item| Ok(T::try_from(item)?)).collect(),
+    other => Err(bad_conversion(IpldKind::List, &other)),
+});
+
+impl_try_from!({T: TryFrom<Ipld>} BTreeMap<String, T> where {IpldError: From<T::Error>}, value => match value {
+    Ipld::Map(map) => map.into_iter().map(|(k, v)| Ok((k, T::try_from(v)?))).collect(),
+    other => Err(bad_conversion(IpldKind::Map, &other)),
+});
+
+/// Implement `TryFrom<Ipld>` for tuples, from lists of the same length.
+macro_rules! impl_try_from_tuple {
+    ($len:literal: $($t:ident),+) => {
+        impl<$($t),+> TryFrom<Ipld> for ($($t,)+)
+        where
+            $($t: TryFrom<Ipld>, IpldError: From<$t::Error>,)+
+        {
+            type Error = IpldError;
+            fn try_from(value: Ipld) -> Result<Self, Self::Error> {
+                let list = match value {
+                    Ipld::List(list) if list.len() == $len => list,
+                    Ipld::List(list) => return Err(IpldError::BadLength { expected: $len, found: list.len() }),
+                    other => return Err(bad_conversion(IpldKind::List, &other)),
+                };
+                let mut items = list.into_iter();
+                Ok(($($t::try_from(items.next().expect("length checked"))?,)+))
+            }
+        }
+    };
+}
+
+impl_try_from_tuple!(1: A);
+impl_try_from_tuple!(2: A, B);
+impl_try_from_tuple!(3: A, B, C);
+impl_try_from_tuple!(4: A, B, C, D);
+
+impl Ipld {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            Self::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Ipld]> {
+        match self {
+            Self::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Ipld>> {
+        match self {
+            Self::Map(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    pub fn as_link(&self) -> Option<&Cid> {
+        match self {
+            Self::Link(cid) => Some(cid),
+            _ => None,
+        }
+    }
+}
+
+/// Value of a map entry, or [Ipld::Null] if the key is missing or this is not a map.
+impl Index<&str> for Ipld {
+    type Output = Ipld;
+    fn index(&self, key: &str) -> &Ipld {
+        self.as_map().and_then(|m| m.get(key)).unwrap_or(&NULL)
+    }
+}
+
+/// Item of a list, or [Ipld::Null] if out of bounds or this is not a list.
+impl Index<usize> for Ipld {
+    type Output = Ipld;
+    fn index(&self, index: usize) -> &Ipld {
+        self.as_list().and_then(|l| l.get(index)).unwrap_or(&NULL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipld;
+
+    use super::*;
+
+    #[test]
+    fn test_ipld_try_from() {
+        assert_eq!(u8::try_from(ipld!(255)).unwrap(), 255);
+        assert!(matches!(u8::try_from(ipld!(256)), Err(IpldError::InvalidKind { from: IpldKind::Integer, into: "u8" })));
+        assert!(matches!(u64::try_from(ipld!(-1)), Err(IpldError::InvalidKind { .. })));
+        assert_eq!(i64::try_from(ipld!(i64::MIN)).unwrap(), i64::MIN);
+        assert!(matches!(i64::try_from(ipld!("1")), Err(IpldError::BadConversion { expected: IpldKind::Integer, found: IpldKind::String })));
+        assert_eq!(f32::try_from(ipld!(0.5)).unwrap(), 0.5);
+        assert!(f32::try_from(ipld!(0.1)).is_err());
+        assert_eq!(String::try_from(ipld!("banana")).unwrap(), "banana");
+        assert_eq!(Bytes::try_from(ipld!(b"banana")).unwrap(), Bytes::from_static(b"banana"));
+        assert_eq!(Cid::try_from(ipld!(Cid::default())).unwrap(), Cid::default());
+
+        assert_eq!(Option::<u8>::try_from(ipld!(null)).unwrap(), None);
+        assert_eq!(Option::<u8>::try_from(ipld!(1)).unwrap(), Some(1));
+        assert_eq!(Vec::<u8>::try_from(ipld!([1, 2])).unwrap(), [1, 2]);
+        assert!(Vec::<u8>::try_from(ipld!([1, "2"])).is_err());
+        assert_eq!(Vec::<Ipld>::try_from(ipld!([1, "2"])).unwrap(), [ipld!(1), ipld!("2")]);
+        assert_eq!(BTreeMap::<String, bool>::try_from(ipld!({ "a": true })).unwrap(), BTreeMap::from([(String::from("a"), true)]));
+        assert_eq!(<(String, Option<i32>)>::try_from(ipld!(["a", null])).unwrap(), (String::from("a"), None));
+        assert!(matches!(<(u8, u8)>::try_from(ipld!([1])), Err(IpldError::BadLength { expected: 2, found: 1 })));
+    }
+
+    #[test]
+    fn test_ipld_index() {
+        let cid = Cid::default();
+        let node = ipld!({ "list": [1, { "name": "banana" }], "link": cid });
+        assert_eq!(node["list"][1]["name"].as_str(), Some("banana"));
+        assert_eq!(node["list"].as_list().map(<[_]>::len), Some(2));
+        assert_eq!(node["link"].as_link(), Some(&cid));
+        assert!(node.as_map().is_some());
+        assert_eq!(node["missing"][3], Ipld::Null);
+        assert_eq!(node["list"]["name"], Ipld::Null);
+    }
+}
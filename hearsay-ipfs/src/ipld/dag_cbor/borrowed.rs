@@ -0,0 +1,371 @@
+//! Zero-copy decoding of DAG-CBOR into [IpldRef], borrowing strings and bytes from the input.
+
+use std::{fmt, str};
+
+use cid::Cid;
+
+use crate::ipld::{CodecError, DecodeLimits, Ipld, IpldKind};
+
+use super::{dec::{check_key, read_float, read_header, read_uint, Budget}, derive::skip_item, DagCbor, DecodeMode, Header, MajorType};
+
+/// [Ipld] borrowed from an encoded DAG-CBOR buffer.
+///
+/// Lists and maps are left encoded and decoded as they are iterated, so subtrees that are not
+/// needed are never copied. They are not skipped unread though: [DagCbor::decode_ref] checks the
+/// whole value up front, so reading it afterwards can not fail. Iterating a list or map scans each
+/// nested list or map again to find where it ends, so a value nested `d` deep is read `d` times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IpldRef<'a> {
+    Null,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    List(ListRef<'a>),
+    Map(MapRef<'a>),
+    Link(Cid),
+}
+
+/// Encoded list items of an [IpldRef::List].
+#[derive(Clone, Copy)]
+pub struct ListRef<'a> {
+    len: usize,
+    data: &'a [u8],
+}
+
+/// Encoded map entries of an [IpldRef::Map], in the order they were encoded.
+#[derive(Clone, Copy)]
+pub struct MapRef<'a> {
+    len: usize,
+    data: &'a [u8],
+}
+
+impl DagCbor {
+    /// Decode a data item without copying its strings and bytes, within the [DecodeLimits].
+    /// Indefinite-length items are rejected even when decoding leniently, as their strings are split into chunks.
+    pub fn decode_ref<'a>(&self, mut data: &'a [u8]) -> Result<IpldRef<'a>, CodecError> {
+        let value = read_ref(&mut data, Some(&mut Budget::new(self)))?;
+        if self.mode == DecodeMode::Strict && !data.is_empty() {
+            return Err(CodecError::MalformedData("trailing data"));
+        }
+        Ok(value)
+    }
+}
+
+/// Split `len` bytes off the front of the buffer.
+fn take<'a>(buf: &mut &'a [u8], len: u64) -> Result<&'a [u8], CodecError> {
+    let len = usize::try_from(len).map_err(|_| CodecError::NumberOutOfBounds)?;
+    if buf.len() < len {
+        return Err(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+/// Read a data item from the front of the buffer. Lists and maps are checked against the [Budget]
+/// if there is one, or otherwise trusted and skipped over.
+fn read_ref<'a>(buf: &mut &'a [u8], budget: Option<&mut Budget>) -> Result<IpldRef<'a>, CodecError> {
+    let mode = budget.as_deref().map_or(DecodeMode::Lenient, Budget::mode);
+    let header = read_header(buf).map_err(|e| CodecError::Io(e))?;
+    Ok(match header.major_type {
+        MajorType::PositiveInt => IpldRef::Integer(read_uint(buf, &header, mode)?.into()),
+        MajorType::NegativeInt => IpldRef::Integer(-1i128 - (read_uint(buf, &header, mode)? as i128)),
+        MajorType::ByteString => {
+            let len = read_uint(buf, &header, mode)?;
+            IpldRef::Bytes(take(buf, len)?)
+        },
+        MajorType::TextString => {
+            let len = read_uint(buf, &header, mode)?;
+            IpldRef::String(read_str(buf, len)?)
+        },
+        MajorType::Array => {
+            let len = read_uint(buf, &header, mode)?;
+            let start = *buf;
+            let len = match budget {
+                Some(budget) => {
+                    let len = budget.collection(len)?;
+                    budget.enter()?;
+                    for _ in 0..len {
+                        read_ref(buf, Some(budget))?;
+                    }
+                    budget.exit();
+                    len
+                },
+                None => skip_items(buf, len)?,
+            };
+            IpldRef::List(ListRef { len, data: &start[..start.len() - buf.len()] })
+        },
+        MajorType::Map => {
+            let len = read_uint(buf, &header, mode)?;
+            let start = *buf;
+            let len = match budget {
+                Some(budget) => {
+                    let len = budget.collection(len)?;
+                    budget.enter()?;
+                    let mut prev = None;
+                    let mut sorted = true;
+                    for _ in 0..len {
+                        let header = read_header(buf).map_err(|e| CodecError::Io(e))?;
+                        if header.major_type != MajorType::TextString {
+                            return Err(CodecError::MalformedData("map keys must be strings"));
+                        }
+                        let len = read_uint(buf, &header, mode)?;
+                        let key = read_str(buf, len)?;
+                        sorted &= check_key(prev, key, mode)?;
+                        prev = Some(key);
+                        read_ref(buf, Some(budget))?;
+                    }
+                    // Sorted keys can not repeat, but keys in any other order still must not.
+                    if !sorted {
+                        let entries = MapIter { remaining: len, data: &start[..start.len() - buf.len()] };
+                        let mut keys = entries.map(|(key, _)| key).collect::<Vec<_>>();
+                        keys.sort_unstable();
+                        if keys.windows(2).any(|w| w[0] == w[1]) {
+                            return Err(CodecError::MalformedData("duplicate map keys"));
+                        }
+                    }
+                    budget.exit();
+                    len
+                },
+                None => skip_items(buf, len * 2)? / 2,
+            };
+            IpldRef::Map(MapRef { len, data: &start[..start.len() - buf.len()] })
+        },
+        MajorType::Tag => {
+            if read_uint(buf, &header, mode)? != 42 {
+                return Err(CodecError::MalformedData("unknown tag"));
+            }
+            let header = read_header(buf).map_err(|e| CodecError::Io(e))?;
+            if header.major_type != MajorType::ByteString {
+                return Err(CodecError::MalformedData("unexpected major type"));
+            }
+            let len = read_uint(buf, &header, mode)?;
+            // 0x00 prefix to denote multibase CID
+            let Some((0, mut bytes)) = take(buf, len)?.split_first() else {
+                return Err(CodecError::MalformedData("invalid cid prefix"));
+            };
+            let cid = Cid::read_bytes(&mut bytes).map_err(|_| CodecError::MalformedData("invalid cid data"))?;
+            if !bytes.is_empty() {
+                return Err(CodecError::MalformedData("trailing cid data"));
+            }
+            IpldRef::Link(cid)
+        },
+        MajorType::Other => match header {
+            Header::NULL => IpldRef::Null,
+            Header::TRUE => IpldRef::Bool(true),
+            Header::FALSE => IpldRef::Bool(false),
+            Header::F64 | Header::_F32 | Header::_F16 => IpldRef::Float(read_float(buf, &header, mode)?),
+            _ => return Err(CodecError::MalformedData("unknown header type")),
+        },
+    })
+}
+
+fn read_str<'a>(buf: &mut &'a [u8], len: u64) -> Result<&'a str, CodecError> {
+    str::from_utf8(take(buf, len)?).map_err(|_| CodecError::MalformedData("bytes are not in utf-8 string format"))
+}
+
+/// Skip over items that have already been checked.
+fn skip_items(buf: &mut &[u8], len: u64) -> Result<usize, CodecError> {
+    let unlimited = DecodeLimits { max_depth: usize::MAX, max_collection_len: usize::MAX, max_alloc: usize::MAX };
+    let mut budget = Budget::new(&DagCbor::lenient(unlimited));
+    for _ in 0..len {
+        skip_item(buf, &mut budget)?;
+    }
+    usize::try_from(len).map_err(|_| CodecError::NumberOutOfBounds)
+}
+
+/// Read the next item of a checked [ListRef] or [MapRef].
+fn next_checked<'a>(buf: &mut &'a [u8]) -> IpldRef<'a> {
+    read_ref(buf, None).expect("checked by DagCbor::decode_ref")
+}
+
+impl<'a> IpldRef<'a> {
+    pub fn kind(&self) -> IpldKind {
+        match self {
+            Self::Null => IpldKind::Null,
+            Self::Bool(_) => IpldKind::Bool,
+            Self::Integer(_) => IpldKind::Integer,
+            Self::Float(_) => IpldKind::Float,
+            Self::String(_) => IpldKind::String,
+            Self::Bytes(_) => IpldKind::Bytes,
+            Self::List(_) => IpldKind::List,
+            Self::Map(_) => IpldKind::Map,
+            Self::Link(_) => IpldKind::Link,
+        }
+    }
+
+    /// Value of a map entry, or [None] if the key is missing or this is not a map.
+    pub fn get(&self, key: &str) -> Option<IpldRef<'a>> {
+        match self {
+            Self::Map(m) => m.get(key),
+            _ => None,
+        }
+    }
+
+    /// Copy into an owned [Ipld], decoding the whole tree.
+    pub fn to_ipld(&self) -> Ipld {
+        match *self {
+            Self::Null => Ipld::Null,
+            Self::Bool(b) => Ipld::Bool(b),
+            Self::Integer(i) => Ipld::Integer(i),
+            Self::Float(f) => Ipld::Float(f),
+            Self::String(s) => Ipld::String(s.to_owned()),
+            Self::Bytes(b) => Ipld::Bytes(b.to_vec().into()),
+            Self::List(l) => Ipld::List(l.iter().map(|v| v.to_ipld()).collect()),
+            Self::Map(m) => Ipld::Map(m.iter().map(|(k, v)| (k.to_owned(), v.to_ipld())).collect()),
+            Self::Link(cid) => Ipld::Link(cid),
+        }
+    }
+}
+
+impl<'a> ListRef<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<IpldRef<'a>> {
+        self.iter().nth(index)
+    }
+
+    pub fn iter(&self) -> ListIter<'a> {
+        ListIter { remaining: self.len, data: self.data }
+    }
+}
+
+impl<'a> MapRef<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Only the value of the matching entry is decoded.
+    pub fn get(&self, key: &str) -> Option<IpldRef<'a>> {
+        self.iter().find_map(|(k, v)| (k == key).then_some(v))
+    }
+
+    pub fn iter(&self) -> MapIter<'a> {
+        MapIter { remaining: self.len, data: self.data }
+    }
+}
+
+/// Items of a [ListRef], each decoded as it is reached.
+pub struct ListIter<'a> {
+    remaining: usize,
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = IpldRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.remaining = self.remaining.checked_sub(1)?;
+        Some(next_checked(&mut self.data))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// Entries of a [MapRef], each decoded as it is reached.
+pub struct MapIter<'a> {
+    remaining: usize,
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for MapIter<'a> {
+    type Item = (&'a str, IpldRef<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.remaining = self.remaining.checked_sub(1)?;
+        let IpldRef::String(key) = next_checked(&mut self.data) else {
+            unreachable!("map keys checked by DagCbor::decode_ref");
+        };
+        Some((key, next_checked(&mut self.data)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl fmt::Debug for ListRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Debug for MapRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for ListRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl PartialEq for MapRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+
+    use crate::{ipld, ipld::Codec};
+
+    use super::*;
+
+    #[test]
+    fn test_decode_ref() {
+        let c = DagCbor::default();
+        let cid = Cid::default();
+        let value = ipld!({ "name": "banana", "data": b"split", "list": [1, -2, 0.5, null, true, cid], "nested": { "a": [[]] } });
+        let bytes = c.encode_to_vec(&value).unwrap();
+        let node = c.decode_ref(&bytes).unwrap();
+        assert_eq!(node.to_ipld(), value);
+
+        let Some(IpldRef::String(name)) = node.get("name") else { panic!() };
+        assert!(bytes.as_ptr_range().contains(&name.as_ptr()));
+        assert_eq!(node.get("data"), Some(IpldRef::Bytes(b"split")));
+        let Some(IpldRef::List(list)) = node.get("list") else { panic!() };
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.get(1), Some(IpldRef::Integer(-2)));
+        assert_eq!(list.get(5), Some(IpldRef::Link(cid)));
+        assert_eq!(list.get(6), None);
+        assert_eq!(node.get("missing"), None);
+        assert_eq!(node.get("nested").map(|n| n.kind()), Some(IpldKind::Map));
+    }
+
+    #[test]
+    fn test_decode_ref_invalid() {
+        let c = DagCbor::default();
+        // invalid utf-8 nested in a list
+        assert!(matches!(c.decode_ref(&[0x81, 0x61, 0xff]), Err(CodecError::MalformedData(_))));
+        // duplicate map keys
+        assert!(matches!(c.decode_ref(&[0xa2, 0x61, b'a', 0xf6, 0x61, b'a', 0xf6]), Err(CodecError::MalformedData(_))));
+        // unsorted keys are only accepted leniently, and still must not repeat
+        let lenient = DagCbor::lenient(DecodeLimits::default());
+        assert!(lenient.decode_ref(&[0xa2, 0x61, b'b', 0xf6, 0x61, b'a', 0xf6]).is_ok());
+        assert!(lenient.decode_ref(&[0xa3, 0x61, b'b', 0xf6, 0x61, b'a', 0xf6, 0x61, b'b', 0xf6]).is_err());
+        // truncated byte string
+        assert!(matches!(c.decode_ref(&[0x81, 0x45, 0x00]), Err(CodecError::Io(_))));
+        let nested = [vec![0x81; 10], vec![0xf6]].concat();
+        let shallow = DagCbor::with_limits(DecodeLimits { max_depth: 4, ..Default::default() });
+        assert!(matches!(shallow.decode_ref(&nested), Err(CodecError::LimitExceeded(_))));
+    }
+}
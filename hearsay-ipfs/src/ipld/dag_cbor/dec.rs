@@ -1,11 +1,11 @@
-use std::{collections::BTreeMap, io::{self, Read, Seek, SeekFrom}};
+use std::{collections::BTreeMap, io::{self, Read, Seek, SeekFrom}, mem};
 
 use bytes::Bytes;
 use cid::Cid;
 
-use crate::ipld::{CodecError, Decode};
+use crate::ipld::{CodecError, Decode, DecodeLimits, Ipld};
 
-use super::{DagCbor, Header, MajorType};
+use super::{DagCbor, DecodeMode, Deviations, Header, MajorType};
 
 #[inline]
 pub(super) fn read_header<R: Read>(r: &mut R) -> io::Result<Header> {
@@ -47,83 +47,407 @@ pub(super) fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
     Ok(f64::from_be_bytes(buf))
 }
 
+/// Upper bound on capacity reserved up front from an untrusted length header.
+pub(super) const MAX_PREALLOC: usize = 1024;
+
+/// Tracks resource use of a single decode against its [DecodeLimits], see [Decode::decode_with].
+pub struct Budget {
+    limits: DecodeLimits,
+    mode: DecodeMode,
+    seen: Deviations,
+    depth: usize,
+    alloc: usize,
+}
+
+impl Budget {
+    pub fn new(c: &DagCbor) -> Self {
+        Self { limits: c.limits, mode: c.mode, seen: Deviations::default(), depth: 0, alloc: 0 }
+    }
+
+    #[inline]
+    pub(super) fn mode(&self) -> DecodeMode {
+        self.mode
+    }
+
+    /// Non-canonical features seen so far, when decoding leniently.
+    #[inline]
+    pub(super) fn seen(&mut self) -> &mut Deviations {
+        &mut self.seen
+    }
+
+    /// Whether a string, list or map [Header] starts an indefinite-length item, which is only
+    /// accepted when decoding leniently.
+    #[inline]
+    pub(super) fn indefinite(&mut self, header: &Header) -> Result<bool, CodecError> {
+        if header.short_count != 31 {
+            return Ok(false);
+        }
+        match self.mode {
+            DecodeMode::Strict => Err(CodecError::MalformedData("indefinite-length items are not allowed")),
+            DecodeMode::Lenient => {
+                self.seen.indefinite_lengths = true;
+                Ok(true)
+            },
+        }
+    }
+
+    /// Account for `size` bytes before allocating them.
+    #[inline]
+    pub(super) fn alloc(&mut self, size: usize) -> Result<(), CodecError> {
+        self.alloc = self.alloc.saturating_add(size);
+        if self.alloc > self.limits.max_alloc {
+            return Err(CodecError::LimitExceeded("allocation"));
+        }
+        Ok(())
+    }
+
+    /// Check the length of a list or map, before reading its entries.
+    #[inline]
+    pub(super) fn collection(&mut self, len: u64) -> Result<usize, CodecError> {
+        let len = usize::try_from(len).map_err(|_| CodecError::NumberOutOfBounds)?;
+        if len > self.limits.max_collection_len {
+            return Err(CodecError::LimitExceeded("collection length"));
+        }
+        Ok(len)
+    }
+
+    /// Descend into a list or map.
+    #[inline]
+    pub(super) fn enter(&mut self) -> Result<(), CodecError> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            return Err(CodecError::LimitExceeded("nesting depth"));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(super) fn exit(&mut self) {
+        self.depth -= 1;
+    }
+}
+
 #[inline]
-pub(super) fn read_list<R, T>(r: &mut R, len: u64) -> Result<Vec<T>, CodecError>
+pub(super) fn read_list<R, T>(c: &DagCbor, r: &mut R, len: u64, budget: &mut Budget) -> Result<Vec<T>, CodecError>
 where 
     R: Read + Seek,
     T: Decode<DagCbor>,
 {
-    let len = usize::try_from(len).map_err(|_| CodecError::NumberOutOfBounds)?;
-    let mut list = Vec::with_capacity(len);
+    let len = budget.collection(len)?;
+    budget.enter()?;
+    let mut list = Vec::with_capacity(len.min(MAX_PREALLOC));
     for _ in 0..len {
-        list.push(T::decode(&DagCbor, r)?);
+        budget.alloc(mem::size_of::<T>())?;
+        list.push(T::decode_with(c, r, budget)?);
     }
+    budget.exit();
     Ok(list)
 }
 
+/// Read a byte string without trusting `len` for the allocation size.
 #[inline]
-pub(super) fn read_bytes<R: Read>(r: &mut R, len: u64) -> Result<Bytes, CodecError> {
+pub(super) fn read_bytes<R: Read>(r: &mut R, len: u64, budget: &mut Budget) -> Result<Bytes, CodecError> {
     let len = usize::try_from(len).map_err(|_| CodecError::NumberOutOfBounds)?;
-    let mut buf = Vec::with_capacity(len); // TODO: cap size?
-    r.take(len as u64).read_exact(&mut buf).map_err(|e| CodecError::Io(e))?;
+    budget.alloc(len)?;
+    let mut buf = Vec::with_capacity(len.min(MAX_PREALLOC));
+    r.take(len as u64).read_to_end(&mut buf).map_err(|e| CodecError::Io(e))?;
+    if buf.len() != len {
+        return Err(CodecError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
     Ok(buf.into())
 }
 
 #[inline]
-pub(super) fn read_string<R: Read>(r: &mut R, len: u64) -> Result<String, CodecError> {
-    let buf = read_bytes(r, len)?;
+pub(super) fn read_string<R: Read>(r: &mut R, len: u64, budget: &mut Budget) -> Result<String, CodecError> {
+    let buf = read_bytes(r, len, budget)?;
     Ok(String::from_utf8(buf.into()).map_err(|_| CodecError::MalformedData("bytes are not in utf-8 string format"))?)
 }
 
 #[inline]
-pub(super) fn read_map<R, K, V>(r: &mut R, len: u64) -> Result<BTreeMap<K, V>, CodecError>
+pub(super) fn read_map<R, V>(c: &DagCbor, r: &mut R, len: u64, budget: &mut Budget) -> Result<BTreeMap<String, V>, CodecError>
 where 
     R: Read + Seek,
-    K: Decode<DagCbor> + Ord,
     V: Decode<DagCbor>,
 {
-    let len = usize::try_from(len).map_err(|_| CodecError::NumberOutOfBounds)?;
-    let c = DagCbor;
+    let len = budget.collection(len)?;
+    budget.enter()?;
     let mut map = BTreeMap::new();
+    let mut prev: Option<String> = None;
     for _ in 0..len {
-        let k = K::decode(&c, r)?;
-        let v = V::decode(&c, r)?;
+        budget.alloc(mem::size_of::<(String, V)>())?;
+        let k = String::decode_with(c, r, budget)?;
+        if !check_key(prev.as_deref(), &k, budget.mode())? {
+            budget.seen().unsorted_keys = true;
+        }
+        prev = Some(k.clone());
+        let v = V::decode_with(c, r, budget)?;
         if map.insert(k, v).is_some() {
             return Err(CodecError::MalformedData("duplicate map keys"));
         }
     }
+    budget.exit();
     Ok(map)
 }
 
+/// Check that map keys arrive in canonical order, length-first then bytewise, when decoding strictly.
+/// Duplicate keys are rejected in either mode. Returns whether the keys were in order.
 #[inline]
-pub(super) fn read_link<R: Read>(r: &mut R, header: &Header) -> Result<Cid, CodecError> {
-    let len = match header.major_type {
-        MajorType::ByteString => read_u8(r).map_err(|e| CodecError::Io(e))?,
-        _ => return Err(CodecError::MalformedData("unexpected major type")),
-    };
-    let mut r = r.take(len as u64);
-    if read_u8(&mut r).map_err(|e| CodecError::Io(e))? != 0 {
+pub(super) fn check_key(prev: Option<&str>, key: &str, mode: DecodeMode) -> Result<bool, CodecError> {
+    match prev {
+        Some(prev) if prev == key => Err(CodecError::MalformedData("duplicate map keys")),
+        Some(prev) if (prev.len(), prev) > (key.len(), key) => match mode {
+            DecodeMode::Strict => Err(CodecError::MalformedData("map keys are not in canonical order")),
+            DecodeMode::Lenient => Ok(false),
+        },
+        _ => Ok(true),
+    }
+}
+
+/// Read the byte string following a tag 42 [Header].
+#[inline]
+pub(super) fn read_link<R: Read>(r: &mut R, budget: &mut Budget) -> Result<Cid, CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    if header.major_type != MajorType::ByteString {
+        return Err(CodecError::MalformedData("unexpected major type"));
+    }
+    let len = read_arg(r, &header, budget)?;
+    let buf = read_bytes(r, len, budget)?;
+    // 0x00 prefix to denote multibase CID
+    let Some((0, mut bytes)) = buf.split_first() else {
         return Err(CodecError::MalformedData("invalid cid prefix"));
+    };
+    let cid = Cid::read_bytes(&mut bytes).map_err(|_| CodecError::MalformedData("invalid cid data"))?;
+    if !bytes.is_empty() {
+        return Err(CodecError::MalformedData("trailing cid data"));
     }
-    Cid::read_bytes(&mut r).map_err(|_| CodecError::MalformedData("invalid cid data"))
+    Ok(cid)
 }
 
+/// Read the contents of a byte or text string after its [Header], joining the chunks of an
+/// indefinite-length string when decoding leniently.
+pub(super) fn read_chunks<R: Read>(r: &mut R, header: &Header, budget: &mut Budget) -> Result<Vec<u8>, CodecError> {
+    if !budget.indefinite(header)? {
+        let len = read_arg(r, header, budget)?;
+        return Ok(read_bytes(r, len, budget)?.into());
+    }
+    let mut buf = vec![];
+    loop {
+        let chunk = read_header(r).map_err(|e| CodecError::Io(e))?;
+        if chunk == Header::BREAK {
+            return Ok(buf);
+        }
+        // Chunks are definite-length strings of the same major type.
+        if chunk.major_type != header.major_type || chunk.short_count == 31 {
+            return Err(CodecError::MalformedData("invalid indefinite-length string chunk"));
+        }
+        let len = read_arg(r, &chunk, budget)?;
+        let bytes = read_bytes(r, len, budget)?;
+        if chunk.major_type == MajorType::TextString && std::str::from_utf8(&bytes).is_err() {
+            return Err(CodecError::MalformedData("bytes are not in utf-8 string format"));
+        }
+        buf.extend_from_slice(&bytes);
+    }
+}
+
+/// Read the text string after a [Header], such as a map key.
+pub(super) fn read_text<R: Read>(r: &mut R, header: &Header, budget: &mut Budget) -> Result<String, CodecError> {
+    if header.major_type != MajorType::TextString {
+        return Err(CodecError::MalformedData("map keys must be strings"));
+    }
+    String::from_utf8(read_chunks(r, header, budget)?).map_err(|_| CodecError::MalformedData("bytes are not in utf-8 string format"))
+}
+
+/// Read the length of a list or map after its [Header], or `None` if it is an indefinite-length
+/// item closed by [Header::BREAK].
+pub(super) fn read_collection_len<R: Read>(r: &mut R, header: &Header, budget: &mut Budget) -> Result<Option<usize>, CodecError> {
+    if budget.indefinite(header)? {
+        return Ok(None);
+    }
+    let len = read_arg(r, header, budget)?;
+    Ok(Some(budget.collection(len)?))
+}
+
+/// Read the next header of a list or map, or `None` at its end.
+pub(super) fn read_entry<R: Read>(r: &mut R, len: Option<usize>, read: usize, budget: &mut Budget) -> Result<Option<Header>, CodecError> {
+    match len {
+        Some(len) if read == len => Ok(None),
+        Some(_) => Ok(Some(read_header(r).map_err(|e| CodecError::Io(e))?)),
+        None => match read_header(r).map_err(|e| CodecError::Io(e))? {
+            Header::BREAK => Ok(None),
+            header => {
+                budget.collection(read as u64 + 1)?;
+                Ok(Some(header))
+            },
+        },
+    }
+}
+
+/// Check that `r` is at its end, when decoding strictly. Reads a byte if it is not.
+pub(super) fn read_end<R: Read>(r: &mut R, mode: DecodeMode) -> Result<(), CodecError> {
+    if mode == DecodeMode::Lenient {
+        return Ok(());
+    }
+    let mut buf = [0u8; 1];
+    loop {
+        return match r.read(&mut buf) {
+            Ok(0) => Ok(()),
+            Ok(_) => Err(CodecError::MalformedData("trailing data")),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => Err(CodecError::Io(e)),
+        };
+    }
+}
+
+/// Decode a [Ipld] data item, tracking the whole tree against one [Budget].
+pub(super) fn read_ipld<R: Read>(r: &mut R, budget: &mut Budget) -> Result<Ipld, CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    read_ipld_after(r, header, budget)
+}
+
+fn read_ipld_after<R: Read>(r: &mut R, header: Header, budget: &mut Budget) -> Result<Ipld, CodecError> {
+    Ok(match header.major_type {
+        MajorType::PositiveInt => Ipld::Integer(read_arg(r, &header, budget)?.into()),
+        MajorType::NegativeInt => Ipld::Integer(-1i128 - (read_arg(r, &header, budget)? as i128)),
+        MajorType::ByteString => Ipld::Bytes(read_chunks(r, &header, budget)?.into()),
+        MajorType::TextString => Ipld::String(read_text(r, &header, budget)?),
+        MajorType::Array => {
+            let len = read_collection_len(r, &header, budget)?;
+            budget.enter()?;
+            let mut list = Vec::with_capacity(len.unwrap_or_default().min(MAX_PREALLOC));
+            while let Some(header) = read_entry(r, len, list.len(), budget)? {
+                budget.alloc(mem::size_of::<Ipld>())?;
+                list.push(read_ipld_after(r, header, budget)?);
+            }
+            budget.exit();
+            Ipld::List(list)
+        },
+        MajorType::Map => {
+            let len = read_collection_len(r, &header, budget)?;
+            budget.enter()?;
+            let mut map = BTreeMap::new();
+            let mut prev: Option<String> = None;
+            while let Some(header) = read_entry(r, len, map.len(), budget)? {
+                budget.alloc(mem::size_of::<(String, Ipld)>())?;
+                let k = read_text(r, &header, budget)?;
+                if !check_key(prev.as_deref(), &k, budget.mode())? {
+                    budget.seen().unsorted_keys = true;
+                }
+                prev = Some(k.clone());
+                let v = read_ipld(r, budget)?;
+                if map.insert(k, v).is_some() {
+                    return Err(CodecError::MalformedData("duplicate map keys"));
+                }
+            }
+            budget.exit();
+            Ipld::Map(map)
+        },
+        MajorType::Tag => {
+            let tag = read_arg(r, &header, budget)?;
+            if tag == 42 {
+                Ipld::Link(read_link(r, budget)?)
+            } else {
+                return Err(CodecError::MalformedData("unknown tag"));
+            }
+        },
+        MajorType::Other => match header {
+            Header::NULL => Ipld::Null,
+            Header::TRUE => Ipld::Bool(true),
+            Header::FALSE => Ipld::Bool(false),
+            Header::F64 | Header::_F32 | Header::_F16 => Ipld::Float(read_float_arg(r, &header, budget)?),
+            Header::BREAK => return Err(CodecError::MalformedData("unexpected break")),
+            _ => return Err(CodecError::MalformedData("unknown header type")),
+        },
+    })
+}
+
+/// Read the argument of a [Header], and whether it used the shortest form, see
+/// [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-4.2.1).
 #[inline]
-pub(super) fn read_uint<R: Read>(r: &mut R, header: &Header) -> Result<u64, CodecError> {
+fn read_uint_form<R: Read>(r: &mut R, header: &Header) -> Result<(u64, bool), CodecError> {
     debug_assert!(header.major_type != MajorType::Other);
-    let v = header.short_count;
-    Ok(match v {
-        0..=23 => return Ok(v as u64),
-        24 => read_u8(r).map_err(|e| CodecError::Io(e))? as u64,
-        25 => read_u16(r).map_err(|e| CodecError::Io(e))? as u64,
-        26 => read_u32(r).map_err(|e| CodecError::Io(e))? as u64,
-        27 => read_u64(r).map_err(|e| CodecError::Io(e))?,
+    let (v, min) = match header.short_count {
+        v @ 0..=23 => return Ok((v as u64, true)),
+        24 => (read_u8(r).map_err(|e| CodecError::Io(e))? as u64, 24),
+        25 => (read_u16(r).map_err(|e| CodecError::Io(e))? as u64, 1 << 8),
+        26 => (read_u32(r).map_err(|e| CodecError::Io(e))? as u64, 1 << 16),
+        27 => (read_u64(r).map_err(|e| CodecError::Io(e))?, 1 << 32),
+        31 => return Err(CodecError::MalformedData("unexpected indefinite-length item")),
         _ => return Err(CodecError::MalformedData("unexpected short count"))
-    })
+    };
+    Ok((v, v >= min))
+}
+
+/// Read the argument of a [Header]. When decoding strictly, it must use the shortest form.
+#[inline]
+pub(super) fn read_uint<R: Read>(r: &mut R, header: &Header, mode: DecodeMode) -> Result<u64, CodecError> {
+    let (v, minimal) = read_uint_form(r, header)?;
+    if mode == DecodeMode::Strict && !minimal {
+        return Err(CodecError::MalformedData("non-minimal integer encoding"));
+    }
+    Ok(v)
+}
+
+/// [read_uint], noting a non-minimal form in the [Budget].
+#[inline]
+pub(super) fn read_arg<R: Read>(r: &mut R, header: &Header, budget: &mut Budget) -> Result<u64, CodecError> {
+    let (v, minimal) = read_uint_form(r, header)?;
+    if !minimal {
+        match budget.mode() {
+            DecodeMode::Strict => return Err(CodecError::MalformedData("non-minimal integer encoding")),
+            DecodeMode::Lenient => budget.seen().non_minimal_ints = true,
+        }
+    }
+    Ok(v)
+}
+
+/// Widen a half-precision float, see [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#appendix-D).
+fn f16_to_f64(half: u16) -> f64 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = (half & 0x3ff) as f64;
+    let value = match exp {
+        0 => mant * 2f64.powi(-24),
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mant + 1024.0) * 2f64.powi(exp as i32 - 25),
+    };
+    if half & 0x8000 != 0 { -value } else { value }
+}
+
+/// Read the float after a [Header]. When decoding strictly, it must be a finite f64.
+#[inline]
+pub(super) fn read_float<R: Read>(r: &mut R, header: &Header, mode: DecodeMode) -> Result<f64, CodecError> {
+    let f = match (header, mode) {
+        (&Header::F64, _) => read_f64(r).map_err(|e| CodecError::Io(e))?,
+        (&Header::_F16 | &Header::_F32, DecodeMode::Strict) => return Err(CodecError::MalformedData("floats must be 64-bit")),
+        (&Header::_F16, DecodeMode::Lenient) => f16_to_f64(read_u16(r).map_err(|e| CodecError::Io(e))?),
+        (&Header::_F32, DecodeMode::Lenient) => f32::from_bits(read_u32(r).map_err(|e| CodecError::Io(e))?).into(),
+        _ => return Err(CodecError::MalformedData("unexpected header type")),
+    };
+    if mode == DecodeMode::Strict && !f.is_finite() {
+        return Err(CodecError::NumberOutOfBounds);
+    }
+    Ok(f)
+}
+
+/// [read_float], noting a short or non-finite float in the [Budget].
+#[inline]
+pub(super) fn read_float_arg<R: Read>(r: &mut R, header: &Header, budget: &mut Budget) -> Result<f64, CodecError> {
+    let f = read_float(r, header, budget.mode())?;
+    if *header != Header::F64 {
+        budget.seen().short_floats = true;
+    }
+    if !f.is_finite() {
+        budget.seen().non_finite_floats = true;
+    }
+    Ok(f)
 }
 
 impl Decode<DagCbor> for bool {
-    fn decode<R: Read + Seek>(_c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, _budget: &mut Budget) -> Result<Self, CodecError> {
         match read_header(r).map_err(|e| CodecError::Io(e))? {
             Header::TRUE => Ok(true),
             Header::FALSE => Ok(false),
@@ -134,49 +458,142 @@ impl Decode<DagCbor> for bool {
 
 impl<T: Decode<DagCbor>> Decode<DagCbor> for Option<T> {
     fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
         match read_header(r).map_err(|e| CodecError::Io(e))? {
             Header::NULL => Ok(None),
             _ => {
                 r.seek(SeekFrom::Current(-1)).map_err(|e| CodecError::Io(e))?;
-                Ok(Some(T::decode(c, r)?))
+                Ok(Some(T::decode_with(c, r, budget)?))
             }
         }
     }
 }
 
+impl<T: Decode<DagCbor>> Decode<DagCbor> for Box<T> {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        Ok(Box::new(T::decode_with(c, r, budget)?))
+    }
+}
+
 impl<T: Decode<DagCbor>> Decode<DagCbor> for Vec<T> {
-    fn decode<R: Read + Seek>(_c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
         let header = read_header(r).map_err(|e| CodecError::Io(e))?;
         if header.major_type != MajorType::Array {
             return Err(CodecError::MalformedData("unexpected major type"));
         }
-        let len = read_uint(r, &header)?;
-        read_list(r, len)
+        let len = read_arg(r, &header, budget)?;
+        read_list(c, r, len, budget)
     }
 }
 
 impl Decode<DagCbor> for String {
-    fn decode<R: Read + Seek>(_c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
         let header = read_header(r).map_err(|e| CodecError::Io(e))?;
         if header.major_type != MajorType::TextString {
             return Err(CodecError::MalformedData("unexpected major type"));
         }
-        let len = read_uint(r, &header)?;
-        read_string(r, len)
+        let len = read_arg(r, &header, budget)?;
+        read_string(r, len, budget)
     }
 }
 
-impl Decode<DagCbor> for f64 {
-    fn decode<R: Read + Seek>(_c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
-        let f = match read_header(r).map_err(|e| CodecError::Io(e))? {
-            Header::_F16 => todo!(), // TODO: decode f16?
-            Header::_F32 => todo!(), // TODO: decode f32?
-            Header::F64 => read_f64(r)?,
-            _ => return Err(CodecError::MalformedData("unexpected header type")),
-        };
-        if !f.is_finite() {
-            return Err(CodecError::NumberOutOfBounds);
+impl Decode<DagCbor> for i128 {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+        match header.major_type {
+            MajorType::PositiveInt => Ok(read_arg(r, &header, budget)?.into()),
+            MajorType::NegativeInt => Ok(-1i128 - (read_arg(r, &header, budget)? as i128)),
+            _ => Err(CodecError::MalformedData("unexpected major type")),
         }
-        Ok(f)
+    }
+}
+
+macro_rules! impl_decode_int {
+    ($($t:ty),*) => {
+        $(impl Decode<DagCbor> for $t {
+            fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+                Self::decode_with(c, r, &mut Budget::new(c))
+            }
+
+            fn decode_with<R: Read + Seek>(c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+                <$t>::try_from(i128::decode_with(c, r, budget)?).map_err(|_| CodecError::NumberOutOfBounds)
+            }
+        })*
+    };
+}
+
+impl_decode_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Decode<DagCbor> for Bytes {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+        if header.major_type != MajorType::ByteString {
+            return Err(CodecError::MalformedData("unexpected major type"));
+        }
+        let len = read_arg(r, &header, budget)?;
+        read_bytes(r, len, budget)
+    }
+}
+
+impl Decode<DagCbor> for Cid {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+        if header.major_type != MajorType::Tag || read_arg(r, &header, budget)? != 42 {
+            return Err(CodecError::MalformedData("expected tag 42"));
+        }
+        read_link(r, budget)
+    }
+}
+
+impl<T: Decode<DagCbor>> Decode<DagCbor> for BTreeMap<String, T> {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+        if header.major_type != MajorType::Map {
+            return Err(CodecError::MalformedData("unexpected major type"));
+        }
+        let len = read_arg(r, &header, budget)?;
+        read_map(c, r, len, budget)
+    }
+}
+
+impl Decode<DagCbor> for f64 {
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        Self::decode_with(c, r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+        read_float_arg(r, &header, budget)
     }
 }
@@ -0,0 +1,352 @@
+//! Wire helpers for code generated by `#[derive(DagCbor)]`. Not a stable API.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+pub use crate::ipld::{CodecError, Decode, Encode, IpldKind};
+pub use super::{dec::Budget, DagCbor};
+
+use super::{dec::{read_arg, read_chunks, read_collection_len, read_entry, read_float_arg, read_header, read_string}, enc::write_uint, DecodeMode, Header, MajorType};
+
+pub fn write_null<W: Write>(w: &mut W) -> Result<(), CodecError> {
+    super::enc::write_null(w)
+}
+
+pub fn write_str<W: Write>(w: &mut W, s: &str) -> Result<(), CodecError> {
+    write_uint(w, MajorType::TextString, s.len() as u64).map_err(|e| CodecError::Io(e))?;
+    w.write_all(s.as_bytes()).map_err(|e| CodecError::Io(e))
+}
+
+pub fn write_list<W: Write>(w: &mut W, len: u64) -> Result<(), CodecError> {
+    write_uint(w, MajorType::Array, len).map_err(|e| CodecError::Io(e))
+}
+
+pub fn write_map<W: Write>(w: &mut W, len: u64) -> Result<(), CodecError> {
+    write_uint(w, MajorType::Map, len).map_err(|e| CodecError::Io(e))
+}
+
+pub fn read_null<R: Read>(r: &mut R) -> Result<(), CodecError> {
+    match read_header(r).map_err(|e| CodecError::Io(e))? {
+        Header::NULL => Ok(()),
+        _ => Err(CodecError::MalformedData("expected null")),
+    }
+}
+
+/// Read a map key.
+pub fn read_key<R: Read>(r: &mut R, budget: &mut Budget) -> Result<String, CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    if header.major_type != MajorType::TextString {
+        return Err(CodecError::MalformedData("map keys must be strings"));
+    }
+    let len = read_arg(r, &header, budget)?;
+    read_string(r, len, budget)
+}
+
+/// Check the key read after `prev`, see [DecodeMode].
+pub fn check_key(budget: &mut Budget, prev: &mut Option<String>, key: &str) -> Result<(), CodecError> {
+    if !super::dec::check_key(prev.as_deref(), key, budget.mode())? {
+        budget.seen().unsorted_keys = true;
+    }
+    *prev = Some(key.to_owned());
+    Ok(())
+}
+
+/// Read the length of a list and descend into it, to be left with [end].
+pub fn read_list_len<R: Read>(r: &mut R, budget: &mut Budget) -> Result<u64, CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    if header.major_type != MajorType::Array {
+        return Err(CodecError::MalformedData("unexpected major type"));
+    }
+    read_len(r, &header, budget)
+}
+
+/// Read the length of a map and descend into it, to be left with [end].
+pub fn read_map_len<R: Read>(r: &mut R, budget: &mut Budget) -> Result<u64, CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    if header.major_type != MajorType::Map {
+        return Err(CodecError::MalformedData("unexpected major type"));
+    }
+    read_len(r, &header, budget)
+}
+
+fn read_len<R: Read>(r: &mut R, header: &Header, budget: &mut Budget) -> Result<u64, CodecError> {
+    let len = read_arg(r, header, budget)?;
+    budget.collection(len)?;
+    budget.enter()?;
+    Ok(len)
+}
+
+/// Leave the list or map entered by [read_list_len] or [read_map_len].
+pub fn end(budget: &mut Budget) {
+    budget.exit();
+}
+
+/// Kind of the next data item, without consuming it.
+/// Short floats are only taken for floats when decoding leniently, as [Budget] says.
+pub fn peek_kind<R: Read + Seek>(r: &mut R, budget: &Budget) -> Result<IpldKind, CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    r.seek(SeekFrom::Current(-1)).map_err(|e| CodecError::Io(e))?;
+    Ok(match header.major_type {
+        MajorType::PositiveInt | MajorType::NegativeInt => IpldKind::Integer,
+        MajorType::ByteString => IpldKind::Bytes,
+        MajorType::TextString => IpldKind::String,
+        MajorType::Array => IpldKind::List,
+        MajorType::Map => IpldKind::Map,
+        MajorType::Tag => IpldKind::Link,
+        MajorType::Other => match header {
+            Header::NULL => IpldKind::Null,
+            Header::TRUE | Header::FALSE => IpldKind::Bool,
+            Header::F64 => IpldKind::Float,
+            Header::_F32 | Header::_F16 if budget.mode() == DecodeMode::Lenient => IpldKind::Float,
+            _ => return Err(CodecError::MalformedData("unknown header type")),
+        },
+    })
+}
+
+/// Read past the next data item.
+pub fn skip<R: Read>(r: &mut R, budget: &mut Budget) -> Result<(), CodecError> {
+    skip_item(r, budget)
+}
+
+pub(super) fn skip_item<R: Read>(r: &mut R, budget: &mut Budget) -> Result<(), CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    skip_item_after(r, header, budget)
+}
+
+fn skip_item_after<R: Read>(r: &mut R, header: Header, budget: &mut Budget) -> Result<(), CodecError> {
+    match header.major_type {
+        MajorType::PositiveInt | MajorType::NegativeInt => {
+            read_arg(r, &header, budget)?;
+        },
+        MajorType::ByteString | MajorType::TextString if budget.indefinite(&header)? => {
+            read_chunks(r, &header, budget)?;
+        },
+        MajorType::ByteString | MajorType::TextString => {
+            let len = read_arg(r, &header, budget)?;
+            let read = io::copy(&mut r.take(len), &mut io::sink()).map_err(|e| CodecError::Io(e))?;
+            if read != len {
+                return Err(CodecError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+        },
+        MajorType::Array | MajorType::Map => {
+            let is_map = header.major_type == MajorType::Map;
+            let len = read_collection_len(r, &header, budget)?;
+            budget.enter()?;
+            let mut read = 0;
+            while let Some(entry) = read_entry(r, len, read, budget)? {
+                skip_item_after(r, entry, budget)?;
+                if is_map {
+                    skip_item(r, budget)?;
+                }
+                read += 1;
+            }
+            budget.exit();
+        },
+        MajorType::Tag => {
+            if read_arg(r, &header, budget)? != 42 {
+                return Err(CodecError::MalformedData("unknown tag"));
+            }
+            skip_item(r, budget)?;
+        },
+        MajorType::Other => match header {
+            Header::NULL | Header::TRUE | Header::FALSE => {},
+            Header::F64 | Header::_F32 | Header::_F16 => {
+                read_float_arg(r, &header, budget)?;
+            },
+            _ => return Err(CodecError::MalformedData("unknown header type")),
+        },
+    }
+    Ok(())
+}
+
+/// Find the string value of the `tag` key in the next map, leaving the reader at the start of the map.
+pub fn find_tag<R: Read + Seek>(c: &DagCbor, r: &mut R, tag: &str, budget: &mut Budget) -> Result<String, CodecError> {
+    let start = r.stream_position().map_err(|e| CodecError::Io(e))?;
+    let mut found = None;
+    for _ in 0..read_map_len(r, budget)? {
+        if read_key(r, budget)? == tag {
+            found = Some(String::decode_with(c, r, budget)?);
+            break;
+        }
+        skip(r, budget)?;
+    }
+    end(budget);
+    r.seek(SeekFrom::Start(start)).map_err(|e| CodecError::Io(e))?;
+    found.ok_or(CodecError::MalformedData("missing union tag"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bytes::Bytes;
+    use cid::Cid;
+
+    use crate::ipld::{Codec, DagCbor, DecodeLimits, Ipld};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, DagCbor)]
+    struct Entry {
+        name: String,
+        #[ipld(rename = "Hash")]
+        hash: Cid,
+        #[ipld(optional)]
+        size: Option<u64>,
+        mode: Option<u32>,
+        kind: Kind,
+    }
+
+    #[derive(Debug, PartialEq, DagCbor)]
+    struct Point(i64, i64);
+
+    #[derive(Debug, PartialEq, DagCbor)]
+    enum Kind {
+        File { data: Bytes },
+        #[ipld(rename = "dir")]
+        Directory(Vec<Entry>),
+        Symlink(String, Point),
+        Unknown,
+    }
+
+    #[derive(Debug, PartialEq, DagCbor)]
+    #[ipld(repr = "kinded")]
+    enum Value {
+        Nothing,
+        Number(i128),
+        Text(String),
+        Ratio(f64),
+        #[ipld(kind = "list")]
+        Point(Point),
+    }
+
+    #[derive(Debug, PartialEq, DagCbor)]
+    #[ipld(repr = "inline", tag = "type")]
+    enum Message<T> {
+        #[ipld(rename = "ping")]
+        Ping,
+        Data { id: u32, payload: T },
+    }
+
+    fn ipld_map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+        Ipld::Map(entries.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    /// Encode with the derived impl, check it matches the canonical encoding of `ipld`, and decode it back.
+    fn roundtrip<T: Encode<DagCbor> + Decode<DagCbor> + PartialEq + std::fmt::Debug>(value: T, ipld: Ipld) {
+        let c = DagCbor::default();
+        let bytes = c.encode_to_vec(&value).unwrap();
+        assert_eq!(bytes, c.encode_to_vec(&ipld).unwrap());
+        assert_eq!(c.decode_from_slice::<T>(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_derive_struct() {
+        let entry = |size| Entry {
+            name: String::from("banana"),
+            hash: Cid::default(),
+            size,
+            mode: None,
+            kind: Kind::Unknown,
+        };
+        let ipld = |size: Option<Ipld>| {
+            let mut map = BTreeMap::from([
+                (String::from("name"), Ipld::String(String::from("banana"))),
+                (String::from("Hash"), Ipld::Link(Cid::default())),
+                (String::from("mode"), Ipld::Null),
+                (String::from("kind"), ipld_map([("Unknown", Ipld::Null)])),
+            ]);
+            map.extend(size.map(|size| (String::from("size"), size)));
+            Ipld::Map(map)
+        };
+        roundtrip(entry(None), ipld(None));
+        roundtrip(entry(Some(7)), ipld(Some(Ipld::Integer(7))));
+        roundtrip(Point(-1, 2), Ipld::List(vec![Ipld::Integer(-1), Ipld::Integer(2)]));
+
+        let c = DagCbor::default();
+        let missing = c.encode_to_vec(&ipld_map([("name", Ipld::String(String::new()))])).unwrap();
+        assert!(matches!(c.decode_from_slice::<Entry>(&missing), Err(CodecError::MalformedData(_))));
+        let mut unknown = ipld(None);
+        if let Ipld::Map(map) = &mut unknown {
+            map.insert(String::from("extra"), Ipld::Null);
+        }
+        let unknown = c.encode_to_vec(&unknown).unwrap();
+        assert!(matches!(c.decode_from_slice::<Entry>(&unknown), Err(CodecError::MalformedData(_))));
+        let short = c.encode_to_vec(&Ipld::List(vec![Ipld::Integer(1)])).unwrap();
+        assert!(c.decode_from_slice::<Point>(&short).is_err());
+    }
+
+    #[test]
+    fn test_derive_keyed() {
+        let data = Bytes::from_static(b"banana");
+        roundtrip(
+            Kind::File { data: data.clone() },
+            ipld_map([("File", ipld_map([("data", Ipld::Bytes(data))]))]),
+        );
+        roundtrip(Kind::Directory(vec![]), ipld_map([("dir", Ipld::List(vec![]))]));
+        roundtrip(
+            Kind::Symlink(String::from("a"), Point(0, 1)),
+            ipld_map([("Symlink", Ipld::List(vec![
+                Ipld::String(String::from("a")),
+                Ipld::List(vec![Ipld::Integer(0), Ipld::Integer(1)]),
+            ]))]),
+        );
+        let c = DagCbor::default();
+        let bytes = c.encode_to_vec(&ipld_map([("Link", Ipld::Null)])).unwrap();
+        assert!(c.decode_from_slice::<Kind>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_derive_kinded() {
+        roundtrip(Value::Nothing, Ipld::Null);
+        roundtrip(Value::Number(-3), Ipld::Integer(-3));
+        roundtrip(Value::Text(String::from("banana")), Ipld::String(String::from("banana")));
+        roundtrip(Value::Point(Point(1, 2)), Ipld::List(vec![Ipld::Integer(1), Ipld::Integer(2)]));
+        let c = DagCbor::default();
+        assert!(c.decode_from_slice::<Value>(&c.encode_to_vec(&Ipld::Bool(true)).unwrap()).is_err());
+        // 1.5 as a half float
+        let half = [0xf9, 0x3e, 0x00];
+        assert!(c.decode_from_slice::<Value>(&half).is_err());
+        assert_eq!(DagCbor::lenient(DecodeLimits::default()).decode_from_slice::<Value>(&half).unwrap(), Value::Ratio(1.5));
+    }
+
+    #[test]
+    fn test_derive_inline() {
+        roundtrip(Message::<String>::Ping, ipld_map([("type", Ipld::String(String::from("ping")))]));
+        roundtrip(
+            Message::Data { id: 1, payload: Value::Number(2) },
+            ipld_map([
+                ("type", Ipld::String(String::from("Data"))),
+                ("id", Ipld::Integer(1)),
+                ("payload", Ipld::Integer(2)),
+            ]),
+        );
+        let c = DagCbor::default();
+        let untagged = c.encode_to_vec(&ipld_map([("id", Ipld::Integer(1))])).unwrap();
+        assert!(c.decode_from_slice::<Message<u8>>(&untagged).is_err());
+    }
+
+    #[derive(Debug, PartialEq, DagCbor)]
+    struct Node {
+        children: Vec<Node>,
+    }
+
+    #[test]
+    fn test_derive_limits() {
+        let mut node = Node { children: vec![] };
+        for _ in 0..10 {
+            node = Node { children: vec![node] };
+        }
+        // Each node is a map holding a list.
+        let bytes = DagCbor::default().encode_to_vec(&node).unwrap();
+        let shallow = DagCbor::with_limits(DecodeLimits { max_depth: 21, ..Default::default() });
+        assert!(matches!(shallow.decode_from_slice::<Node>(&bytes), Err(CodecError::LimitExceeded("nesting depth"))));
+        let deep = DagCbor::with_limits(DecodeLimits { max_depth: 22, ..Default::default() });
+        assert_eq!(deep.decode_from_slice::<Node>(&bytes).unwrap(), node);
+
+        // Allocations are counted over the whole data item, not per string or list.
+        let names = vec![String::from("banana"); 4];
+        let bytes = DagCbor::default().encode_to_vec(&names).unwrap();
+        let small = DagCbor::with_limits(DecodeLimits { max_alloc: 100, ..Default::default() });
+        assert!(matches!(small.decode_from_slice::<Vec<String>>(&bytes), Err(CodecError::LimitExceeded("allocation"))));
+    }
+}
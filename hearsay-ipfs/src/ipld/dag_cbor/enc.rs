@@ -14,7 +14,7 @@ pub(super) fn write_null<W: Write>(w: &mut W) -> Result<(), CodecError> {
 
 /// Use the minimal encoding to convey the data item. See <https://datatracker.ietf.org/doc/html/rfc8949#section-4.2.1>.
 #[inline]
-fn write_uint<W: Write>(w: &mut W, major_type: MajorType, data: u64) -> io::Result<()> {
+pub(super) fn write_uint<W: Write>(w: &mut W, major_type: MajorType, data: u64) -> io::Result<()> {
     match data {
         0..=23 => w.write_all(&[Header::new(major_type, data as u8).into()]),
         24..=255 => {
@@ -23,12 +23,12 @@ fn write_uint<W: Write>(w: &mut W, major_type: MajorType, data: u64) -> io::Resu
         },
         256..=65_535 => {
             let mut buf = [Header::new(major_type, 25).into(), 0, 0];
-            buf[1..].copy_from_slice(&data.to_be_bytes());
+            buf[1..].copy_from_slice(&(data as u16).to_be_bytes());
             w.write_all(&buf)
         },
         65_536..=4_294_967_295 => {
             let mut buf = [Header::new(major_type, 26).into(), 0, 0, 0, 0];
-            buf[1..].copy_from_slice(&data.to_be_bytes());
+            buf[1..].copy_from_slice(&(data as u32).to_be_bytes());
             w.write_all(&buf)
         },
         ..=u64::MAX => {
@@ -58,6 +58,12 @@ impl<T: Encode<DagCbor>> Encode<DagCbor> for Option<T> {
     }
 }
 
+impl<T: Encode<DagCbor>> Encode<DagCbor> for Box<T> {
+    fn encode<W: Write>(&self, c: &DagCbor, w: &mut W) -> Result<(), CodecError> {
+        self.as_ref().encode(c, w)
+    }
+}
+
 impl<T: Encode<DagCbor>> Encode<DagCbor> for Vec<T> {
     fn encode<W: Write>(&self, c: &DagCbor, w: &mut W) -> Result<(), CodecError> {
         write_uint(w, MajorType::Array, self.len() as u64).map_err(|e| CodecError::Io(e))?;
@@ -94,15 +100,28 @@ impl Encode<DagCbor> for i128 {
     fn encode<W: Write>(&self, _c: &DagCbor, w: &mut W) -> Result<(), CodecError> {
         const MAX: i128 = 2i128.pow(64) - 1;
         const MIN: i128 = -(2i128.pow(64));
-        let major_type = match self {
-            MIN..0 => MajorType::NegativeInt,
-            ..=MAX => MajorType::PositiveInt,
+        // Negative integers are written as -1 - n, see [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-3.1).
+        let (major_type, data) = match *self {
+            MIN..0 => (MajorType::NegativeInt, -1 - self),
+            0..=MAX => (MajorType::PositiveInt, *self),
             _ => return Err(CodecError::NumberOutOfBounds),
         };
-        write_uint(w, major_type, self.abs() as u64).map_err(|e| CodecError::Io(e))
+        write_uint(w, major_type, data as u64).map_err(|e| CodecError::Io(e))
     }
 }
 
+macro_rules! impl_encode_int {
+    ($($t:ty),*) => {
+        $(impl Encode<DagCbor> for $t {
+            fn encode<W: Write>(&self, c: &DagCbor, w: &mut W) -> Result<(), CodecError> {
+                i128::from(*self).encode(c, w)
+            }
+        })*
+    };
+}
+
+impl_encode_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
 impl Encode<DagCbor> for String {
     fn encode<W: Write>(&self, _c: &DagCbor, w: &mut W) -> Result<(), CodecError> {
         write_uint(w, MajorType::TextString, self.len() as u64).map_err(|e| CodecError::Io(e))?;
@@ -148,3 +167,18 @@ impl<T: Encode<DagCbor> + 'static> Encode<DagCbor> for BTreeMap<String, T> { //
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_heads() {
+        let mut buf = vec![];
+        write_uint(&mut buf, MajorType::TextString, 256).unwrap();
+        write_uint(&mut buf, MajorType::Array, 65_536).unwrap();
+        assert_eq!(buf, [0x79, 0x01, 0x00, 0x9a, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(Header::from(0xf5), Header::TRUE);
+        assert_eq!(Into::<u8>::into(Header::FALSE), 0xf4);
+    }
+}
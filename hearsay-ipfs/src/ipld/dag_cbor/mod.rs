@@ -1,20 +1,117 @@
 //! IPLD DAG-CBOR Implimentation
 //! Note: For use in IPLD DAG-CBOR only. Not nessisarily compatable otherwise.
 
-use std::io::{Read, Seek, Write};
+use std::io::{Cursor, Read, Seek, Write};
+pub use borrowed::{IpldRef, ListIter, ListRef, MapIter, MapRef};
+pub use stream::DagCborReader;
+pub use visit::DagCborEncoder;
 use dec::*;
 use enc::*;
-use super::{Codec, CodecError, Decode, Encode, Ipld};
+use super::{visit::Visitor, Codec, CodecError, Decode, DecodeLimits, Encode, Ipld};
 
+mod borrowed;
 mod dec;
+pub mod derive;
 mod enc;
+mod stream;
+mod visit;
 
 /// Codec for [CBOR](https://datatracker.ietf.org/doc/html/rfc8949).
 /// See IPLD DAG-CBOR [Spec](https://ipld.io/specs/codecs/dag-cbor/spec/).
-pub struct DagCbor;
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DagCbor {
+    /// Bounds applied while decoding.
+    pub limits: DecodeLimits,
+    /// How closely input must follow the canonical encoding.
+    pub mode: DecodeMode,
+}
+
+impl DagCbor {
+    pub fn with_limits(limits: DecodeLimits) -> Self {
+        Self { limits, mode: DecodeMode::Strict }
+    }
+
+    /// Accept non-canonical input, for salvaging data written by other tooling.
+    pub fn lenient(limits: DecodeLimits) -> Self {
+        Self { limits, mode: DecodeMode::Lenient }
+    }
+
+    /// Decode leniently within the [DecodeLimits], reporting how `bytes` differ from the canonical encoding.
+    /// Typed values are read as by [Decode], so indefinite-length items are only accepted by [Ipld].
+    pub fn decode_lenient<T: Decode<Self>>(&self, bytes: &[u8]) -> Result<(T, Deviations), CodecError> {
+        let lenient = Self::lenient(self.limits);
+        let mut r = Cursor::new(bytes);
+        let mut budget = Budget::new(&lenient);
+        let value = T::decode_with(&lenient, &mut r, &mut budget)?;
+        let mut seen = *budget.seen();
+        seen.trailing_data = r.position() != bytes.len() as u64;
+        Ok((value, seen))
+    }
+
+    /// [decode_lenient](Self::decode_lenient) as [Visitor](super::visit::Visitor) events, reading to the end of `r`.
+    /// Pass another codec's encoder to transcode non-canonical input while reporting what was fixed.
+    pub fn decode_visit_lenient<R: Read, V: Visitor + ?Sized>(&self, r: &mut R, v: &mut V) -> Result<Deviations, CodecError> {
+        let mut budget = Budget::new(&Self::lenient(self.limits));
+        visit::visit_item(r, &mut budget, v)?;
+        let mut seen = *budget.seen();
+        seen.trailing_data = match read_end(r, DecodeMode::Strict) {
+            Ok(()) => false,
+            Err(CodecError::MalformedData(_)) => true,
+            Err(e) => return Err(e),
+        };
+        Ok(seen)
+    }
+}
+
+/// How closely DAG-CBOR input must follow the [strictness](https://ipld.io/specs/codecs/dag-cbor/spec/#strictness)
+/// rules of the spec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Only accept the canonical encoding, so that re-encoding gives back the same bytes and [Cid](cid::Cid).
+    /// Non-minimal lengths, unsorted map keys, 16 and 32-bit or non-finite floats, indefinite-length
+    /// items and trailing bytes are rejected.
+    #[default]
+    Strict,
+    /// Accept all of those, widening floats to 64 bits.
+    /// Tags other than 42 and duplicate map keys are rejected in either mode.
+    Lenient,
+}
+
+/// Non-canonical features seen by [DagCbor::decode_lenient] and [DagCbor::decode_visit_lenient].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Deviations {
+    /// Integers or lengths not in their shortest form.
+    pub non_minimal_ints: bool,
+    /// Map keys out of length-first order.
+    pub unsorted_keys: bool,
+    /// Strings, lists or maps closed by a break code.
+    pub indefinite_lengths: bool,
+    /// 16 or 32-bit floats, widened to 64 bits.
+    pub short_floats: bool,
+    /// NaN or infinite floats.
+    pub non_finite_floats: bool,
+    /// Bytes after the data item.
+    pub trailing_data: bool,
+}
+
+impl Deviations {
+    /// Whether the input was in canonical form, so re-encoding it gives the same bytes.
+    pub fn is_canonical(&self) -> bool {
+        *self == Self::default()
+    }
+}
 
 impl Codec for DagCbor {
     const CODE: u64 = 0x71;
+
+    type Budget = Budget;
+
+    /// When decoding strictly, the data item must be followed by the end of `r`.
+    fn decode<T: Decode<Self>, R: Read + Seek>(&self, r: &mut R) -> Result<T, CodecError> {
+        let value = T::decode(self, r)?;
+        read_end(r, self.mode)?;
+        Ok(value)
+    }
 }
 
 impl Encode<DagCbor> for Ipld {
@@ -34,44 +131,12 @@ impl Encode<DagCbor> for Ipld {
 }
 
 impl Decode<DagCbor> for Ipld {
-    fn decode<R: Read + Seek>(_: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
-        let header = read_header(r).map_err(|e| CodecError::Io(e))?;
-        Ok(match header.major_type {
-            MajorType::PositiveInt => Self::Integer(read_uint(r, &header)?.into()),
-            MajorType::NegativeInt => Self::Integer(-1i128 - (read_uint(r, &header)? as i128)),
-            MajorType::ByteString => {
-                let len = read_uint(r, &header)?;
-                Self::Bytes(read_bytes(r, len)?)
-            },
-            MajorType::TextString => {
-                let len = read_uint(r, &header)?;
-                Self::String(read_string(r, len)?)
-            },
-            MajorType::Array => {
-                let len = read_uint(r, &header)?;
-                Self::List(read_list(r, len)?)
-            },
-            MajorType::Map => {
-                let len = read_uint(r, &header)?;
-                Self::Map(read_map(r, len)?)
-            },
-            MajorType::Tag => {
-                let tag = read_uint(r, &header)?;
-                if tag == 42 {
-                    Self::Link(read_link(r, &header)?)
-                } else {
-                    return Err(CodecError::MalformedData("unknown tag"));
-                }
-            },
-            MajorType::Other => match header {
-                Header::NULL => Self::Null,
-                Header::TRUE => Self::Bool(true),
-                Header::FALSE => Self::Bool(false),
-                Header::F64 => Self::Float(read_f64(r)?),
-                // TODO: f16 & f32 ?
-                _ => return Err(CodecError::MalformedData("unknown header type")),
-            },
-        })
+    fn decode<R: Read + Seek>(c: &DagCbor, r: &mut R) -> Result<Self, CodecError> {
+        read_ipld(r, &mut Budget::new(c))
+    }
+
+    fn decode_with<R: Read + Seek>(_c: &DagCbor, r: &mut R, budget: &mut Budget) -> Result<Self, CodecError> {
+        read_ipld(r, budget)
     }
 }
 
@@ -114,9 +179,9 @@ pub(crate) struct Header {
 
 impl Header {
     /// True boolean [Header]. See [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-3.3).
-    pub(crate) const TRUE: Header = Header::new(MajorType::Other, 20);
+    pub(crate) const TRUE: Header = Header::new(MajorType::Other, 21);
     /// False boolean [Header]. See [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-3.3).
-    pub(crate) const FALSE: Header = Header::new(MajorType::Other, 21);
+    pub(crate) const FALSE: Header = Header::new(MajorType::Other, 20);
     /// Null value [Header]. See [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-3.3).
     pub(crate) const NULL: Header = Header::new(MajorType::Other, 22);
     /// Unused by IPLD: 16-bit float [Header]. See [RFC 8949](https://datatracker.ietf.org/doc/html/rfc8949#section-3.3).
@@ -140,7 +205,7 @@ impl Header {
 impl From<u8> for Header {
     fn from(value: u8) -> Self {
         Self { 
-            major_type: (value << 5).into(),
+            major_type: (value >> 5).into(),
             short_count: value & 0b0001_1111,
         }
     }
@@ -154,10 +219,119 @@ impl Into<u8> for Header {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
+    use bytes::Bytes;
+
+    use crate::{ipld, ipld::visit::{transcode, IpldBuilder, StreamCodec}};
+
     use super::*;
 
     #[test]
     fn dag_cbor_roundtrips(){
         // TODO: DAG-CBOR tests
     }
+
+    #[test]
+    fn dag_cbor_limits() {
+        let c = DagCbor::default();
+        // byte string claiming 2^32 bytes
+        assert!(matches!(c.decode_from_slice::<Ipld>(&[0x5a, 0xff, 0xff, 0xff, 0xff, 0x00]), Err(CodecError::LimitExceeded(_))));
+        // array claiming 2^64-1 items
+        assert!(c.decode_from_slice::<Ipld>(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // array claiming 2^20 items with no data
+        assert!(matches!(c.decode_from_slice::<Ipld>(&[0x9a, 0x00, 0x10, 0x00, 0x00]), Err(CodecError::Io(_))));
+
+        let nested = [vec![0x81; 10], vec![0xf6]].concat();
+        assert!(c.decode_from_slice::<Ipld>(&nested).is_ok());
+        let shallow = DagCbor::with_limits(DecodeLimits { max_depth: 4, ..Default::default() });
+        assert!(matches!(shallow.decode_from_slice::<Ipld>(&nested), Err(CodecError::LimitExceeded(_))));
+        let small = DagCbor::with_limits(DecodeLimits { max_alloc: 8, ..Default::default() });
+        assert!(matches!(small.decode_from_slice::<Ipld>(b"\x4a0123456789"), Err(CodecError::LimitExceeded(_))));
+        assert_eq!(c.decode_from_slice::<Ipld>(b"\x4a0123456789").unwrap(), Ipld::Bytes(Bytes::from_static(b"0123456789")));
+    }
+
+    #[test]
+    fn dag_cbor_strict() {
+        let strict = DagCbor::default();
+        let lenient = DagCbor::lenient(DecodeLimits::default());
+        // non-minimal integer, unsorted keys, keys not length-first, trailing bytes, non-finite float
+        let salvageable: [&[u8]; 5] = [
+            &[0x18, 0x05],
+            &[0xa2, 0x61, b'b', 0xf6, 0x61, b'a', 0xf6],
+            &[0xa2, 0x62, b'a', b'a', 0xf6, 0x61, b'b', 0xf6],
+            &[0xf6, 0xf6],
+            &[0xfb, 0x7f, 0xf8, 0, 0, 0, 0, 0, 0],
+        ];
+        for bytes in salvageable {
+            assert!(strict.decode_from_slice::<Ipld>(bytes).is_err());
+            assert!(strict.decode_ref(bytes).is_err());
+            assert!(lenient.decode_from_slice::<Ipld>(bytes).is_ok());
+            assert!(lenient.decode_ref(bytes).is_ok());
+        }
+        assert!(strict.decode_from_slice::<u64>(&[0x19, 0x00, 0x01]).is_err());
+        assert_eq!(lenient.decode_from_slice::<u64>(&[0x19, 0x00, 0x01]).unwrap(), 1);
+        assert!(strict.decode_from_slice::<BTreeMap<String, Ipld>>(salvageable[1]).is_err());
+        // trailing bytes on every decode path
+        let trailing = salvageable[3];
+        assert!(strict.decode::<Ipld, _>(&mut Cursor::new(trailing)).is_err());
+        assert!(strict.decode_visit(&mut &trailing[..], &mut IpldBuilder::default()).is_err());
+        assert!(lenient.decode_visit(&mut &trailing[..], &mut IpldBuilder::default()).is_ok());
+
+        // non-42 tag, duplicate keys
+        let invalid: [&[u8]; 2] = [&[0xc1, 0x00], &[0xa2, 0x61, b'a', 0xf6, 0x61, b'a', 0xf6]];
+        for bytes in invalid {
+            assert!(strict.decode_from_slice::<Ipld>(bytes).is_err());
+            assert!(lenient.decode_from_slice::<Ipld>(bytes).is_err());
+        }
+        assert!(derive::skip(&mut &invalid[0][..], &mut Budget::new(&lenient)).is_err());
+        assert!(strict.decode_from_slice::<Ipld>(&[0x9f, 0xff]).is_err());
+        assert!(strict.decode_from_slice::<Ipld>(&[0xfa, 0, 0, 0, 0]).is_err());
+        assert!(strict.decode_from_slice::<f64>(&[0xfa, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn dag_cbor_lenient() {
+        let c = DagCbor::default();
+        let lenient = DagCbor::lenient(DecodeLimits::default());
+        let (value, seen) = c.decode_lenient::<Ipld>(&[0xf9, 0x3e, 0x00]).unwrap();
+        assert_eq!(value, Ipld::Float(1.5));
+        assert_eq!(seen, Deviations { short_floats: true, ..Default::default() });
+        assert_eq!(lenient.decode_from_slice::<f64>(&[0xf9, 0x7b, 0xff]).unwrap(), 65504.0);
+        assert_eq!(lenient.decode_from_slice::<f64>(&[0xf9, 0x80, 0x01]).unwrap(), -5.960464477539063e-8);
+        assert_eq!(lenient.decode_from_slice::<f64>(&[0xfa, 0x47, 0xc3, 0x50, 0x00]).unwrap(), 100000.0);
+        let (_, seen) = c.decode_lenient::<Ipld>(&[0x18, 0x05, 0x00]).unwrap();
+        assert_eq!(seen, Deviations { non_minimal_ints: true, trailing_data: true, ..Default::default() });
+        // {"b": 1.5, "a": 1.5} with half floats, decoded as a typed map
+        let (value, seen) = c.decode_lenient::<BTreeMap<String, f64>>(&[0xa2, 0x61, b'b', 0xf9, 0x3e, 0x00, 0x61, b'a', 0xf9, 0x3e, 0x00]).unwrap();
+        assert_eq!(value, BTreeMap::from([(String::from("a"), 1.5), (String::from("b"), 1.5)]));
+        assert_eq!(seen, Deviations { unsorted_keys: true, short_floats: true, ..Default::default() });
+
+        // {_ "a": [_ 1, (_ "x", "yz")], "b": (_ h'01', h'0203')}
+        let indefinite = [
+            0xbf, 0x61, b'a', 0x9f, 0x01, 0x7f, 0x61, b'x', 0x62, b'y', b'z', 0xff, 0xff,
+            0x61, b'b', 0x5f, 0x41, 0x01, 0x42, 0x02, 0x03, 0xff, 0xff,
+        ];
+        let expected = ipld!({ "a": [1, "xyz"], "b": b"\x01\x02\x03" });
+        let (value, seen) = c.decode_lenient::<Ipld>(&indefinite).unwrap();
+        assert_eq!(value, expected);
+        assert!(seen.indefinite_lengths && !seen.is_canonical());
+        let mut canonical = vec![];
+        transcode(&lenient, &mut indefinite.as_slice(), &c, &mut canonical).unwrap();
+        assert_eq!(canonical, c.encode_to_vec(&expected).unwrap());
+        let mut fixed = vec![];
+        let seen = c.decode_visit_lenient(&mut indefinite.as_slice(), &mut c.encoder(&mut fixed)).unwrap();
+        assert_eq!(fixed, canonical);
+        assert_eq!(seen, Deviations { indefinite_lengths: true, ..Default::default() });
+        let (_, seen) = c.decode_lenient::<Ipld>(&canonical).unwrap();
+        assert!(seen.is_canonical());
+
+        // stray break, chunk of another type, nested indefinite chunk, chunk splitting a character
+        let invalid: [&[u8]; 4] = [&[0xff], &[0x5f, 0x61, b'a', 0xff], &[0x5f, 0x5f, 0xff, 0xff], &[0x7f, 0x61, 0xc3, 0x61, 0xa9, 0xff]];
+        for bytes in invalid {
+            assert!(c.decode_lenient::<Ipld>(bytes).is_err());
+        }
+        let small = DagCbor::lenient(DecodeLimits { max_collection_len: 2, ..Default::default() });
+        assert!(matches!(small.decode_from_slice::<Ipld>(&[0x9f, 0x01, 0x02, 0x03, 0xff]), Err(CodecError::LimitExceeded(_))));
+    }
 }
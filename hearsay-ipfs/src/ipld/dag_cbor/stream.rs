@@ -0,0 +1,236 @@
+//! Incremental DAG-CBOR decoding from async sources, which need not implement [Seek](std::io::Seek).
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::ipld::{Codec, CodecError, Decode};
+
+use super::{DagCbor, DecodeMode, Header, MajorType};
+
+/// Reads DAG-CBOR data items from an [AsyncRead], one at a time.
+///
+/// Headers are peeked to find where the next item ends, so that only its bytes are buffered. All of
+/// them, headers included, count against [max_alloc](crate::ipld::DecodeLimits::max_alloc). The item is then decoded from the buffer. Reads
+/// are small, so a socket should be wrapped in a [BufReader](tokio::io::BufReader).
+pub struct DagCborReader<R> {
+    c: DagCbor,
+    r: R,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> DagCborReader<R> {
+    pub fn new(c: DagCbor, r: R) -> Self {
+        Self { c, r, buf: vec![] }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Decode the next data item, or `None` if the source ended before it.
+    pub async fn decode<T: Decode<DagCbor>>(&mut self) -> Result<Option<T>, CodecError> {
+        match self.read_item().await? {
+            Some(bytes) => Ok(Some(self.c.decode_from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Decode the next message, prefixed with its length as an
+    /// [unsigned varint](https://github.com/multiformats/unsigned-varint), or `None` if the source
+    /// ended before it. When decoding strictly, the message must hold exactly one data item.
+    pub async fn decode_length_delimited<T: Decode<DagCbor>>(&mut self) -> Result<Option<T>, CodecError> {
+        let Some(len) = self.read_varint().await? else {
+            return Ok(None);
+        };
+        self.buf.clear();
+        self.fill(len).await?;
+        let value = self.c.decode_from_slice(&self.buf)?;
+        Ok(Some(value))
+    }
+
+    /// Read the bytes of the next data item, or `None` if the source ended before it.
+    pub async fn read_item(&mut self) -> Result<Option<Bytes>, CodecError> {
+        self.buf.clear();
+        let mut first = [0u8];
+        if self.r.read(&mut first).await.map_err(|e| CodecError::Io(e))? == 0 {
+            return Ok(None);
+        }
+        self.buf.push(first[0]);
+        // Items left in each open list, map, tag or string, or `None` until a break.
+        let mut open: Vec<Option<u64>> = vec![Some(1)];
+        let mut next = Some(first[0]);
+        while let Some(remaining) = open.last_mut() {
+            match remaining {
+                Some(0) => {
+                    open.pop();
+                    continue;
+                },
+                Some(n) => *n -= 1,
+                None => {},
+            }
+            let byte = match next.take() {
+                Some(byte) => byte,
+                None => self.read_u8().await?,
+            };
+            let header = Header::from(byte);
+            if header == Header::BREAK {
+                if open.pop() != Some(None) {
+                    return Err(CodecError::MalformedData("unexpected break"));
+                }
+                continue;
+            }
+            let arg = match header.short_count {
+                v @ 0..=23 => v as u64,
+                v @ 24..=27 => self.read_be(1 << (v - 24)).await?,
+                31 if self.c.mode == DecodeMode::Lenient => {
+                    if !matches!(header.major_type, MajorType::ByteString | MajorType::TextString | MajorType::Array | MajorType::Map) {
+                        return Err(CodecError::MalformedData("unexpected short count"));
+                    }
+                    self.enter(&mut open, None)?;
+                    continue;
+                },
+                31 => return Err(CodecError::MalformedData("indefinite-length items are not allowed")),
+                _ => return Err(CodecError::MalformedData("unexpected short count")),
+            };
+            match header.major_type {
+                MajorType::ByteString | MajorType::TextString => self.fill(arg).await?,
+                MajorType::Array | MajorType::Map => {
+                    if arg > self.c.limits.max_collection_len as u64 {
+                        return Err(CodecError::LimitExceeded("collection length"));
+                    }
+                    let items = if header.major_type == MajorType::Map { arg * 2 } else { arg };
+                    self.enter(&mut open, Some(items))?;
+                },
+                MajorType::Tag if arg != 42 => return Err(CodecError::MalformedData("unknown tag")),
+                MajorType::Tag => self.enter(&mut open, Some(1))?,
+                MajorType::PositiveInt | MajorType::NegativeInt | MajorType::Other => {},
+            }
+        }
+        Ok(Some(Bytes::copy_from_slice(&self.buf)))
+    }
+
+    /// Open a nested item, within the depth limit.
+    fn enter(&self, open: &mut Vec<Option<u64>>, items: Option<u64>) -> Result<(), CodecError> {
+        if open.len() > self.c.limits.max_depth {
+            return Err(CodecError::LimitExceeded("nesting depth"));
+        }
+        open.push(items);
+        Ok(())
+    }
+
+    async fn read_u8(&mut self) -> Result<u8, CodecError> {
+        if self.buf.len() >= self.c.limits.max_alloc {
+            return Err(CodecError::LimitExceeded("allocation"));
+        }
+        let byte = self.r.read_u8().await.map_err(|e| CodecError::Io(e))?;
+        self.buf.push(byte);
+        Ok(byte)
+    }
+
+    /// Read a big-endian argument of `len` bytes.
+    async fn read_be(&mut self, len: usize) -> Result<u64, CodecError> {
+        let mut value = 0;
+        for _ in 0..len {
+            value = value << 8 | self.read_u8().await? as u64;
+        }
+        Ok(value)
+    }
+
+    /// Append exactly `len` bytes to the buffer, within the allocation limit.
+    async fn fill(&mut self, len: u64) -> Result<(), CodecError> {
+        if (self.buf.len() as u64).saturating_add(len) > self.c.limits.max_alloc as u64 {
+            return Err(CodecError::LimitExceeded("allocation"));
+        }
+        let read = (&mut self.r).take(len).read_to_end(&mut self.buf).await.map_err(|e| CodecError::Io(e))?;
+        if (read as u64) != len {
+            return Err(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(())
+    }
+
+    /// Read an unsigned varint, or `None` if the source ended before it.
+    async fn read_varint(&mut self) -> Result<Option<u64>, CodecError> {
+        let mut value = 0u64;
+        // The spec caps varints at 9 bytes.
+        for i in 0..9 {
+            let mut byte = [0u8];
+            if self.r.read(&mut byte).await.map_err(|e| CodecError::Io(e))? == 0 {
+                return match i {
+                    0 => Ok(None),
+                    _ => Err(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into())),
+                };
+            }
+            value |= u64::from(byte[0] & 0x7f) << (7 * i);
+            if byte[0] & 0x80 == 0 {
+                return Ok(Some(value));
+            }
+        }
+        Err(CodecError::NumberOutOfBounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{ipld, ipld::{DecodeLimits, Ipld}};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reader_items() {
+        let c = DagCbor::default();
+        let first = ipld!({ "name": "banana", "list": [1, [2, [3]], b"bytes"], "link": null });
+        let second = ipld!(-1);
+        let bytes = [c.encode_to_vec(&first).unwrap(), c.encode_to_vec(&second).unwrap()].concat();
+
+        // Written a few bytes at a time, as from a socket.
+        let (mut tx, rx) = tokio::io::duplex(4);
+        tokio::spawn(async move {
+            for chunk in bytes.chunks(3) {
+                tx.write_all(chunk).await.unwrap();
+            }
+        });
+        let mut reader = DagCborReader::new(c, rx);
+        assert_eq!(reader.decode::<Ipld>().await.unwrap(), Some(first));
+        assert_eq!(reader.decode::<i64>().await.unwrap(), Some(-1));
+        assert_eq!(reader.decode::<Ipld>().await.unwrap(), None);
+
+        let mut reader = DagCborReader::new(DagCbor::lenient(DecodeLimits::default()), &[0x9f, 0x7f, 0x61, b'a', 0xff, 0xff][..]);
+        assert_eq!(reader.decode::<Ipld>().await.unwrap(), Some(ipld!(["a"])));
+        let mut reader = DagCborReader::new(c, &[0x9f, 0xff][..]);
+        assert!(reader.decode::<Ipld>().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reader_length_delimited() {
+        let c = DagCbor::default();
+        let message = c.encode_to_vec(&ipld!({ "id": 7 })).unwrap();
+        let frame = [&[message.len() as u8][..], &message, &[0x02, 0xf6, 0xf6]].concat();
+        let mut reader = DagCborReader::new(c, frame.as_slice());
+        assert_eq!(reader.decode_length_delimited::<Ipld>().await.unwrap(), Some(ipld!({ "id": 7 })));
+        // trailing data in the frame
+        assert!(reader.decode_length_delimited::<Ipld>().await.is_err());
+        assert_eq!(reader.decode_length_delimited::<Ipld>().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_reader_limits() {
+        let c = DagCbor::with_limits(DecodeLimits { max_alloc: 16, max_depth: 4, ..Default::default() });
+        // byte string claiming 2^32 bytes is refused before it is read
+        let mut reader = DagCborReader::new(c, &[0x5a, 0xff, 0xff, 0xff, 0xff, 0x00][..]);
+        assert!(matches!(reader.read_item().await, Err(CodecError::LimitExceeded(_))));
+        let nested = [vec![0x81; 10], vec![0xf6]].concat();
+        let mut reader = DagCborReader::new(c, nested.as_slice());
+        assert!(matches!(reader.read_item().await, Err(CodecError::LimitExceeded(_))));
+        let mut reader = DagCborReader::new(c, &[0xff, 0xff, 0x01][..]);
+        assert!(matches!(reader.decode_length_delimited::<Ipld>().await, Err(CodecError::LimitExceeded(_))));
+        // headers and ints count against max_alloc too: [[1, 2, ..., 20]] is 22 bytes
+        let wide = [vec![0x81, 0x94], (1..=20).collect()].concat();
+        let mut reader = DagCborReader::new(c, wide.as_slice());
+        assert!(matches!(reader.read_item().await, Err(CodecError::LimitExceeded("allocation"))));
+        // truncated
+        let mut reader = DagCborReader::new(c, &[0x82, 0x01][..]);
+        assert!(matches!(reader.read_item().await, Err(CodecError::Io(_))));
+    }
+}
@@ -0,0 +1,257 @@
+//! [StreamCodec] support for DAG-CBOR.
+
+use std::{cmp::Ordering, io::{Read, Write}};
+
+use cid::Cid;
+
+use crate::ipld::{visit::{StreamCodec, Visitor}, CodecError, Encode};
+
+use super::{dec::{check_key, read_arg, read_chunks, read_collection_len, read_entry, read_float_arg, read_header, read_end, read_link, read_text, Budget}, enc::{write_null, write_uint}, DagCbor, Header, MajorType};
+
+impl StreamCodec for DagCbor {
+    type Encoder<'a, W: Write + 'a> = DagCborEncoder<'a, W>;
+
+    /// When decoding strictly, the data item must be followed by the end of `r`.
+    fn decode_visit<R: Read, V: Visitor + ?Sized>(&self, r: &mut R, v: &mut V) -> Result<(), CodecError> {
+        visit_item(r, &mut Budget::new(self), v)?;
+        read_end(r, self.mode)
+    }
+
+    fn encoder<'a, W: Write + 'a>(&self, w: &'a mut W) -> Self::Encoder<'a, W> {
+        DagCborEncoder { c: *self, w, frames: vec![] }
+    }
+}
+
+/// Decode a data item as [Visitor] events, tracking the whole tree against one [Budget].
+pub(super) fn visit_item<R: Read, V: Visitor + ?Sized>(r: &mut R, budget: &mut Budget, v: &mut V) -> Result<(), CodecError> {
+    let header = read_header(r).map_err(|e| CodecError::Io(e))?;
+    visit_item_after(r, header, budget, v)
+}
+
+fn visit_item_after<R: Read, V: Visitor + ?Sized>(r: &mut R, header: Header, budget: &mut Budget, v: &mut V) -> Result<(), CodecError> {
+    match header.major_type {
+        MajorType::PositiveInt => v.visit_integer(read_arg(r, &header, budget)?.into()),
+        MajorType::NegativeInt => v.visit_integer(-1i128 - (read_arg(r, &header, budget)? as i128)),
+        MajorType::ByteString => v.visit_bytes(&read_chunks(r, &header, budget)?),
+        MajorType::TextString => v.visit_string(&read_text(r, &header, budget)?),
+        MajorType::Array => {
+            let len = read_collection_len(r, &header, budget)?;
+            budget.enter()?;
+            v.visit_list_start(len)?;
+            let mut read = 0;
+            while let Some(header) = read_entry(r, len, read, budget)? {
+                visit_item_after(r, header, budget, v)?;
+                read += 1;
+            }
+            budget.exit();
+            v.visit_list_end()
+        },
+        MajorType::Map => {
+            let len = read_collection_len(r, &header, budget)?;
+            budget.enter()?;
+            v.visit_map_start(len)?;
+            let mut prev: Option<String> = None;
+            let mut read = 0;
+            while let Some(header) = read_entry(r, len, read, budget)? {
+                let key = read_text(r, &header, budget)?;
+                if !check_key(prev.as_deref(), &key, budget.mode())? {
+                    budget.seen().unsorted_keys = true;
+                }
+                v.visit_key(&key)?;
+                prev = Some(key);
+                visit_item(r, budget, v)?;
+                read += 1;
+            }
+            budget.exit();
+            v.visit_map_end()
+        },
+        MajorType::Tag => {
+            if read_arg(r, &header, budget)? != 42 {
+                return Err(CodecError::MalformedData("unknown tag"));
+            }
+            v.visit_link(&read_link(r, budget)?)
+        },
+        MajorType::Other => match header {
+            Header::NULL => v.visit_null(),
+            Header::TRUE => v.visit_bool(true),
+            Header::FALSE => v.visit_bool(false),
+            Header::F64 | Header::_F32 | Header::_F16 => v.visit_float(read_float_arg(r, &header, budget)?),
+            Header::BREAK => Err(CodecError::MalformedData("unexpected break")),
+            _ => Err(CodecError::MalformedData("unknown header type")),
+        },
+    }
+}
+
+/// [Visitor] that writes DAG-CBOR.
+///
+/// Scalars and lists of known length are written as they arrive. Map entries are held encoded until
+/// the end of the map to write them in canonical key order, and lists of unknown length until their
+/// end to write their length first. Lists and maps must end with as many items as they declared,
+/// and every key must be followed by a value.
+pub struct DagCborEncoder<'a, W> {
+    c: DagCbor,
+    w: &'a mut W,
+    frames: Vec<Frame>,
+}
+
+/// An open list or map.
+enum Frame {
+    /// List of known length, with the items still to come.
+    List { remaining: usize },
+    Buffered { len: usize, buf: Vec<u8> },
+    Map {
+        /// Declared number of entries, if known.
+        len: Option<usize>,
+        /// Keys and encoded values.
+        entries: Vec<(String, Vec<u8>)>,
+        /// Whether the last key is still waiting for its value.
+        keyed: bool,
+    },
+}
+
+impl<W: Write> DagCborEncoder<'_, W> {
+    /// Where the next value is written, the innermost buffer if there is one.
+    fn out(&mut self) -> Result<&mut dyn Write, CodecError> {
+        for frame in self.frames.iter_mut().rev() {
+            match frame {
+                Frame::List { .. } => continue,
+                Frame::Buffered { buf, .. } => return Ok(buf),
+                Frame::Map { entries, .. } => return match entries.last_mut() {
+                    Some((_, buf)) => Ok(buf),
+                    None => Err(CodecError::MalformedData("map value without a key")),
+                },
+            }
+        }
+        Ok(&mut *self.w)
+    }
+
+    /// Start a value, counting it against the list or map it is in.
+    fn item(&mut self) -> Result<&mut dyn Write, CodecError> {
+        match self.frames.last_mut() {
+            Some(Frame::List { remaining: 0 }) => return Err(CodecError::MalformedData("more list items than declared")),
+            Some(Frame::List { remaining }) => *remaining -= 1,
+            Some(Frame::Buffered { len, .. }) => *len += 1,
+            Some(Frame::Map { keyed, .. }) => match keyed {
+                true => *keyed = false,
+                false => return Err(CodecError::MalformedData("map value without a key")),
+            },
+            None => {},
+        }
+        self.out()
+    }
+
+    fn encode<T: Encode<DagCbor> + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        let c = self.c;
+        value.encode(&c, &mut self.item()?)
+    }
+}
+
+impl<W: Write> Visitor for DagCborEncoder<'_, W> {
+    fn visit_null(&mut self) -> Result<(), CodecError> {
+        write_null(&mut self.item()?)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), CodecError> {
+        self.encode(&value)
+    }
+
+    fn visit_integer(&mut self, value: i128) -> Result<(), CodecError> {
+        self.encode(&value)
+    }
+
+    fn visit_float(&mut self, value: f64) -> Result<(), CodecError> {
+        self.encode(&value)
+    }
+
+    fn visit_string(&mut self, value: &str) -> Result<(), CodecError> {
+        let mut out = self.item()?;
+        write_uint(&mut out, MajorType::TextString, value.len() as u64).map_err(|e| CodecError::Io(e))?;
+        out.write_all(value.as_bytes()).map_err(|e| CodecError::Io(e))
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> Result<(), CodecError> {
+        let mut out = self.item()?;
+        write_uint(&mut out, MajorType::ByteString, value.len() as u64).map_err(|e| CodecError::Io(e))?;
+        out.write_all(value).map_err(|e| CodecError::Io(e))
+    }
+
+    fn visit_link(&mut self, cid: &Cid) -> Result<(), CodecError> {
+        self.encode(cid)
+    }
+
+    fn visit_list_start(&mut self, len: Option<usize>) -> Result<(), CodecError> {
+        let mut out = self.item()?;
+        let frame = match len {
+            Some(len) => {
+                write_uint(&mut out, MajorType::Array, len as u64).map_err(|e| CodecError::Io(e))?;
+                Frame::List { remaining: len }
+            },
+            None => Frame::Buffered { len: 0, buf: vec![] },
+        };
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    fn visit_list_end(&mut self) -> Result<(), CodecError> {
+        match self.frames.pop() {
+            Some(Frame::List { remaining: 0 }) => Ok(()),
+            Some(Frame::List { .. }) => Err(CodecError::MalformedData("fewer list items than declared")),
+            Some(Frame::Buffered { len, buf }) => {
+                let mut out = self.out()?;
+                write_uint(&mut out, MajorType::Array, len as u64).map_err(|e| CodecError::Io(e))?;
+                out.write_all(&buf).map_err(|e| CodecError::Io(e))
+            },
+            _ => Err(CodecError::MalformedData("unexpected end of list")),
+        }
+    }
+
+    fn visit_map_start(&mut self, len: Option<usize>) -> Result<(), CodecError> {
+        // Checked now, rather than when the map is written.
+        self.item()?;
+        self.frames.push(Frame::Map { len, entries: vec![], keyed: false });
+        Ok(())
+    }
+
+    fn visit_key(&mut self, key: &str) -> Result<(), CodecError> {
+        let Some(Frame::Map { len, entries, keyed }) = self.frames.last_mut() else {
+            return Err(CodecError::MalformedData("map key outside of a map"));
+        };
+        if *keyed {
+            return Err(CodecError::MalformedData("map key without a value"));
+        }
+        if len.is_some_and(|len| entries.len() == len) {
+            return Err(CodecError::MalformedData("more map entries than declared"));
+        }
+        entries.push((key.to_owned(), vec![]));
+        *keyed = true;
+        Ok(())
+    }
+
+    fn visit_map_end(&mut self) -> Result<(), CodecError> {
+        let Some(Frame::Map { len, mut entries, keyed }) = self.frames.pop() else {
+            return Err(CodecError::MalformedData("unexpected end of map"));
+        };
+        if keyed {
+            return Err(CodecError::MalformedData("map key without a value"));
+        }
+        if len.is_some_and(|len| entries.len() != len) {
+            return Err(CodecError::MalformedData("fewer map entries than declared"));
+        }
+        // Ordering for [RFC 7049](https://datatracker.ietf.org/doc/html/rfc7049#section-3.9), as in the encoder.
+        entries.sort_unstable_by(|(a, _), (b, _)| match a.len().cmp(&b.len()) {
+            Ordering::Equal => a.cmp(b),
+            order => order,
+        });
+        if entries.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(CodecError::MalformedData("duplicate map keys"));
+        }
+        let mut out = self.out()?;
+        write_uint(&mut out, MajorType::Map, entries.len() as u64).map_err(|e| CodecError::Io(e))?;
+        for (key, value) in entries {
+            write_uint(&mut out, MajorType::TextString, key.len() as u64).map_err(|e| CodecError::Io(e))?;
+            out.write_all(key.as_bytes()).map_err(|e| CodecError::Io(e))?;
+            out.write_all(&value).map_err(|e| CodecError::Io(e))?;
+        }
+        Ok(())
+    }
+}
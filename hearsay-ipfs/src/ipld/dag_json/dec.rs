@@ -0,0 +1,328 @@
+use std::io::Read;
+
+use base64::Engine;
+use cid::Cid;
+
+use crate::ipld::{visit::Visitor, CodecError, DecodeLimits};
+
+use super::BASE64;
+
+/// Longest number accepted, far beyond any integer or float that fits the data model.
+const MAX_NUMBER_LEN: usize = 1024;
+
+/// Reads a single DAG-JSON value with one byte of lookahead.
+pub(super) struct Parser<'r, R> {
+    r: &'r mut R,
+    peeked: Option<u8>,
+    limits: DecodeLimits,
+    depth: usize,
+    alloc: usize,
+}
+
+impl<'r, R: Read> Parser<'r, R> {
+    pub(super) fn new(r: &'r mut R, limits: DecodeLimits) -> Self {
+        Self { r, peeked: None, limits, depth: 0, alloc: 0 }
+    }
+
+    /// Parse a value, followed by nothing but whitespace.
+    pub(super) fn parse<V: Visitor + ?Sized>(mut self, v: &mut V) -> Result<(), CodecError> {
+        self.value(v)?;
+        match self.skip_ws()? {
+            None => Ok(()),
+            Some(_) => Err(CodecError::MalformedData("trailing data")),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, CodecError> {
+        if self.peeked.is_none() {
+            let mut buf = [0u8; 1];
+            if self.r.read(&mut buf).map_err(|e| CodecError::Io(e))? == 1 {
+                self.peeked = Some(buf[0]);
+            }
+        }
+        Ok(self.peeked)
+    }
+
+    fn next(&mut self) -> Result<u8, CodecError> {
+        self.peek()?;
+        self.peeked.take().ok_or(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into()))
+    }
+
+    /// Skip whitespace, returning the next byte without consuming it.
+    fn skip_ws(&mut self) -> Result<Option<u8>, CodecError> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.peeked = None;
+        }
+        self.peek()
+    }
+
+    fn next_ws(&mut self) -> Result<u8, CodecError> {
+        self.skip_ws()?;
+        self.next()
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), CodecError> {
+        match self.next_ws()? == expected {
+            true => Ok(()),
+            false => Err(CodecError::MalformedData("unexpected character")),
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Result<(), CodecError> {
+        for &expected in literal {
+            if self.next()? != expected {
+                return Err(CodecError::MalformedData("unexpected character"));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<(), CodecError> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            return Err(CodecError::LimitExceeded("nesting depth"));
+        }
+        Ok(())
+    }
+
+    fn collection(&self, len: usize) -> Result<(), CodecError> {
+        if len > self.limits.max_collection_len {
+            return Err(CodecError::LimitExceeded("collection length"));
+        }
+        Ok(())
+    }
+
+    fn value<V: Visitor + ?Sized>(&mut self, v: &mut V) -> Result<(), CodecError> {
+        match self.skip_ws()?.ok_or(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into()))? {
+            b'n' => {
+                self.literal(b"null")?;
+                v.visit_null()
+            },
+            b't' => {
+                self.literal(b"true")?;
+                v.visit_bool(true)
+            },
+            b'f' => {
+                self.literal(b"false")?;
+                v.visit_bool(false)
+            },
+            b'"' => {
+                let s = self.string()?;
+                v.visit_string(&s)
+            },
+            b'[' => self.list(v),
+            b'{' => self.map(v),
+            b'-' | b'0'..=b'9' => self.number(v),
+            _ => Err(CodecError::MalformedData("unexpected character")),
+        }
+    }
+
+    fn list<V: Visitor + ?Sized>(&mut self, v: &mut V) -> Result<(), CodecError> {
+        self.expect(b'[')?;
+        self.enter()?;
+        v.visit_list_start(None)?;
+        if self.skip_ws()? == Some(b']') {
+            self.peeked = None;
+        } else {
+            let mut len = 0;
+            loop {
+                len += 1;
+                self.collection(len)?;
+                self.value(v)?;
+                match self.next_ws()? {
+                    b',' => continue,
+                    b']' => break,
+                    _ => return Err(CodecError::MalformedData("unexpected character")),
+                }
+            }
+        }
+        self.depth -= 1;
+        v.visit_list_end()
+    }
+
+    fn key(&mut self) -> Result<String, CodecError> {
+        let key = self.string()?;
+        self.expect(b':')?;
+        Ok(key)
+    }
+
+    fn map<V: Visitor + ?Sized>(&mut self, v: &mut V) -> Result<(), CodecError> {
+        self.expect(b'{')?;
+        self.enter()?;
+        if self.skip_ws()? == Some(b'}') {
+            self.peeked = None;
+            self.depth -= 1;
+            v.visit_map_start(Some(0))?;
+            return v.visit_map_end();
+        }
+        let key = self.key()?;
+        if key == "/" {
+            self.reserved(v)?;
+            self.depth -= 1;
+            return Ok(());
+        }
+        v.visit_map_start(None)?;
+        v.visit_key(&key)?;
+        self.value(v)?;
+        let mut len = 1;
+        loop {
+            match self.next_ws()? {
+                b',' => {},
+                b'}' => break,
+                _ => return Err(CodecError::MalformedData("unexpected character")),
+            }
+            len += 1;
+            self.collection(len)?;
+            let key = self.key()?;
+            if key == "/" {
+                return Err(CodecError::MalformedData("\"/\" is a reserved map key"));
+            }
+            v.visit_key(&key)?;
+            self.value(v)?;
+        }
+        self.depth -= 1;
+        v.visit_map_end()
+    }
+
+    /// The rest of a map whose first key is `"/"`, which must be a link or bytes.
+    fn reserved<V: Visitor + ?Sized>(&mut self, v: &mut V) -> Result<(), CodecError> {
+        let misuse = CodecError::MalformedData("\"/\" is a reserved map key");
+        match self.skip_ws()? {
+            Some(b'"') => {
+                let cid = Cid::try_from(self.string()?.as_str()).map_err(|_| CodecError::MalformedData("invalid cid"))?;
+                if self.next_ws()? != b'}' {
+                    return Err(misuse);
+                }
+                v.visit_link(&cid)
+            },
+            Some(b'{') => {
+                self.peeked = None;
+                self.skip_ws()?;
+                if self.key()? != "bytes" || self.skip_ws()? != Some(b'"') {
+                    return Err(misuse);
+                }
+                let data = self.string()?;
+                if self.next_ws()? != b'}' || self.next_ws()? != b'}' {
+                    return Err(misuse);
+                }
+                let bytes = BASE64.decode(data).map_err(|_| CodecError::MalformedData("invalid base64"))?;
+                v.visit_bytes(&bytes)
+            },
+            _ => Err(misuse),
+        }
+    }
+
+    /// Read a string, including the quotes.
+    fn string(&mut self) -> Result<String, CodecError> {
+        self.expect(b'"')?;
+        let mut buf = vec![];
+        loop {
+            if self.alloc + buf.len() >= self.limits.max_alloc {
+                return Err(CodecError::LimitExceeded("allocation"));
+            }
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(CodecError::MalformedData("invalid escape")),
+                    };
+                    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                0x00..=0x1f => return Err(CodecError::MalformedData("unescaped control character")),
+                b => buf.push(b),
+            }
+        }
+        self.alloc += buf.len();
+        String::from_utf8(buf).map_err(|_| CodecError::MalformedData("bytes are not in utf-8 string format"))
+    }
+
+    /// Character of a `\uXXXX` escape, or of two for a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, CodecError> {
+        let invalid = CodecError::MalformedData("invalid unicode escape");
+        let high = self.hex4()?;
+        let code = match high {
+            0xd800..=0xdbff => {
+                if self.next()? != b'\\' || self.next()? != b'u' {
+                    return Err(invalid);
+                }
+                let low = self.hex4()?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(invalid);
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            },
+            code => code,
+        };
+        char::from_u32(code).ok_or(invalid)
+    }
+
+    fn hex4(&mut self) -> Result<u32, CodecError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = (self.next()? as char).to_digit(16).ok_or(CodecError::MalformedData("invalid unicode escape"))?;
+            code = code << 4 | digit;
+        }
+        Ok(code)
+    }
+
+    /// Read a number, which is a float if it has a fraction or exponent. See [RFC 8259](https://datatracker.ietf.org/doc/html/rfc8259#section-6).
+    fn number<V: Visitor + ?Sized>(&mut self, v: &mut V) -> Result<(), CodecError> {
+        let mut s = String::new();
+        while let Some(b @ (b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')) = self.peek()? {
+            if s.len() == MAX_NUMBER_LEN {
+                return Err(CodecError::NumberOutOfBounds);
+            }
+            s.push(b as char);
+            self.peeked = None;
+        }
+        if !valid_number(s.as_bytes()) {
+            return Err(CodecError::MalformedData("invalid number"));
+        }
+        if s.contains(['.', 'e', 'E']) {
+            let f = s.parse::<f64>().map_err(|_| CodecError::MalformedData("invalid number"))?;
+            if !f.is_finite() {
+                return Err(CodecError::NumberOutOfBounds);
+            }
+            v.visit_float(f)
+        } else {
+            v.visit_integer(s.parse().map_err(|_| CodecError::NumberOutOfBounds)?)
+        }
+    }
+}
+
+/// `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`
+fn valid_number(s: &[u8]) -> bool {
+    let digits = |s: &[u8]| s.iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut i = usize::from(s.first() == Some(&b'-'));
+    match (s.get(i), digits(&s[i..])) {
+        (Some(b'0'), 1) => i += 1,
+        (Some(b'1'..=b'9'), n) => i += n,
+        _ => return false,
+    }
+    if s.get(i) == Some(&b'.') {
+        match digits(&s[i + 1..]) {
+            0 => return false,
+            n => i += 1 + n,
+        }
+    }
+    if let Some(b'e' | b'E') = s.get(i) {
+        i += 1;
+        if let Some(b'+' | b'-') = s.get(i) {
+            i += 1;
+        }
+        match digits(&s[i..]) {
+            0 => return false,
+            n => i += n,
+        }
+    }
+    i == s.len()
+}
@@ -0,0 +1,173 @@
+use std::io::Write;
+
+use base64::Engine;
+use cid::Cid;
+
+use crate::ipld::{visit::Visitor, CodecError};
+
+use super::BASE64;
+
+/// [Visitor] that writes DAG-JSON without whitespace.
+///
+/// Scalars and lists are written as they arrive. Map entries are held encoded until the end of the
+/// map, to write them in key order.
+pub struct DagJsonEncoder<'a, W> {
+    w: &'a mut W,
+    frames: Vec<Frame>,
+}
+
+/// An open list or map.
+enum Frame {
+    List { empty: bool },
+    /// Keys and encoded values.
+    Map(Vec<(String, Vec<u8>)>),
+}
+
+impl<'a, W: Write> DagJsonEncoder<'a, W> {
+    pub(super) fn new(w: &'a mut W) -> Self {
+        Self { w, frames: vec![] }
+    }
+
+    /// Where the next value is written, the innermost map entry if there is one.
+    fn out(&mut self) -> Result<&mut dyn Write, CodecError> {
+        for frame in self.frames.iter_mut().rev() {
+            if let Frame::Map(entries) = frame {
+                return match entries.last_mut() {
+                    Some((_, buf)) => Ok(buf),
+                    None => Err(CodecError::MalformedData("map value without a key")),
+                };
+            }
+        }
+        Ok(&mut *self.w)
+    }
+
+    /// Start a value, separating it from the previous item of a list.
+    fn item(&mut self) -> Result<&mut dyn Write, CodecError> {
+        let comma = match self.frames.last_mut() {
+            Some(Frame::List { empty }) => !std::mem::replace(empty, false),
+            _ => false,
+        };
+        let out = self.out()?;
+        if comma {
+            out.write_all(b",").map_err(|e| CodecError::Io(e))?;
+        }
+        Ok(out)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CodecError> {
+        self.item()?.write_all(bytes).map_err(|e| CodecError::Io(e))
+    }
+}
+
+/// Write a JSON string, escaping only what [RFC 8259](https://datatracker.ietf.org/doc/html/rfc8259#section-7) requires.
+fn write_string(w: &mut dyn Write, s: &str) -> Result<(), CodecError> {
+    let mut buf = Vec::with_capacity(s.len() + 2);
+    buf.push(b'"');
+    for b in s.bytes() {
+        match b {
+            b'"' => buf.extend_from_slice(b"\\\""),
+            b'\\' => buf.extend_from_slice(b"\\\\"),
+            b'\n' => buf.extend_from_slice(b"\\n"),
+            b'\r' => buf.extend_from_slice(b"\\r"),
+            b'\t' => buf.extend_from_slice(b"\\t"),
+            0x08 => buf.extend_from_slice(b"\\b"),
+            0x0c => buf.extend_from_slice(b"\\f"),
+            0x00..=0x1f => buf.extend_from_slice(format!("\\u{b:04x}").as_bytes()),
+            _ => buf.push(b),
+        }
+    }
+    buf.push(b'"');
+    w.write_all(&buf).map_err(|e| CodecError::Io(e))
+}
+
+impl<W: Write> Visitor for DagJsonEncoder<'_, W> {
+    fn visit_null(&mut self) -> Result<(), CodecError> {
+        self.write(b"null")
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), CodecError> {
+        self.write(if value { b"true" } else { b"false" })
+    }
+
+    fn visit_integer(&mut self, value: i128) -> Result<(), CodecError> {
+        self.write(value.to_string().as_bytes())
+    }
+
+    fn visit_float(&mut self, value: f64) -> Result<(), CodecError> {
+        if !value.is_finite() {
+            return Err(CodecError::NumberOutOfBounds);
+        }
+        // Debug formatting is the shortest round trip, and always has a `.` or exponent to tell it from an integer.
+        self.write(format!("{value:?}").as_bytes())
+    }
+
+    fn visit_string(&mut self, value: &str) -> Result<(), CodecError> {
+        write_string(self.item()?, value)
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> Result<(), CodecError> {
+        let out = self.item()?;
+        out.write_all(br#"{"/":{"bytes":""#).map_err(|e| CodecError::Io(e))?;
+        out.write_all(BASE64.encode(value).as_bytes()).map_err(|e| CodecError::Io(e))?;
+        out.write_all(br#""}}"#).map_err(|e| CodecError::Io(e))
+    }
+
+    fn visit_link(&mut self, cid: &Cid) -> Result<(), CodecError> {
+        let out = self.item()?;
+        out.write_all(br#"{"/":""#).map_err(|e| CodecError::Io(e))?;
+        out.write_all(cid.to_string().as_bytes()).map_err(|e| CodecError::Io(e))?;
+        out.write_all(br#""}"#).map_err(|e| CodecError::Io(e))
+    }
+
+    fn visit_list_start(&mut self, _len: Option<usize>) -> Result<(), CodecError> {
+        self.write(b"[")?;
+        self.frames.push(Frame::List { empty: true });
+        Ok(())
+    }
+
+    fn visit_list_end(&mut self) -> Result<(), CodecError> {
+        let Some(Frame::List { .. }) = self.frames.pop() else {
+            return Err(CodecError::MalformedData("unexpected end of list"));
+        };
+        self.out()?.write_all(b"]").map_err(|e| CodecError::Io(e))
+    }
+
+    fn visit_map_start(&mut self, _len: Option<usize>) -> Result<(), CodecError> {
+        // Checked now, rather than when the map is written.
+        self.item()?;
+        self.frames.push(Frame::Map(vec![]));
+        Ok(())
+    }
+
+    fn visit_key(&mut self, key: &str) -> Result<(), CodecError> {
+        let Some(Frame::Map(entries)) = self.frames.last_mut() else {
+            return Err(CodecError::MalformedData("map key outside of a map"));
+        };
+        if key == "/" {
+            return Err(CodecError::MalformedData("\"/\" is a reserved map key"));
+        }
+        entries.push((key.to_owned(), vec![]));
+        Ok(())
+    }
+
+    fn visit_map_end(&mut self) -> Result<(), CodecError> {
+        let Some(Frame::Map(mut entries)) = self.frames.pop() else {
+            return Err(CodecError::MalformedData("unexpected end of map"));
+        };
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        if entries.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(CodecError::MalformedData("duplicate map keys"));
+        }
+        let out = self.out()?;
+        out.write_all(b"{").map_err(|e| CodecError::Io(e))?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",").map_err(|e| CodecError::Io(e))?;
+            }
+            write_string(out, key)?;
+            out.write_all(b":").map_err(|e| CodecError::Io(e))?;
+            out.write_all(value).map_err(|e| CodecError::Io(e))?;
+        }
+        out.write_all(b"}").map_err(|e| CodecError::Io(e))
+    }
+}
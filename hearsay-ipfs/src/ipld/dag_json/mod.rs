@@ -1,23 +1,142 @@
+//! IPLD DAG-JSON Implimentation.
+
 use std::io::{Read, Seek, Write};
 
-use super::{Codec, CodecError, Decode, Encode, Ipld};
+use base64::{alphabet, engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig}};
+use dec::Parser;
+pub use enc::DagJsonEncoder;
+
+use super::{visit::{IpldBuilder, StreamCodec, Visitor}, Codec, CodecError, Decode, DecodeLimits, Encode, Ipld};
+
+mod dec;
+mod enc;
+
+/// Bytes are standard base64 without padding, padding is accepted when decoding.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
 
 /// IPLD DAG-JSON. See [IPLD spec](https://ipld.io/specs/codecs/dag-json/spec/) and [DAG-JSON spec](https://datatracker.ietf.org/doc/html/rfc8259).
-pub struct DagJson;
+///
+/// Links are `{"/": "<cid>"}` and bytes are `{"/": {"bytes": "<base64>"}}`. Any other map with a
+/// `"/"` key is rejected, both when encoding and decoding.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DagJson {
+    /// Bounds applied while decoding.
+    pub limits: DecodeLimits,
+}
+
+impl DagJson {
+    pub fn with_limits(limits: DecodeLimits) -> Self {
+        Self { limits }
+    }
+}
 
 impl Codec for DagJson {
     /// See <https://github.com/multiformats/multicodec/blob/master/table.csv>
     const CODE: u64 = 0x0129;
+
+    type Budget = ();
+}
+
+impl StreamCodec for DagJson {
+    type Encoder<'a, W: Write + 'a> = DagJsonEncoder<'a, W>;
+
+    fn decode_visit<R: Read, V: Visitor + ?Sized>(&self, r: &mut R, v: &mut V) -> Result<(), CodecError> {
+        Parser::new(r, self.limits).parse(v)
+    }
+
+    fn encoder<'a, W: Write + 'a>(&self, w: &'a mut W) -> Self::Encoder<'a, W> {
+        DagJsonEncoder::new(w)
+    }
 }
 
 impl Encode<DagJson> for Ipld {
     fn encode<W: Write>(&self, c: &DagJson, w: &mut W) -> Result<(), CodecError> {
-        todo!()
+        self.visit(&mut c.encoder(w))
     }
 }
 
 impl Decode<DagJson> for Ipld {
     fn decode<R: Read + Seek>(c: &DagJson, r: &mut R) -> Result<Self, CodecError> {
-        todo!()
+        let mut builder = IpldBuilder::default();
+        c.decode_visit(r, &mut builder)?;
+        builder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use cid::Cid;
+
+    use crate::{ipld, ipld::{visit::transcode, DagCbor}};
+
+    use super::*;
+
+    #[test]
+    fn test_dag_json_roundtrip() {
+        let c = DagJson::default();
+        let cid = Cid::try_from("bafyreidykglsfhoixmivffc5uwhcgshx4j465xwqntbmu43nb2dzqwfvae").unwrap();
+        let value = ipld!({
+            "link": cid,
+            "bytes": b"banana",
+            "list": [1, -1, 1.0, 0.5, -0.0, 1e300, null, true, false],
+            "text": "quote \" slash \\ newline \n tab \t nul \u{0} é",
+            "aa": {},
+            "b": [],
+        });
+        let json = c.encode_to_vec(&value).unwrap();
+        assert_eq!(String::from_utf8(json.clone()).unwrap(), concat!(
+            r#"{"aa":{},"b":[],"bytes":{"/":{"bytes":"YmFuYW5h"}},"#,
+            r#""link":{"/":"bafyreidykglsfhoixmivffc5uwhcgshx4j465xwqntbmu43nb2dzqwfvae"},"#,
+            r#""list":[1,-1,1.0,0.5,-0.0,1e300,null,true,false],"#,
+            r#""text":"quote \" slash \\ newline \n tab \t nul \u0000 é"}"#,
+        ));
+        assert_eq!(c.decode_from_slice::<Ipld>(&json).unwrap(), value);
+
+        let mut cbor = vec![];
+        transcode(&c, &mut json.as_slice(), &DagCbor::default(), &mut cbor).unwrap();
+        assert_eq!(cbor, DagCbor::default().encode_to_vec(&value).unwrap());
+        let mut back = vec![];
+        transcode(&DagCbor::default(), &mut cbor.as_slice(), &c, &mut back).unwrap();
+        assert_eq!(back, json);
+    }
+
+    #[test]
+    fn test_dag_json_decode() {
+        let c = DagJson::default();
+        let decode = |s: &str| c.decode_from_slice::<Ipld>(s.as_bytes());
+        assert_eq!(decode(" { \"a\" : [ 1 , 2.5e1 , \"\\u00e9\\ud83d\\ude00\" ] } \n").unwrap(), ipld!({ "a": [1, 25.0, "é😀"] }));
+        assert_eq!(decode(r#"{"/":{"bytes":"YmFuYW5hcw=="}}"#).unwrap(), Ipld::Bytes(Bytes::from_static(b"bananas")));
+        assert_eq!(decode("-0").unwrap(), ipld!(0));
+        assert_eq!(decode("18446744073709551615").unwrap(), ipld!(u64::MAX));
+
+        for invalid in [
+            r#"{"/":"not a cid"}"#,
+            r#"{"/":{"bytes":"YmFu","x":1}}"#,
+            r#"{"/":"bafyreidykglsfhoixmivffc5uwhcgshx4j465xwqntbmu43nb2dzqwfvae","a":1}"#,
+            r#"{"a":1,"/":2}"#,
+            r#"{"/":1}"#,
+            r#"{"a":1,"a":2}"#,
+            "[1,]", "01", "1.", "\"\\ud83d\"", "\"\u{1}\"", "nul", "1 2", "[1", "1e400",
+        ] {
+            assert!(decode(invalid).is_err(), "{invalid}");
+        }
+        let nested = "[".repeat(10) + &"]".repeat(10);
+        assert!(decode(&nested).is_ok());
+        let shallow = DagJson::with_limits(DecodeLimits { max_depth: 4, ..Default::default() });
+        assert!(matches!(shallow.decode_from_slice::<Ipld>(nested.as_bytes()), Err(CodecError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_dag_json_encode_reserved() {
+        let c = DagJson::default();
+        assert!(c.encode_to_vec(&ipld!({ "/": "x" })).is_err());
+        assert!(c.encode_to_vec(&ipld!({ "a": { "/": 1, "b": 2 } })).is_err());
+        assert!(c.encode_to_vec(&ipld!(f64::NAN)).is_err());
     }
 }
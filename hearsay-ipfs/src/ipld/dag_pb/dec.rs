@@ -0,0 +1,115 @@
+use std::io::Read;
+
+use bytes::Bytes;
+use cid::Cid;
+
+use crate::ipld::CodecError;
+
+use super::{DagPb, PbLink, PbNode};
+
+/// Protobuf wire types, see <https://protobuf.dev/programming-guides/encoding/#structure>.
+const VARINT: u64 = 0;
+const LEN: u64 = 2;
+
+/// Read a whole node, within the [DagPb] limits.
+pub(super) fn read_node<R: Read>(c: &DagPb, r: &mut R) -> Result<PbNode, CodecError> {
+    let max = c.limits.max_alloc;
+    let mut buf = vec![];
+    r.take(max as u64 + 1).read_to_end(&mut buf).map_err(|e| CodecError::Io(e))?;
+    if buf.len() > max {
+        return Err(CodecError::LimitExceeded("allocation"));
+    }
+    let buf = Bytes::from(buf);
+    let mut rest = &buf[..];
+    let mut node = PbNode::default();
+    while !rest.is_empty() {
+        let (field, wire) = read_key(&mut rest)?;
+        if wire != LEN {
+            return Err(CodecError::MalformedData("unexpected DAG-PB wire type"));
+        }
+        let value = read_len(&mut rest)?;
+        match field {
+            1 if node.data.is_some() => return Err(CodecError::MalformedData("duplicate DAG-PB Data")),
+            1 => node.data = Some(buf.slice_ref(value)),
+            2 if node.data.is_some() => return Err(CodecError::MalformedData("DAG-PB Links must come before Data")),
+            2 => {
+                if node.links.len() == c.limits.max_collection_len {
+                    return Err(CodecError::LimitExceeded("collection length"));
+                }
+                node.links.push(read_link(value)?);
+            },
+            _ => return Err(CodecError::MalformedData("unknown DAG-PB node field")),
+        }
+    }
+    Ok(node)
+}
+
+/// Fields must be in order, and each at most once.
+fn read_link(mut buf: &[u8]) -> Result<PbLink, CodecError> {
+    let (mut hash, mut name, mut tsize) = (None, None, None);
+    let mut last = 0;
+    while !buf.is_empty() {
+        let (field, wire) = read_key(&mut buf)?;
+        if field <= last {
+            return Err(CodecError::MalformedData("DAG-PB link fields out of order"));
+        }
+        last = field;
+        match (field, wire) {
+            (1, LEN) => {
+                let mut bytes = read_len(&mut buf)?;
+                let cid = Cid::read_bytes(&mut bytes).map_err(|_| CodecError::MalformedData("invalid cid data"))?;
+                if !bytes.is_empty() {
+                    return Err(CodecError::MalformedData("trailing cid data"));
+                }
+                hash = Some(cid);
+            },
+            (2, LEN) => {
+                let bytes = read_len(&mut buf)?;
+                let s = std::str::from_utf8(bytes).map_err(|_| CodecError::MalformedData("bytes are not in utf-8 string format"))?;
+                name = Some(s.to_owned());
+            },
+            (3, VARINT) => tsize = Some(read_varint(&mut buf)?),
+            (1..=3, _) => return Err(CodecError::MalformedData("unexpected DAG-PB wire type")),
+            _ => return Err(CodecError::MalformedData("unknown DAG-PB link field")),
+        }
+    }
+    let hash = hash.ok_or(CodecError::MalformedData("DAG-PB link is missing its Hash"))?;
+    Ok(PbLink { hash, name, tsize })
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, CodecError> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let (&b, rest) = buf.split_first().ok_or(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into()))?;
+        *buf = rest;
+        // The 10th byte only has room for the top bit.
+        if i == 9 && b > 1 {
+            break;
+        }
+        value |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CodecError::NumberOutOfBounds)
+}
+
+/// Field number and wire type.
+fn read_key(buf: &mut &[u8]) -> Result<(u64, u64), CodecError> {
+    let key = read_varint(buf)?;
+    match key >> 3 {
+        0 => Err(CodecError::MalformedData("invalid protobuf field number")),
+        field => Ok((field, key & 0x7)),
+    }
+}
+
+/// Length-delimited value.
+fn read_len<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], CodecError> {
+    let len = usize::try_from(read_varint(buf)?).map_err(|_| CodecError::NumberOutOfBounds)?;
+    if buf.len() < len {
+        return Err(CodecError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
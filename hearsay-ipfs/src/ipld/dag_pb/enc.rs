@@ -0,0 +1,43 @@
+use super::{PbLink, PbNode};
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Length-delimited field.
+fn write_len(buf: &mut Vec<u8>, field: u8, value: &[u8]) {
+    buf.push(field << 3 | 2);
+    write_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+fn encode_link(link: &PbLink) -> Vec<u8> {
+    let mut buf = vec![];
+    write_len(&mut buf, 1, &link.hash.to_bytes());
+    if let Some(name) = &link.name {
+        write_len(&mut buf, 2, name.as_bytes());
+    }
+    if let Some(tsize) = link.tsize {
+        buf.push(3 << 3);
+        write_varint(&mut buf, tsize);
+    }
+    buf
+}
+
+/// Links, sorted by name with a missing name sorting as empty, then Data.
+pub(super) fn encode_node(node: &PbNode) -> Vec<u8> {
+    let mut links = Vec::from_iter(&node.links);
+    links.sort_by(|a, b| a.name.as_deref().unwrap_or_default().as_bytes().cmp(b.name.as_deref().unwrap_or_default().as_bytes()));
+    let mut buf = vec![];
+    for link in links {
+        write_len(&mut buf, 2, &encode_link(link));
+    }
+    if let Some(data) = &node.data {
+        write_len(&mut buf, 1, data);
+    }
+    buf
+}
@@ -1,23 +1,308 @@
-use std::io::{Read, Seek, Write};
+//! IPLD DAG-PB Implimentation.
 
-use super::{Codec, CodecError, Decode, Encode, Ipld};
+use std::{collections::BTreeMap, io::{Read, Seek, Write}};
+
+use bytes::Bytes;
+use cid::Cid;
+
+use super::{visit::{IpldBuilder, StreamCodec, Visitor}, Codec, CodecError, Decode, DecodeLimits, Encode, Ipld};
+
+mod dec;
+mod enc;
 
 /// IPLD DAG-Protobuf. See {IPLD spec}(https://ipld.io/specs/codecs/dag-pb/spec/)
-pub struct DagPb;
+///
+/// Only the `{Data, Links: [{Hash, Name, Tsize}]}` form can be encoded. Decoding is strict about
+/// field order, duplicate and unknown fields.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DagPb {
+    /// Bounds applied while decoding.
+    pub limits: DecodeLimits,
+}
+
+impl DagPb {
+    pub fn with_limits(limits: DecodeLimits) -> Self {
+        Self { limits }
+    }
+}
 
 impl Codec for DagPb {
     /// See <https://github.com/multiformats/multicodec/blob/master/table.csv>
     const CODE: u64 = 0x70;
+
+    type Budget = ();
+}
+
+/// A DAG-PB node, `PBNode` in the spec.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PbNode {
+    /// Sorted by name when encoded.
+    pub links: Vec<PbLink>,
+    pub data: Option<Bytes>,
+}
+
+/// A link of a [PbNode], `PBLink` in the spec.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbLink {
+    pub hash: Cid,
+    pub name: Option<String>,
+    /// Cumulative size of the linked DAG.
+    pub tsize: Option<u64>,
+}
+
+impl Encode<DagPb> for PbNode {
+    fn encode<W: Write>(&self, _c: &DagPb, w: &mut W) -> Result<(), CodecError> {
+        w.write_all(&enc::encode_node(self)).map_err(|e| CodecError::Io(e))
+    }
+}
+
+impl Decode<DagPb> for PbNode {
+    fn decode<R: Read + Seek>(c: &DagPb, r: &mut R) -> Result<Self, CodecError> {
+        dec::read_node(c, r)
+    }
 }
 
 impl Encode<DagPb> for Ipld {
     fn encode<W: Write>(&self, c: &DagPb, w: &mut W) -> Result<(), CodecError> {
-        todo!()
+        PbNode::try_from(self)?.encode(c, w)
     }
 }
 
 impl Decode<DagPb> for Ipld {
     fn decode<R: Read + Seek>(c: &DagPb, r: &mut R) -> Result<Self, CodecError> {
-        todo!()
+        Ok(PbNode::decode(c, r)?.into())
+    }
+}
+
+impl From<PbNode> for Ipld {
+    fn from(value: PbNode) -> Self {
+        let links = value.links.into_iter().map(|link| {
+            let mut map = BTreeMap::from([(String::from("Hash"), Ipld::Link(link.hash))]);
+            if let Some(name) = link.name {
+                map.insert(String::from("Name"), Ipld::String(name));
+            }
+            if let Some(tsize) = link.tsize {
+                map.insert(String::from("Tsize"), Ipld::from(tsize));
+            }
+            Ipld::Map(map)
+        });
+        let mut map = BTreeMap::from([(String::from("Links"), Ipld::List(links.collect()))]);
+        if let Some(data) = value.data {
+            map.insert(String::from("Data"), Ipld::Bytes(data));
+        }
+        Ipld::Map(map)
+    }
+}
+
+impl TryFrom<&Ipld> for PbNode {
+    type Error = CodecError;
+    fn try_from(value: &Ipld) -> Result<Self, Self::Error> {
+        let Ipld::Map(node) = value else {
+            return Err(CodecError::MalformedData("DAG-PB node must be a map"));
+        };
+        if node.keys().any(|k| k != "Data" && k != "Links") {
+            return Err(CodecError::MalformedData("unknown DAG-PB node field"));
+        }
+        let data = match node.get("Data") {
+            None => None,
+            Some(Ipld::Bytes(data)) => Some(data.clone()),
+            Some(_) => return Err(CodecError::MalformedData("DAG-PB Data must be bytes")),
+        };
+        let Some(Ipld::List(links)) = node.get("Links") else {
+            return Err(CodecError::MalformedData("DAG-PB Links must be a list"));
+        };
+        let links = links.iter().map(|link| {
+            let Ipld::Map(link) = link else {
+                return Err(CodecError::MalformedData("DAG-PB link must be a map"));
+            };
+            if link.keys().any(|k| k != "Hash" && k != "Name" && k != "Tsize") {
+                return Err(CodecError::MalformedData("unknown DAG-PB link field"));
+            }
+            let Some(Ipld::Link(hash)) = link.get("Hash") else {
+                return Err(CodecError::MalformedData("DAG-PB link Hash must be a link"));
+            };
+            let name = match link.get("Name") {
+                None => None,
+                Some(Ipld::String(name)) => Some(name.clone()),
+                Some(_) => return Err(CodecError::MalformedData("DAG-PB link Name must be a string")),
+            };
+            let tsize = match link.get("Tsize") {
+                None => None,
+                Some(Ipld::Integer(tsize)) => Some(u64::try_from(*tsize).map_err(|_| CodecError::NumberOutOfBounds)?),
+                Some(_) => return Err(CodecError::MalformedData("DAG-PB link Tsize must be an integer")),
+            };
+            Ok(PbLink { hash: *hash, name, tsize })
+        });
+        Ok(PbNode { links: links.collect::<Result<_, _>>()?, data })
+    }
+}
+
+impl StreamCodec for DagPb {
+    type Encoder<'a, W: Write + 'a> = DagPbEncoder<'a, W>;
+
+    /// A node is small and flat, so it is decoded whole before its events are sent.
+    fn decode_visit<R: Read, V: Visitor + ?Sized>(&self, r: &mut R, v: &mut V) -> Result<(), CodecError> {
+        Ipld::from(dec::read_node(self, r)?).visit(v)
+    }
+
+    fn encoder<'a, W: Write + 'a>(&self, w: &'a mut W) -> Self::Encoder<'a, W> {
+        DagPbEncoder { w, builder: IpldBuilder::default(), depth: 0 }
+    }
+}
+
+/// [Visitor] that writes DAG-PB, once the whole node has arrived.
+pub struct DagPbEncoder<'a, W> {
+    w: &'a mut W,
+    builder: IpldBuilder,
+    depth: usize,
+}
+
+impl<W: Write> DagPbEncoder<'_, W> {
+    /// Write the node once the events of a whole value have arrived.
+    fn done(&mut self, result: Result<(), CodecError>) -> Result<(), CodecError> {
+        result?;
+        if self.depth > 0 {
+            return Ok(());
+        }
+        let node = PbNode::try_from(&std::mem::take(&mut self.builder).finish()?)?;
+        self.w.write_all(&enc::encode_node(&node)).map_err(|e| CodecError::Io(e))
+    }
+}
+
+impl<W: Write> Visitor for DagPbEncoder<'_, W> {
+    fn visit_null(&mut self) -> Result<(), CodecError> {
+        let result = self.builder.visit_null();
+        self.done(result)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), CodecError> {
+        let result = self.builder.visit_bool(value);
+        self.done(result)
+    }
+
+    fn visit_integer(&mut self, value: i128) -> Result<(), CodecError> {
+        let result = self.builder.visit_integer(value);
+        self.done(result)
+    }
+
+    fn visit_float(&mut self, value: f64) -> Result<(), CodecError> {
+        let result = self.builder.visit_float(value);
+        self.done(result)
+    }
+
+    fn visit_string(&mut self, value: &str) -> Result<(), CodecError> {
+        let result = self.builder.visit_string(value);
+        self.done(result)
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> Result<(), CodecError> {
+        let result = self.builder.visit_bytes(value);
+        self.done(result)
+    }
+
+    fn visit_link(&mut self, cid: &Cid) -> Result<(), CodecError> {
+        let result = self.builder.visit_link(cid);
+        self.done(result)
+    }
+
+    fn visit_list_start(&mut self, len: Option<usize>) -> Result<(), CodecError> {
+        self.depth += 1;
+        self.builder.visit_list_start(len)
+    }
+
+    fn visit_list_end(&mut self) -> Result<(), CodecError> {
+        self.depth = self.depth.saturating_sub(1);
+        let result = self.builder.visit_list_end();
+        self.done(result)
+    }
+
+    fn visit_map_start(&mut self, len: Option<usize>) -> Result<(), CodecError> {
+        self.depth += 1;
+        self.builder.visit_map_start(len)
+    }
+
+    fn visit_key(&mut self, key: &str) -> Result<(), CodecError> {
+        self.builder.visit_key(key)
+    }
+
+    fn visit_map_end(&mut self) -> Result<(), CodecError> {
+        self.depth = self.depth.saturating_sub(1);
+        let result = self.builder.visit_map_end();
+        self.done(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use multihash_codetable::{Code, MultihashDigest};
+
+    use crate::{ipld, ipld::{visit::transcode, DagJson}};
+
+    use super::*;
+
+    #[test]
+    fn test_dag_pb_roundtrip() {
+        let c = DagPb::default();
+        // Empty UnixFS directory.
+        let empty_dir = [0x0a, 0x02, 0x08, 0x01];
+        let node = c.decode_from_slice::<Ipld>(&empty_dir).unwrap();
+        assert_eq!(node, ipld!({ "Data": b"\x08\x01", "Links": [] }));
+        assert_eq!(c.encode_to_vec(&node).unwrap(), empty_dir);
+        let cid = Cid::new_v0(Code::Sha2_256.digest(&empty_dir)).unwrap();
+        assert_eq!(cid.to_string(), "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn");
+
+        assert_eq!(c.decode_from_slice::<Ipld>(&[]).unwrap(), ipld!({ "Links": [] }));
+        assert_eq!(c.encode_to_vec(&ipld!({ "Links": [] })).unwrap(), []);
+
+        let node = PbNode {
+            links: vec![
+                PbLink { hash: cid, name: Some(String::from("b")), tsize: Some(4) },
+                PbLink { hash: cid, name: None, tsize: None },
+                PbLink { hash: cid, name: Some(String::from("a")), tsize: Some(0) },
+            ],
+            data: Some(Bytes::from_static(b"banana")),
+        };
+        let bytes = c.encode_to_vec(&node).unwrap();
+        let decoded = c.decode_from_slice::<PbNode>(&bytes).unwrap();
+        let names = decoded.links.iter().map(|l| l.name.as_deref()).collect::<Vec<_>>();
+        assert_eq!(names, [None, Some("a"), Some("b")]);
+        assert_eq!(decoded.links[1].tsize, Some(0));
+        assert_eq!(c.encode_to_vec(&Ipld::from(decoded.clone())).unwrap(), bytes);
+
+        let mut json = vec![];
+        transcode(&c, &mut bytes.as_slice(), &DagJson::default(), &mut json).unwrap();
+        let mut back = vec![];
+        transcode(&DagJson::default(), &mut json.as_slice(), &c, &mut back).unwrap();
+        assert_eq!(back, bytes);
+    }
+
+    #[test]
+    fn test_dag_pb_strict() {
+        let c = DagPb::default();
+        let decode = |bytes: &[u8]| c.decode_from_slice::<PbNode>(bytes);
+        let cid = Cid::new_v0(Code::Sha2_256.digest(b"")).unwrap().to_bytes();
+        let link = [&[0x0a, cid.len() as u8][..], &cid].concat();
+        let with_link = |link: &[u8]| [&[0x12, link.len() as u8][..], link].concat();
+        assert!(decode(&with_link(&link)).is_ok());
+        // Data before Links
+        assert!(decode(&[&[0x0a, 0x00][..], &with_link(&link)].concat()).is_err());
+        // duplicate Data
+        assert!(decode(&[0x0a, 0x00, 0x0a, 0x00]).is_err());
+        // unknown node field
+        assert!(decode(&[0x1a, 0x00]).is_err());
+        // missing Hash
+        assert!(decode(&with_link(&[0x12, 0x01, b'a'])).is_err());
+        // Name before Hash
+        assert!(decode(&with_link(&[&[0x12, 0x01, b'a'][..], &link].concat())).is_err());
+        // Tsize with the wrong wire type
+        assert!(decode(&with_link(&[&link[..], &[0x1a, 0x00]].concat())).is_err());
+        // unknown link field
+        assert!(decode(&with_link(&[&link[..], &[0x20, 0x00]].concat())).is_err());
+        // truncated
+        assert!(decode(&[0x0a, 0x05, 0x00]).is_err());
+
+        assert!(c.encode_to_vec(&ipld!({ "Links": [], "Other": 1 })).is_err());
+        assert!(c.encode_to_vec(&ipld!({ "Data": b"" })).is_err());
+        assert!(c.encode_to_vec(&ipld!({ "Links": [{ "Name": "a" }] })).is_err());
     }
 }
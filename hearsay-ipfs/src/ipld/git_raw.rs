@@ -0,0 +1,442 @@
+//! Git objects as IPLD, linking to other objects by the SHA-1 [Cid]s of their raw form.
+//! See <https://github.com/ipfs/go-ipld-git> and <https://git-scm.com/book/en/v2/Git-Internals-Git-Objects>.
+
+use std::{collections::BTreeMap, io::{Read, Seek, Write}, str};
+
+use bytes::Bytes;
+use cid::{multihash::Multihash, Cid, Version};
+
+use crate::{hasher::SHA1, CidPrefix};
+
+use super::{Codec, CodecError, Decode, DecodeLimits, Encode, Ipld};
+
+/// Mode of tree entries that are themselves trees, which sort as if their name ended in `/`.
+const TREE_MODE: &str = "40000";
+
+/// Git objects, in the uncompressed form git hashes: a `<type> <length>\0` header and the object body.
+///
+/// - blob: the content, as bytes
+/// - tree: map of entry names to `{"mode", "hash"}`. Trees with names that are not UTF-8 are rejected, as map keys
+///   are strings.
+/// - commit: `{"tree", "parents", "author", "committer", "other", "message"}`
+/// - tag: `{"object", "type", "tag", "tagger", "other", "message"}`, where `tagger` may be missing
+///
+/// People are `{"name", "email", "date", "timezone"}`. Headers without a field of their own, such as `gpgsig`
+/// and `encoding`, are kept in order in `other` as `[key, value]` pairs, with continuation lines joined by `\n`.
+/// `other` may be left out when encoding. Text is a string, or bytes where it is not UTF-8.
+///
+/// Only objects that re-encode to identical bytes are decoded, so every decoded object keeps its hash.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitRaw {
+    /// Bounds applied while decoding.
+    pub limits: DecodeLimits,
+}
+
+impl GitRaw {
+    /// Prefix of the [Cid]s git objects are addressed by: git-raw and SHA-1.
+    pub const PREFIX: CidPrefix = CidPrefix {
+        version: Version::V1,
+        codec: Self::CODE,
+        mh_type: SHA1,
+        mh_len: None,
+    };
+
+    pub fn with_limits(limits: DecodeLimits) -> Self {
+        Self { limits }
+    }
+}
+
+impl Codec for GitRaw {
+    /// See <https://github.com/multiformats/multicodec/blob/master/table.csv>
+    const CODE: u64 = 0x78;
+
+    type Budget = ();
+}
+
+impl Decode<GitRaw> for Ipld {
+    fn decode<R: Read + Seek>(c: &GitRaw, r: &mut R) -> Result<Self, CodecError> {
+        let max = c.limits.max_alloc as u64;
+        let mut buf = vec![];
+        r.take(max + 1).read_to_end(&mut buf).map_err(|e| CodecError::Io(e))?;
+        if buf.len() as u64 > max {
+            return Err(CodecError::LimitExceeded("allocation"));
+        }
+        decode_object(c, &buf)
+    }
+}
+
+impl Encode<GitRaw> for Ipld {
+    fn encode<W: Write>(&self, _c: &GitRaw, w: &mut W) -> Result<(), CodecError> {
+        w.write_all(&encode_object(self)?).map_err(|e| CodecError::Io(e))
+    }
+}
+
+/// [Cid] of the git object with the SHA-1 digest.
+pub fn git_cid(digest: &[u8; 20]) -> Cid {
+    Cid::new_v1(GitRaw::CODE, Multihash::wrap(SHA1, digest).unwrap())
+}
+
+fn decode_object(c: &GitRaw, data: &[u8]) -> Result<Ipld, CodecError> {
+    let nul = data.iter().position(|&b| b == 0).ok_or(CodecError::MalformedData("missing object header"))?;
+    let header = str::from_utf8(&data[..nul]).map_err(|_| CodecError::MalformedData("invalid object header"))?;
+    let (kind, len) = header.split_once(' ').ok_or(CodecError::MalformedData("invalid object header"))?;
+    let body = &data[nul + 1..];
+    if len != body.len().to_string() {
+        return Err(CodecError::MalformedData("object length does not match its header"));
+    }
+    let ipld = match kind {
+        "blob" => return Ok(Ipld::Bytes(Bytes::copy_from_slice(body))),
+        "tree" => decode_tree(c, body)?,
+        "commit" => decode_commit(c, body)?,
+        "tag" => decode_tag(body)?,
+        _ => return Err(CodecError::MalformedData("unknown object type")),
+    };
+    // Anything not captured by the fields, such as a misordered tree, would change the hash.
+    if encode_object(&ipld)? != data {
+        return Err(CodecError::MalformedData("object does not re-encode identically"));
+    }
+    Ok(ipld)
+}
+
+fn encode_object(ipld: &Ipld) -> Result<Vec<u8>, CodecError> {
+    let (kind, body) = match ipld {
+        Ipld::Bytes(b) => ("blob", b.to_vec()),
+        Ipld::Map(map) if map.get("tree").is_some_and(|t| t.as_link().is_some()) => ("commit", encode_commit(map)?),
+        Ipld::Map(map) if map.get("object").is_some_and(|o| o.as_link().is_some()) => ("tag", encode_tag(map)?),
+        Ipld::Map(map) => ("tree", encode_tree(map)?),
+        _ => return Err(CodecError::MalformedData("expected a git blob, tree, commit or tag")),
+    };
+    let mut out = format!("{kind} {}\0", body.len()).into_bytes();
+    out.extend(body);
+    Ok(out)
+}
+
+fn decode_tree(c: &GitRaw, mut body: &[u8]) -> Result<Ipld, CodecError> {
+    let mut entries = BTreeMap::new();
+    while !body.is_empty() {
+        if entries.len() >= c.limits.max_collection_len {
+            return Err(CodecError::LimitExceeded("collection length"));
+        }
+        let (mode, rest) = split_once(body, b" ").ok_or(CodecError::MalformedData("invalid tree entry"))?;
+        let (name, rest) = split_once(rest, b"\0").ok_or(CodecError::MalformedData("invalid tree entry"))?;
+        let (digest, rest) = rest.split_first_chunk::<20>().ok_or(CodecError::MalformedData("invalid tree entry"))?;
+        let mode = str::from_utf8(mode).map_err(|_| CodecError::MalformedData("invalid tree entry"))?;
+        let name = str::from_utf8(name).map_err(|_| CodecError::MalformedData("tree entry name is not UTF-8"))?;
+        let entry = Ipld::Map(BTreeMap::from([
+            (String::from("mode"), Ipld::String(mode.into())),
+            (String::from("hash"), Ipld::Link(git_cid(digest))),
+        ]));
+        if entries.insert(name.into(), entry).is_some() {
+            return Err(CodecError::MalformedData("duplicate tree entry"));
+        }
+        body = rest;
+    }
+    Ok(Ipld::Map(entries))
+}
+
+fn encode_tree(entries: &BTreeMap<String, Ipld>) -> Result<Vec<u8>, CodecError> {
+    let mut sorted = vec![];
+    for (name, entry) in entries {
+        let mode = field(entry, "mode")?.as_str().ok_or(CodecError::MalformedData("tree entry mode must be a string"))?;
+        let digest = sha1_digest(field(entry, "hash")?)?;
+        let mut key = name.as_bytes().to_vec();
+        if mode == TREE_MODE {
+            key.push(b'/');
+        }
+        sorted.push((key, name, mode, digest));
+    }
+    sorted.sort();
+    let mut out = vec![];
+    for (_, name, mode, digest) in sorted {
+        if name.is_empty() || name.contains(['/', '\0']) {
+            return Err(CodecError::MalformedData("invalid tree entry name"));
+        }
+        out.extend(format!("{mode} {name}\0").as_bytes());
+        out.extend(digest);
+    }
+    Ok(out)
+}
+
+fn decode_commit(c: &GitRaw, body: &[u8]) -> Result<Ipld, CodecError> {
+    let (headers, message) = split_headers(body)?;
+    let mut headers = headers.into_iter().peekable();
+    let mut map = BTreeMap::new();
+    map.insert(String::from("tree"), Ipld::Link(parse_sha1(&expect(&mut headers, "tree")?)?));
+    let mut parents = vec![];
+    while let Some((_, hex)) = headers.next_if(|(key, _)| key == "parent") {
+        if parents.len() >= c.limits.max_collection_len {
+            return Err(CodecError::LimitExceeded("collection length"));
+        }
+        parents.push(Ipld::Link(parse_sha1(&hex)?));
+    }
+    map.insert(String::from("parents"), Ipld::List(parents));
+    map.insert(String::from("author"), parse_person(&expect(&mut headers, "author")?)?);
+    map.insert(String::from("committer"), parse_person(&expect(&mut headers, "committer")?)?);
+    map.insert(String::from("other"), other_headers(headers));
+    map.insert(String::from("message"), text(message));
+    Ok(Ipld::Map(map))
+}
+
+fn encode_commit(map: &BTreeMap<String, Ipld>) -> Result<Vec<u8>, CodecError> {
+    let mut out = vec![];
+    write_header(&mut out, "tree", format_sha1(map_field(map, "tree")?)?.as_bytes());
+    let parents = map_field(map, "parents")?.as_list().ok_or(CodecError::MalformedData("commit parents must be a list"))?;
+    for parent in parents {
+        write_header(&mut out, "parent", format_sha1(parent)?.as_bytes());
+    }
+    write_header(&mut out, "author", &format_person(map_field(map, "author")?)?);
+    write_header(&mut out, "committer", &format_person(map_field(map, "committer")?)?);
+    write_other(&mut out, map)?;
+    out.push(b'\n');
+    out.extend(as_text(map_field(map, "message")?)?);
+    Ok(out)
+}
+
+fn decode_tag(body: &[u8]) -> Result<Ipld, CodecError> {
+    let (headers, message) = split_headers(body)?;
+    let mut headers = headers.into_iter().peekable();
+    let mut map = BTreeMap::new();
+    map.insert(String::from("object"), Ipld::Link(parse_sha1(&expect(&mut headers, "object")?)?));
+    map.insert(String::from("type"), text(&expect(&mut headers, "type")?));
+    map.insert(String::from("tag"), text(&expect(&mut headers, "tag")?));
+    if let Some((_, tagger)) = headers.next_if(|(key, _)| key == "tagger") {
+        map.insert(String::from("tagger"), parse_person(&tagger)?);
+    }
+    map.insert(String::from("other"), other_headers(headers));
+    map.insert(String::from("message"), text(message));
+    Ok(Ipld::Map(map))
+}
+
+fn encode_tag(map: &BTreeMap<String, Ipld>) -> Result<Vec<u8>, CodecError> {
+    let mut out = vec![];
+    write_header(&mut out, "object", format_sha1(map_field(map, "object")?)?.as_bytes());
+    write_header(&mut out, "type", as_text(map_field(map, "type")?)?);
+    write_header(&mut out, "tag", as_text(map_field(map, "tag")?)?);
+    if let Some(tagger) = map.get("tagger") {
+        write_header(&mut out, "tagger", &format_person(tagger)?);
+    }
+    write_other(&mut out, map)?;
+    out.push(b'\n');
+    out.extend(as_text(map_field(map, "message")?)?);
+    Ok(out)
+}
+
+/// Keys and values of the headers of a commit or tag.
+type Headers = Vec<(String, Vec<u8>)>;
+
+/// Split a commit or tag into its headers, with continuation lines joined by `\n`, and its message.
+fn split_headers(mut body: &[u8]) -> Result<(Headers, &[u8]), CodecError> {
+    let mut headers: Headers = vec![];
+    loop {
+        let (line, rest) = split_once(body, b"\n").ok_or(CodecError::MalformedData("unterminated header"))?;
+        body = rest;
+        if line.is_empty() {
+            return Ok((headers, body));
+        }
+        if let Some(continued) = line.strip_prefix(b" ") {
+            let (_, value) = headers.last_mut().ok_or(CodecError::MalformedData("continuation line without a header"))?;
+            value.push(b'\n');
+            value.extend(continued);
+            continue;
+        }
+        let (key, value) = split_once(line, b" ").ok_or(CodecError::MalformedData("header without a value"))?;
+        let key = str::from_utf8(key).map_err(|_| CodecError::MalformedData("header key is not UTF-8"))?;
+        headers.push((key.into(), value.to_vec()));
+    }
+}
+
+fn expect(headers: &mut impl Iterator<Item = (String, Vec<u8>)>, key: &str) -> Result<Vec<u8>, CodecError> {
+    match headers.next() {
+        Some((k, value)) if k == key => Ok(value),
+        _ => Err(CodecError::MalformedData("missing or misordered header")),
+    }
+}
+
+fn other_headers(headers: impl Iterator<Item = (String, Vec<u8>)>) -> Ipld {
+    Ipld::List(headers.map(|(key, value)| Ipld::List(vec![Ipld::String(key), text(&value)])).collect())
+}
+
+fn write_header(out: &mut Vec<u8>, key: &str, value: &[u8]) {
+    out.extend(key.as_bytes());
+    out.push(b' ');
+    for &b in value {
+        out.push(b);
+        if b == b'\n' {
+            out.push(b' ');
+        }
+    }
+    out.push(b'\n');
+}
+
+fn write_other(out: &mut Vec<u8>, map: &BTreeMap<String, Ipld>) -> Result<(), CodecError> {
+    let Some(other) = map.get("other") else {
+        return Ok(());
+    };
+    for header in other.as_list().ok_or(CodecError::MalformedData("other headers must be a list"))? {
+        let [key, value] = header.as_list().unwrap_or_default() else {
+            return Err(CodecError::MalformedData("other headers must be [key, value] pairs"));
+        };
+        let key = key.as_str().filter(|k| !k.is_empty() && !k.contains([' ', '\n']))
+            .ok_or(CodecError::MalformedData("invalid header key"))?;
+        write_header(out, key, as_text(value)?);
+    }
+    Ok(())
+}
+
+/// `name <email> date timezone`
+fn parse_person(value: &[u8]) -> Result<Ipld, CodecError> {
+    let invalid = || CodecError::MalformedData("invalid person");
+    let (name, rest) = split_once(value, b" <").ok_or_else(invalid)?;
+    let (email, rest) = split_once(rest, b"> ").ok_or_else(invalid)?;
+    let (date, timezone) = split_once(rest, b" ").ok_or_else(invalid)?;
+    Ok(Ipld::Map(BTreeMap::from([
+        (String::from("name"), text(name)),
+        (String::from("email"), text(email)),
+        (String::from("date"), text(date)),
+        (String::from("timezone"), text(timezone)),
+    ])))
+}
+
+fn format_person(person: &Ipld) -> Result<Vec<u8>, CodecError> {
+    let mut out = as_text(field(person, "name")?)?.to_vec();
+    out.extend(b" <");
+    out.extend(as_text(field(person, "email")?)?);
+    out.extend(b"> ");
+    out.extend(as_text(field(person, "date")?)?);
+    out.push(b' ');
+    out.extend(as_text(field(person, "timezone")?)?);
+    Ok(out)
+}
+
+fn parse_sha1(hex: &[u8]) -> Result<Cid, CodecError> {
+    let invalid = || CodecError::MalformedData("invalid object hash");
+    if hex.len() != 40 {
+        return Err(invalid());
+    }
+    let mut digest = [0u8; 20];
+    for (byte, pair) in digest.iter_mut().zip(hex.chunks_exact(2)) {
+        // Git writes lowercase hex, and anything else would not re-encode.
+        let pair = str::from_utf8(pair).ok().filter(|p| !p.contains(|c: char| c.is_ascii_uppercase())).ok_or_else(invalid)?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(git_cid(&digest))
+}
+
+fn sha1_digest(link: &Ipld) -> Result<&[u8], CodecError> {
+    let hash = link.as_link().ok_or(CodecError::MalformedData("expected a link"))?.hash();
+    if hash.code() != SHA1 || hash.size() != 20 {
+        return Err(CodecError::MalformedData("git links must be SHA-1"));
+    }
+    Ok(hash.digest())
+}
+
+fn format_sha1(link: &Ipld) -> Result<String, CodecError> {
+    Ok(sha1_digest(link)?.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn text(bytes: &[u8]) -> Ipld {
+    match str::from_utf8(bytes) {
+        Ok(s) => Ipld::String(s.into()),
+        Err(_) => Ipld::Bytes(Bytes::copy_from_slice(bytes)),
+    }
+}
+
+fn as_text(ipld: &Ipld) -> Result<&[u8], CodecError> {
+    match ipld {
+        Ipld::String(s) => Ok(s.as_bytes()),
+        Ipld::Bytes(b) => Ok(b),
+        _ => Err(CodecError::MalformedData("expected a string or bytes")),
+    }
+}
+
+fn field<'a>(ipld: &'a Ipld, key: &str) -> Result<&'a Ipld, CodecError> {
+    map_field(ipld.as_map().ok_or(CodecError::MalformedData("expected a map"))?, key)
+}
+
+fn map_field<'a>(map: &'a BTreeMap<String, Ipld>, key: &str) -> Result<&'a Ipld, CodecError> {
+    map.get(key).ok_or(CodecError::MalformedData("missing field"))
+}
+
+fn split_once<'a>(bytes: &'a [u8], sep: &[u8]) -> Option<(&'a [u8], &'a [u8])> {
+    let i = bytes.windows(sep.len()).position(|w| w == sep)?;
+    Some((&bytes[..i], &bytes[i + sep.len()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Block;
+
+    use super::*;
+
+    const COMMIT: &[u8] = b"commit 229\0tree 259cc2852f75cbd3e55194fe4bf3ccbd1141096d\nparent e8ff503fb906884029707d10bfdd485acded2f1f\n\
+        author Ada Lovelace <ada@example.com> 1700000000 +0100\ncommitter Ada Lovelace <ada@example.com> 1700000000 +0100\n\nSecond\n\nLonger body.\n";
+
+    const TAG: &[u8] = b"tag 134\0object c92357dec47ac63d510f68033a94a5242f4ec5eb\ntype commit\ntag v1\n\
+        tagger Ada Lovelace <ada@example.com> 1700000000 +0100\n\nRelease v1\n";
+
+    fn cid(hex: &str) -> Cid {
+        parse_sha1(hex.as_bytes()).unwrap()
+    }
+
+    fn git_block(data: &[u8]) -> Block {
+        Block::from_data(&GitRaw::PREFIX, Bytes::copy_from_slice(data)).unwrap()
+    }
+
+    #[test]
+    fn test_git_raw_objects() {
+        let c = GitRaw::default();
+        let commit: Ipld = c.decode_from_slice(COMMIT).unwrap();
+        assert_eq!(commit, crate::ipld!({
+            "tree": cid("259cc2852f75cbd3e55194fe4bf3ccbd1141096d"),
+            "parents": [cid("e8ff503fb906884029707d10bfdd485acded2f1f")],
+            "author": { "name": "Ada Lovelace", "email": "ada@example.com", "date": "1700000000", "timezone": "+0100" },
+            "committer": { "name": "Ada Lovelace", "email": "ada@example.com", "date": "1700000000", "timezone": "+0100" },
+            "other": [],
+            "message": "Second\n\nLonger body.\n",
+        }));
+        assert_eq!(c.encode_to_vec(&commit).unwrap(), COMMIT);
+        assert_eq!(*git_block(COMMIT).cid(), cid("c92357dec47ac63d510f68033a94a5242f4ec5eb"));
+
+        let tag: Ipld = c.decode_from_slice(TAG).unwrap();
+        assert_eq!(tag.as_map().unwrap()["object"], Ipld::Link(cid("c92357dec47ac63d510f68033a94a5242f4ec5eb")));
+        assert_eq!(c.encode_to_vec(&tag).unwrap(), TAG);
+        assert_eq!(*git_block(TAG).cid(), cid("5924014eb94350ff365f2dde5be83fc69be81a20"));
+
+        // `src.txt` sorts before the `src` tree, as `src/`
+        let tree = crate::ipld!({
+            "a.txt": { "mode": "100644", "hash": cid("2227cddb7f6318ea735a1c4adb52f5cd36c5783c") },
+            "src": { "mode": "40000", "hash": cid("5d90422423db5ef6b431e8b9e60e0baf04b8742a") },
+            "src.txt": { "mode": "100644", "hash": cid("c1b0730e0133447badcfd47fd144e254807b06e1") },
+        });
+        let data = c.encode_to_vec(&tree).unwrap();
+        assert_eq!(*git_block(&data).cid(), cid("259cc2852f75cbd3e55194fe4bf3ccbd1141096d"));
+        assert_eq!(c.decode_from_slice::<Ipld>(&data).unwrap(), tree);
+
+        let blob = c.encode_to_vec(&Ipld::Bytes(Bytes::from_static(b"hello\n"))).unwrap();
+        assert_eq!(blob, b"blob 6\0hello\n");
+        assert_eq!(*git_block(&blob).cid(), cid("ce013625030ba8dba906f756967f9e9ca394464a"));
+    }
+
+    #[test]
+    fn test_git_raw_roundtrip() {
+        let c = GitRaw::default();
+        let body = b"tree 259cc2852f75cbd3e55194fe4bf3ccbd1141096d\n\
+            author Ada <ada@example.com> 1700000000 +0100\ncommitter Ada <ada@example.com> 1700000000 +0100\n\
+            encoding ISO-8859-1\ngpgsig -----BEGIN PGP SIGNATURE-----\n \n abc\n -----END PGP SIGNATURE-----\n\nCaf\xe9\n";
+        let commit = [format!("commit {}\0", body.len()).as_bytes(), body].concat();
+        let ipld: Ipld = c.decode_from_slice(&commit).unwrap();
+        let map = ipld.as_map().unwrap();
+        assert_eq!(map["other"], crate::ipld!([
+            ["encoding", "ISO-8859-1"],
+            ["gpgsig", "-----BEGIN PGP SIGNATURE-----\n\nabc\n-----END PGP SIGNATURE-----"],
+        ]));
+        assert_eq!(map["message"], Ipld::Bytes(Bytes::from_static(b"Caf\xe9\n")));
+        assert_eq!(c.encode_to_vec(&ipld).unwrap(), commit);
+
+        // misordered tree entries would not hash the same once re-encoded
+        let tree = [&b"tree 66\0"[..], b"100644 b\0", &[1; 20], b"100644 a\0", &[2; 20]].concat();
+        assert!(matches!(c.decode_from_slice::<Ipld>(&tree), Err(CodecError::MalformedData(_))));
+        assert!(c.decode_from_slice::<Ipld>(&COMMIT[..COMMIT.len() - 1]).is_err());
+        assert!(c.decode_from_slice::<Ipld>(b"blob 5\0hello\n").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! Enumeration of the links within an [Ipld] node.
+
+use std::{collections::btree_map, slice};
+
+use cid::Cid;
+
+use super::Ipld;
+
+/// Iterator over every [Cid] linked from an [Ipld] node, in depth-first order.
+/// Map values are visited in key order, as stored.
+pub struct Links<'a> {
+    stack: Vec<Children<'a>>,
+}
+
+enum Children<'a> {
+    One(Option<&'a Ipld>),
+    List(slice::Iter<'a, Ipld>),
+    Map(btree_map::Values<'a, String, Ipld>),
+}
+
+impl<'a> Iterator for Links<'a> {
+    type Item = &'a Cid;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(children) = self.stack.last_mut() {
+            let next = match children {
+                Children::One(node) => node.take(),
+                Children::List(iter) => iter.next(),
+                Children::Map(iter) => iter.next(),
+            };
+            match next {
+                Some(Ipld::Link(cid)) => return Some(cid),
+                Some(Ipld::List(l)) => self.stack.push(Children::List(l.iter())),
+                Some(Ipld::Map(m)) => self.stack.push(Children::Map(m.values())),
+                Some(_) => {},
+                None => {
+                    self.stack.pop();
+                },
+            }
+        }
+        None
+    }
+}
+
+impl Ipld {
+    /// Every [Cid] linked from this node, including the node itself if it is a link.
+    pub fn links(&self) -> Links<'_> {
+        Links { stack: vec![Children::One(Some(self))] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+    use multihash_codetable::{Code, MultihashDigest};
+
+    use crate::ipld;
+
+    #[test]
+    fn test_ipld_links() {
+        let cid = |data: &[u8]| Cid::new_v1(0x55, Code::Sha2_256.digest(data));
+        let (a, b, c) = (cid(b"a"), cid(b"b"), cid(b"c"));
+        let node = ipld!({ "x": [a, { "y": b }, 1], "z": a, "w": null, "v": [[[c]]] });
+        assert_eq!(node.links().collect::<Vec<_>>(), [&c, &a, &b, &a]);
+        assert_eq!(ipld!(a).links().collect::<Vec<_>>(), [&a]);
+        assert_eq!(ipld!([1, "a", {}]).links().count(), 0);
+    }
+}
@@ -1,18 +1,36 @@
-use std::{any::TypeId, collections::BTreeMap, fmt, io::{self, Cursor, Read, Seek, Write}};
+use std::{collections::BTreeMap, convert::Infallible, fmt, io::{self, Cursor, Read, Seek, Write}};
 
 use bytes::Bytes;
 use cid::Cid;
-use dag_cbor::DagCbor;
-use dag_json::DagJson;
-use dag_pb::DagPb;
+pub use dag_cbor::{DagCbor, DagCborEncoder, DagCborReader, DecodeMode, Deviations, IpldRef, ListIter, ListRef, MapIter, MapRef};
+#[doc(hidden)]
+pub use dag_cbor::derive as __dag_cbor;
+pub use dag_json::{DagJson, DagJsonEncoder};
+pub use dag_pb::{DagPb, DagPbEncoder, PbLink, PbNode};
+pub use git_raw::{git_cid, GitRaw};
+pub use hearsay_ipfs_derive::DagCbor;
 use thiserror::Error;
 
-use crate::Block;
+use crate::{hasher::HasherRegistry, Block, BlockError, CidPrefix};
 
+mod cmp;
+mod convert;
 mod dag_cbor;
 mod dag_json;
 mod dag_pb;
+mod git_raw;
+mod links;
+pub mod patch;
+pub mod path;
 mod raw;
+pub(crate) mod registry;
+pub mod schema;
+pub mod selector;
+pub mod serde;
+pub mod visit;
+pub use links::Links;
+pub use raw::RawData;
+pub use registry::{BlockCodec, CodecRegistry};
 
 pub trait Encode<C: Codec + ?Sized> {
     fn encode<W: Write>(&self, c: &C, w: &mut W) -> Result<(), CodecError>;
@@ -20,12 +38,21 @@ pub trait Encode<C: Codec + ?Sized> {
 
 pub trait Decode<C: Codec>: Sized {
     fn decode<R: Read + Seek>(c: &C, r: &mut R) -> Result<Self, CodecError>;
+
+    /// Decode as part of a larger data item, counting against its [Codec::Budget].
+    /// Defaults to [Decode::decode], which starts a fresh budget.
+    fn decode_with<R: Read + Seek>(c: &C, r: &mut R, _budget: &mut C::Budget) -> Result<Self, CodecError> {
+        Self::decode(c, r)
+    }
 }
 
 pub trait Codec: Sized {
     /// See <https://github.com/multiformats/multicodec/blob/master/table.csv>.
     const CODE: u64;
 
+    /// Resources used so far by a decode, shared by every value read from one data item.
+    type Budget;
+
     fn encode<T: Encode<Self>, W: Write>(&self, data: &T, w: &mut W) -> Result<(), CodecError> {
         data.encode(self, w)
     }
@@ -56,6 +83,8 @@ pub enum CodecKind {
     DagCbor = DagCbor::CODE,
     /// DAG-JSON codec 0x0129
     DagJson = DagJson::CODE,
+    /// Git object codec 0x78
+    GitRaw = GitRaw::CODE,
 }
 
 impl TryFrom<u64> for CodecKind {
@@ -66,6 +95,7 @@ impl TryFrom<u64> for CodecKind {
             DagPb::CODE => CodecKind::DagPb,
             DagCbor::CODE => CodecKind::DagCbor,
             DagJson::CODE => CodecKind::DagJson,
+            GitRaw::CODE => CodecKind::GitRaw,
             _ => return Err(CodecError::UnsupportedCodec(value)),
         })
     }
@@ -77,26 +107,63 @@ pub enum CodecError {
     Io(#[from] io::Error),
     #[error("number is not properly contained")]
     NumberOutOfBounds,
-    #[error("codec id {:x?} is not supported", 0)]
+    #[error("codec id {0:#x} is not supported")]
     UnsupportedCodec(u64),
-    #[error("malformed data: {}", 0)]
+    #[error("malformed data: {0}")]
     MalformedData(&'static str),
+    #[error("decode limit exceeded: {0}")]
+    LimitExceeded(&'static str),
+}
+
+/// Bounds on the resources a decoder may spend on untrusted input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting of lists and maps.
+    pub max_depth: usize,
+    /// Maximum number of entries in a single list or map.
+    pub max_collection_len: usize,
+    /// Maximum number of bytes allocated over a whole decode.
+    pub max_alloc: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_collection_len: 1 << 21,
+            max_alloc: 64 << 20,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum IpldError {
+    #[error("expected {expected:?}, found {found:?}")]
     BadConversion {
         expected: IpldKind,
         found: IpldKind,
     },
+    /// The value is of the right kind but does not fit the type, such as an out of range integer.
+    #[error("{from:?} value does not fit in {into}")]
     InvalidKind {
         from: IpldKind,
-        into: TypeId,
+        into: &'static str,
     },
+    #[error("expected a list of length {expected}, found {found}")]
+    BadLength {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl From<Infallible> for IpldError {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
 }
 
 /// IPLD data-model, see [reference](https://ipld.io/docs/data-model/kinds/).
-#[derive(PartialEq)]
+#[derive(Clone)]
 pub enum Ipld {
     Null,
     Bool(bool),
@@ -160,25 +227,25 @@ impl Ipld {
 
 }
 
+impl Ipld {
+    /// Decode a [Block] with the built-in codec of its [Cid], within the [DecodeLimits].
+    /// See [CodecRegistry] for other codecs.
+    pub fn decode_block(block: &Block, limits: DecodeLimits) -> Result<Self, CodecError> {
+        CodecRegistry::builtin().decode(block, limits)
+    }
+}
+
+impl Ipld {
+    /// Encode into a [Block] with the built-in codec and hash of the [CidPrefix].
+    pub fn encode_block_with(&self, hashers: &HasherRegistry, prefix: &CidPrefix) -> Result<Block, BlockError> {
+        CodecRegistry::builtin().encode(hashers, prefix, self)
+    }
+}
+
 impl TryFrom<Block> for Ipld {
     type Error = CodecError;
     fn try_from(value: Block) -> Result<Self, Self::Error> {
-        let kind = CodecKind::try_from(value.cid().codec())?;
-        Ok(match kind {
-            CodecKind::Raw => Ipld::Bytes(value.inner().clone()), // PERF: remove clone?
-            CodecKind::DagPb => {
-                let pb = DagPb;
-                pb.decode_from_slice(value.data())?
-            },
-            CodecKind::DagCbor => {
-                let cbor = DagCbor;
-                cbor.decode_from_slice(value.data())?
-            },
-            CodecKind::DagJson => {
-                let json = DagJson;
-                json.decode_from_slice(value.data())?
-            },
-        })
+        Ipld::decode_block(&value, DecodeLimits::default())
     }
 }
 
@@ -197,6 +264,12 @@ impl From<String> for Ipld {
     }
 }
 
+impl From<&str> for Ipld {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
 impl From<bool> for Ipld {
     fn from(value: bool) -> Self {
         Self::Bool(value)
@@ -272,6 +345,18 @@ impl From<Bytes> for Ipld {
     }
 }
 
+impl From<&[u8]> for Ipld {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(Bytes::copy_from_slice(value))
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Ipld {
+    fn from(value: &[u8; N]) -> Self {
+        Self::Bytes(Bytes::copy_from_slice(value))
+    }
+}
+
 impl From<Cid> for Ipld {
     fn from(value: Cid) -> Self {
         Self::Link(value)
@@ -291,7 +376,7 @@ impl From<BTreeMap<String, Ipld>> for Ipld {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum IpldKind {
     Null,
     Bool,
@@ -304,7 +389,149 @@ pub enum IpldKind {
     Link,
 }
 
+/// Build [Ipld] with JSON-like syntax.
+/// Map keys are string literals or expressions convertible into [String], parenthesized if they are not a single token.
+/// Any other value is converted with [Ipld::from], so [Cid]s become links and byte string literals become bytes.
+///
+/// ```
+/// # use hearsay_ipfs::{ipld, ipld::Ipld};
+/// let name = "banana";
+/// let node = ipld!({
+///     "name": name,
+///     "sizes": [1, 2, null],
+///     "data": b"\x00\x01",
+///     (format!("{name}s")): { "ripe": true },
+/// });
+/// assert_eq!(node.get_path("bananas/ripe").unwrap(), &Ipld::Bool(true));
+/// ```
+///
+/// Malformed input is a compile error saying what is missing:
+///
+/// ```compile_fail
+/// # use hearsay_ipfs::ipld;
+/// let node = ipld!({ "name": "banana", "ripe" });
+/// ```
+#[macro_export]
 macro_rules! ipld {
+    // Done with a list.
+    (@list [$($elems:expr,)*]) => {
+        ::std::vec![$($elems,)*]
+    };
+
+    (@list [$($elems:expr),*]) => {
+        ::std::vec![$($elems),*]
+    };
+
+    // Next element is null, a bool, a list or a map.
+    (@list [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!(null)] $($rest)*)
+    };
+
+    (@list [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!(true)] $($rest)*)
+    };
+
+    (@list [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!(false)] $($rest)*)
+    };
+
+    (@list [$($elems:expr,)*] [$($list:tt)*] $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!([$($list)*])] $($rest)*)
+    };
+
+    (@list [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!({$($map)*})] $($rest)*)
+    };
+
+    // Next element is an expression, followed by a comma or the end.
+    (@list [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!($next),] $($rest)*)
+    };
+
+    (@list [$($elems:expr,)*] $last:expr) => {
+        $crate::ipld!(@list [$($elems,)* $crate::ipld!($last)])
+    };
+
+    // Comma after the most recent element.
+    (@list [$($elems:expr),*] , $($rest:tt)*) => {
+        $crate::ipld!(@list [$($elems,)*] $($rest)*)
+    };
+
+    (@list [$($elems:expr),*] $unexpected:tt $($rest:tt)*) => {
+        ::std::compile_error!(::std::concat!("ipld!: expected `,` between list elements in `", ::std::stringify!($unexpected $($rest)*), "`"))
+    };
+
+    // Done with a map.
+    (@map $map:ident () () ()) => {};
+
+    // Insert the current entry, followed by a comma or the end.
+    (@map $map:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        let _ = $map.insert(($($key)+).into(), $value);
+        $crate::ipld!(@map $map () ($($rest)*) ($($rest)*));
+    };
+
+    (@map $map:ident [$($key:tt)+] ($value:expr)) => {
+        let _ = $map.insert(($($key)+).into(), $value);
+    };
+
+    // Next value is null, a bool, a list or a map.
+    (@map $map:ident ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!(null)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!(true)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!(false)) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: [$($list:tt)*] $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!([$($list)*])) $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: {$($inner:tt)*} $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!({$($inner)*})) $($rest)*);
+    };
+
+    // Next value is an expression, followed by a comma or the end.
+    (@map $map:ident ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!($value)) , $($rest)*);
+    };
+
+    (@map $map:ident ($($key:tt)+) (: $value:expr) $copy:tt) => {
+        $crate::ipld!(@map $map [$($key)+] ($crate::ipld!($value)));
+    };
+
+    // Missing value, or missing colon and value, for the last entry.
+    (@map $map:ident ($($key:tt)+) (:) $copy:tt) => {
+        ::std::compile_error!(::std::concat!("ipld!: missing value for map key `", ::std::stringify!($($key)+), "`"));
+    };
+
+    (@map $map:ident ($($key:tt)+) () $copy:tt) => {
+        ::std::compile_error!(::std::concat!("ipld!: expected `:` and a value after map key `", ::std::stringify!($($key)+), "`"));
+    };
+
+    // Misplaced colon, or a comma inside a key.
+    (@map $map:ident () (: $($rest:tt)*) $copy:tt) => {
+        ::std::compile_error!("ipld!: missing map key before `:`");
+    };
+
+    (@map $map:ident ($($key:tt)*) (, $($rest:tt)*) $copy:tt) => {
+        ::std::compile_error!(::std::concat!("ipld!: expected `:` after map key `", ::std::stringify!($($key)*), "`, found `,`"));
+    };
+
+    // Parenthesized key.
+    (@map $map:ident () (($key:expr) : $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map ($key) (: $($rest)*) (: $($rest)*));
+    };
+
+    // Munch a token into the current key.
+    (@map $map:ident ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
+        $crate::ipld!(@map $map ($($key)* $tt) ($($rest)*) ($($rest)*));
+    };
+
     (null) => {
         $crate::ipld::Ipld::Null
     };
@@ -317,23 +544,28 @@ macro_rules! ipld {
         $crate::ipld::Ipld::Bool(false)
     };
 
-    // empty map
+    ([]) => {
+        $crate::ipld::Ipld::List(::std::vec![])
+    };
+
+    ([ $($tt:tt)+ ]) => {
+        $crate::ipld::Ipld::List($crate::ipld!(@list [] $($tt)+))
+    };
+
     ({}) => {
-        $crate::ipld::Ipld::Map(BTreeMap::new())
+        $crate::ipld::Ipld::Map(::std::collections::BTreeMap::new())
     };
 
-    // filled map
     ({ $($tt:tt)+ }) => {
         $crate::ipld::Ipld::Map({
-            let mut object = BTreeMap::new();
-            ipld!(@object object () ($($tt)+) ($($tt)+));
-            object
+            let mut map = ::std::collections::BTreeMap::new();
+            $crate::ipld!(@map map () ($($tt)+) ($($tt)+));
+            map
         })
     };
 
-    // 
     ($other:expr) => {
-        { $crate::ipld::Ipld::from($other) }
+        $crate::ipld::Ipld::from($other)
     };
 }
 
@@ -342,16 +574,54 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_ipld_macro() {
         assert_eq!(ipld!(null), Ipld::Null);
         assert_eq!(ipld!(true), Ipld::Bool(true));
         assert_eq!(ipld!(i64::MIN), Ipld::Integer(i64::MIN.into()));
         assert_eq!(ipld!(3.14f64), Ipld::Float(3.14f64));
         assert_eq!(ipld!(String::from("banana")), Ipld::String(String::from("banana")));
+        assert_eq!(ipld!("banana"), Ipld::String(String::from("banana")));
         assert_eq!(ipld!(Bytes::from_static(b"banana")), Ipld::Bytes(Bytes::from_static(b"banana")));
-        // TODO: add ipld list, map, link tests
+        assert_eq!(ipld!(b"banana"), Ipld::Bytes(Bytes::from_static(b"banana")));
+        assert_eq!(ipld!(Cid::default()), Ipld::Link(Cid::default()));
+        assert_eq!(ipld!([]), Ipld::List(vec![]));
+        assert_eq!(ipld!({}), Ipld::Map(BTreeMap::new()));
     }
-    
+
+    #[test]
+    fn test_ipld_macro_nested() {
+        let cid = Cid::default();
+        let key = String::from("link");
+        let list = ipld!([1, -2, null, [true, false], { "a": "b" }, cid, 1 + 2,]);
+        assert_eq!(list, Ipld::List(vec![
+            Ipld::Integer(1),
+            Ipld::Integer(-2),
+            Ipld::Null,
+            Ipld::List(vec![Ipld::Bool(true), Ipld::Bool(false)]),
+            Ipld::Map(BTreeMap::from([(String::from("a"), Ipld::String(String::from("b")))])),
+            Ipld::Link(cid),
+            Ipld::Integer(3),
+        ]));
+
+        let map = ipld!({
+            "null": null,
+            "list": [1, [2]],
+            "map": { "inner": {} },
+            key: cid,
+            (format!("{}s", "byte")): b"\x00",
+            "sum": 1 + 2
+        });
+        assert_eq!(map, Ipld::Map(BTreeMap::from([
+            (String::from("null"), Ipld::Null),
+            (String::from("list"), Ipld::List(vec![Ipld::Integer(1), Ipld::List(vec![Ipld::Integer(2)])])),
+            (String::from("map"), Ipld::Map(BTreeMap::from([(String::from("inner"), Ipld::Map(BTreeMap::new()))]))),
+            (String::from("link"), Ipld::Link(cid)),
+            (String::from("bytes"), Ipld::Bytes(Bytes::from_static(b"\x00"))),
+            (String::from("sum"), Ipld::Integer(3)),
+        ])));
+    }
+
     #[test]
     fn test_ipld_conversions() {
         assert_eq!(Ipld::from(true), Ipld::Bool(true));
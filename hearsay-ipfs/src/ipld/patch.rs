@@ -0,0 +1,286 @@
+//! [IPLD Patch](https://ipld.io/specs/patch/), edits in the style of JSON Patch addressed by IPLD paths.
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::BlockError;
+
+use super::{path::{segments, PathError}, Ipld};
+
+#[derive(Debug, Error)]
+pub enum PatchError {
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error(transparent)]
+    Block(#[from] BlockError),
+    #[error("test failed at {0:?}")]
+    TestFailed(String),
+    #[error("cannot move {from:?} into its own child {path:?}")]
+    MoveIntoChild {
+        from: String,
+        path: String,
+    },
+    #[error("cannot remove the root")]
+    RemoveRoot,
+    #[error("malformed patch: {0}")]
+    Malformed(&'static str),
+}
+
+/// A single patch operation. For `add`, the last segment of a list path may be `-` to append.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    Add { path: String, value: Ipld },
+    Remove { path: String },
+    Replace { path: String, value: Ipld },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Ipld },
+}
+
+impl Operation {
+    /// Path the operation changes, or tests.
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Add { path, .. } | Self::Remove { path } | Self::Replace { path, .. } => path,
+            Self::Move { path, .. } | Self::Copy { path, .. } | Self::Test { path, .. } => path,
+        }
+    }
+
+    /// Path a `move` or `copy` reads from.
+    pub fn from(&self) -> Option<&str> {
+        match self {
+            Self::Move { from, .. } | Self::Copy { from, .. } => Some(from),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a patch, a list of operations such as `{ "op": "add", "path": "/a/b", "value": 1 }`.
+pub fn parse(patch: &Ipld) -> Result<Vec<Operation>, PatchError> {
+    let Ipld::List(ops) = patch else {
+        return Err(PatchError::Malformed("patch is not a list"));
+    };
+    ops.iter().map(Operation::try_from).collect()
+}
+
+impl TryFrom<&Ipld> for Operation {
+    type Error = PatchError;
+    fn try_from(value: &Ipld) -> Result<Self, Self::Error> {
+        let string = |key| match &value[key] {
+            Ipld::String(s) => Ok(s.clone()),
+            _ => Err(PatchError::Malformed("missing string field")),
+        };
+        let value = || match value.as_map().and_then(|m| m.get("value")) {
+            Some(v) => Ok(v.clone()),
+            None => Err(PatchError::Malformed("missing value")),
+        };
+        let path = string("path")?;
+        Ok(match string("op")?.as_str() {
+            "add" => Self::Add { path, value: value()? },
+            "remove" => Self::Remove { path },
+            "replace" => Self::Replace { path, value: value()? },
+            "move" => Self::Move { from: string("from")?, path },
+            "copy" => Self::Copy { from: string("from")?, path },
+            "test" => Self::Test { path, value: value()? },
+            _ => return Err(PatchError::Malformed("unknown op")),
+        })
+    }
+}
+
+impl From<&Operation> for Ipld {
+    fn from(value: &Operation) -> Self {
+        let (op, path, from, value) = match value {
+            Operation::Add { path, value } => ("add", path, None, Some(value)),
+            Operation::Remove { path } => ("remove", path, None, None),
+            Operation::Replace { path, value } => ("replace", path, None, Some(value)),
+            Operation::Move { from, path } => ("move", path, Some(from), None),
+            Operation::Copy { from, path } => ("copy", path, Some(from), None),
+            Operation::Test { path, value } => ("test", path, None, Some(value)),
+        };
+        let mut map = BTreeMap::from([
+            (String::from("op"), Ipld::from(op)),
+            (String::from("path"), Ipld::from(path.as_str())),
+        ]);
+        if let Some(from) = from {
+            map.insert(String::from("from"), Ipld::from(from.as_str()));
+        }
+        if let Some(value) = value {
+            map.insert(String::from("value"), value.clone());
+        }
+        Ipld::Map(map)
+    }
+}
+
+/// Apply the operations in order. On error the node is left unchanged.
+pub fn apply(node: &mut Ipld, ops: &[Operation]) -> Result<(), PatchError> {
+    let mut patched = node.clone();
+    for op in ops {
+        apply_op(&mut patched, op)?;
+    }
+    *node = patched;
+    Ok(())
+}
+
+/// What a patch is applied to, a single node or the blocks of a DAG.
+pub(crate) trait Target {
+    fn read(&mut self, path: &str) -> Result<Ipld, PatchError>;
+    fn add(&mut self, path: &str, value: Ipld) -> Result<(), PatchError>;
+    fn remove(&mut self, path: &str) -> Result<Ipld, PatchError>;
+    fn replace(&mut self, path: &str, value: Ipld) -> Result<Ipld, PatchError>;
+}
+
+pub(crate) fn apply_op(target: &mut impl Target, op: &Operation) -> Result<(), PatchError> {
+    match op {
+        Operation::Add { path, value } => target.add(path, value.clone()),
+        Operation::Remove { path } => target.remove(path).map(drop),
+        Operation::Replace { path, value } => target.replace(path, value.clone()).map(drop),
+        Operation::Move { from, path } => {
+            let (mut f, mut p) = (segments(from), segments(path));
+            if f.by_ref().zip(p.by_ref()).all(|(f, p)| f == p) && f.next().is_none() && p.next().is_some() {
+                return Err(PatchError::MoveIntoChild { from: from.clone(), path: path.clone() });
+            }
+            let value = target.remove(from)?;
+            target.add(path, value)
+        },
+        Operation::Copy { from, path } => {
+            let value = target.read(from)?;
+            target.add(path, value)
+        },
+        Operation::Test { path, value } => match target.read(path)? == *value {
+            true => Ok(()),
+            false => Err(PatchError::TestFailed(path.clone())),
+        },
+    }
+}
+
+/// Segments leading to the container of the path, and the last segment. [None] for the root.
+pub(crate) fn split(path: &str) -> Option<(Vec<&str>, &str)> {
+    let mut parent = segments(path).collect::<Vec<_>>();
+    let key = parent.pop()?;
+    Some((parent, key))
+}
+
+/// Insert into a map, replacing any existing entry, or into a list, shifting later items.
+pub(crate) fn add(container: &mut Ipld, key: &str, value: Ipld) -> Result<(), PathError> {
+    match container {
+        Ipld::Map(m) => {
+            m.insert(key.to_owned(), value);
+        },
+        Ipld::List(l) if key == "-" => l.push(value),
+        Ipld::List(l) => {
+            let index = list_index(key)?;
+            if index > l.len() {
+                return Err(PathError::NotFound { segment: key.to_owned(), kind: container.kind() });
+            }
+            l.insert(index, value);
+        },
+        _ => return Err(PathError::NotTraversable(container.kind())),
+    }
+    Ok(())
+}
+
+pub(crate) fn remove(container: &mut Ipld, key: &str) -> Result<Ipld, PathError> {
+    container.get_segment(key)?;
+    Ok(match container {
+        Ipld::Map(m) => m.remove(key).expect("checked by get_segment"),
+        Ipld::List(l) => l.remove(list_index(key)?),
+        _ => unreachable!("checked by get_segment"),
+    })
+}
+
+pub(crate) fn replace(container: &mut Ipld, key: &str, value: Ipld) -> Result<Ipld, PathError> {
+    Ok(std::mem::replace(container.get_segment_mut(key)?, value))
+}
+
+fn list_index(segment: &str) -> Result<usize, PathError> {
+    segment.parse().map_err(|_| PathError::InvalidIndex { segment: segment.to_owned() })
+}
+
+/// Container at the end of the parent segments, which may not cross links.
+fn container<'a>(node: &'a mut Ipld, parent: &[&str]) -> Result<&'a mut Ipld, PathError> {
+    match node.get_path_mut(&parent.join("/"))? {
+        Ipld::Link(cid) => Err(PathError::Link(*cid)),
+        container => Ok(container),
+    }
+}
+
+/// A single node, where paths may not cross links.
+impl Target for Ipld {
+    fn read(&mut self, path: &str) -> Result<Ipld, PatchError> {
+        Ok(self.get_path(path)?.clone())
+    }
+
+    fn add(&mut self, path: &str, value: Ipld) -> Result<(), PatchError> {
+        match split(path) {
+            Some((parent, key)) => Ok(add(container(self, &parent)?, key, value)?),
+            None => {
+                *self = value;
+                Ok(())
+            },
+        }
+    }
+
+    fn remove(&mut self, path: &str) -> Result<Ipld, PatchError> {
+        let (parent, key) = split(path).ok_or(PatchError::RemoveRoot)?;
+        Ok(remove(container(self, &parent)?, key)?)
+    }
+
+    fn replace(&mut self, path: &str, value: Ipld) -> Result<Ipld, PatchError> {
+        match split(path) {
+            Some((parent, key)) => Ok(replace(container(self, &parent)?, key, value)?),
+            None => Ok(std::mem::replace(self, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+
+    use crate::ipld;
+
+    use super::*;
+
+    #[test]
+    fn test_patch_apply() {
+        let patch = ipld!([
+            { "op": "test", "path": "/name", "value": "banana" },
+            { "op": "add", "path": "/tags/-", "value": "yellow" },
+            { "op": "add", "path": "/tags/0", "value": "fruit" },
+            { "op": "replace", "path": "/size/length", "value": 20 },
+            { "op": "copy", "from": "/name", "path": "/alias" },
+            { "op": "move", "from": "/size", "path": "/dimensions" },
+            { "op": "remove", "path": "/tags/1" },
+        ]);
+        let ops = parse(&patch).unwrap();
+        assert_eq!(Ipld::List(ops.iter().map(Ipld::from).collect()), patch);
+
+        let mut node = ipld!({ "name": "banana", "tags": ["ripe"], "size": { "length": 18 } });
+        apply(&mut node, &ops).unwrap();
+        assert_eq!(node, ipld!({ "name": "banana", "alias": "banana", "tags": ["fruit", "yellow"], "dimensions": { "length": 20 } }));
+
+        let original = node.clone();
+        let failing = [
+            Operation::Remove { path: String::from("/name") },
+            Operation::Test { path: String::from("/alias"), value: ipld!("apple") },
+        ];
+        assert!(matches!(apply(&mut node, &failing), Err(PatchError::TestFailed(_))));
+        assert_eq!(node, original);
+    }
+
+    #[test]
+    fn test_patch_errors() {
+        let mut node = ipld!({ "a": { "b": [1] }, "link": Cid::default() });
+        let op = |op: Operation| apply(&mut node.clone(), &[op]);
+        let path = |p: &str| String::from(p);
+        assert!(matches!(op(Operation::Remove { path: path("") }), Err(PatchError::RemoveRoot)));
+        assert!(matches!(op(Operation::Move { from: path("a"), path: path("a/b/c") }), Err(PatchError::MoveIntoChild { .. })));
+        assert!(matches!(op(Operation::Add { path: path("a/b/2"), value: ipld!(2) }), Err(PatchError::Path(PathError::NotFound { .. }))));
+        assert!(matches!(op(Operation::Replace { path: path("a/c"), value: ipld!(2) }), Err(PatchError::Path(_))));
+        assert!(matches!(op(Operation::Add { path: path("link/x"), value: ipld!(2) }), Err(PatchError::Path(PathError::Link(_)))));
+        assert!(matches!(parse(&ipld!([{ "op": "add", "path": "a" }])), Err(PatchError::Malformed(_))));
+        apply(&mut node, &[Operation::Replace { path: path(""), value: ipld!(null) }]).unwrap();
+        assert_eq!(node, Ipld::Null);
+    }
+}
@@ -0,0 +1,117 @@
+//! Path traversal within a single [Ipld] node.
+//! See <https://ipld.io/docs/data-model/pathing/>.
+
+use cid::Cid;
+use thiserror::Error;
+
+use super::{Ipld, IpldKind};
+
+#[derive(Debug, Error)]
+pub enum PathError {
+    #[error("no {segment:?} in {kind:?}")]
+    NotFound {
+        segment: String,
+        kind: IpldKind,
+    },
+    #[error("{segment:?} is not a list index")]
+    InvalidIndex {
+        segment: String,
+    },
+    #[error("cannot traverse into {0:?}")]
+    NotTraversable(IpldKind),
+    #[error("path crosses link {0}")]
+    Link(Cid),
+}
+
+/// Split a path into its segments, ignoring leading, trailing and repeated `/`.
+pub fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+impl Ipld {
+    /// Child of a map by key, or of a list by index.
+    pub fn get_segment(&self, segment: &str) -> Result<&Ipld, PathError> {
+        let not_found = || PathError::NotFound {
+            segment: segment.to_owned(),
+            kind: self.kind(),
+        };
+        match self {
+            Self::Map(m) => m.get(segment).ok_or_else(not_found),
+            Self::List(l) => {
+                let index = segment.parse::<usize>().map_err(|_| PathError::InvalidIndex {
+                    segment: segment.to_owned(),
+                })?;
+                l.get(index).ok_or_else(not_found)
+            },
+            _ => Err(PathError::NotTraversable(self.kind())),
+        }
+    }
+
+    /// Mutable child of a map by key, or of a list by index.
+    pub fn get_segment_mut(&mut self, segment: &str) -> Result<&mut Ipld, PathError> {
+        let kind = self.kind();
+        let not_found = || PathError::NotFound {
+            segment: segment.to_owned(),
+            kind,
+        };
+        match self {
+            Self::Map(m) => m.get_mut(segment).ok_or_else(not_found),
+            Self::List(l) => {
+                let index = segment.parse::<usize>().map_err(|_| PathError::InvalidIndex {
+                    segment: segment.to_owned(),
+                })?;
+                l.get_mut(index).ok_or_else(not_found)
+            },
+            _ => Err(PathError::NotTraversable(kind)),
+        }
+    }
+
+    /// Follow a path such as `foo/0/bar` within this node.
+    /// Fails if the path crosses a [Ipld::Link], see [crate::repo::Repository::resolve] to follow them.
+    pub fn get_path(&self, path: &str) -> Result<&Ipld, PathError> {
+        let mut node = self;
+        for segment in segments(path) {
+            if let Self::Link(cid) = node {
+                return Err(PathError::Link(*cid));
+            }
+            node = node.get_segment(segment)?;
+        }
+        Ok(node)
+    }
+
+    /// Mutable node at the end of a path within this node, see [Ipld::get_path].
+    pub fn get_path_mut(&mut self, path: &str) -> Result<&mut Ipld, PathError> {
+        let mut node = self;
+        for segment in segments(path) {
+            if let Self::Link(cid) = node {
+                return Err(PathError::Link(*cid));
+            }
+            node = node.get_segment_mut(segment)?;
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_ipld_get_path() {
+        let cid = Cid::default();
+        let bar = Ipld::Map(BTreeMap::from([(String::from("bar"), Ipld::Integer(7))]));
+        let list = Ipld::List(vec![bar, Ipld::Link(cid)]);
+        let root = Ipld::Map(BTreeMap::from([(String::from("foo"), list)]));
+
+        assert_eq!(root.get_path("").unwrap(), &root);
+        assert_eq!(root.get_path("foo/0/bar").unwrap(), &Ipld::Integer(7));
+        assert_eq!(root.get_path("/foo/0/bar/").unwrap(), &Ipld::Integer(7));
+        assert_eq!(root.get_path("foo/1").unwrap(), &Ipld::Link(cid));
+        assert!(matches!(root.get_path("foo/1/bar"), Err(PathError::Link(_))));
+        assert!(matches!(root.get_path("foo/2"), Err(PathError::NotFound { .. })));
+        assert!(matches!(root.get_path("foo/bar"), Err(PathError::InvalidIndex { .. })));
+        assert!(matches!(root.get_path("foo/0/bar/baz"), Err(PathError::NotTraversable(IpldKind::Integer))));
+    }
+}
@@ -10,6 +10,8 @@ pub struct RawData;
 impl Codec for RawData {
     /// See <https://github.com/multiformats/multicodec/blob/master/table.csv>
     const CODE: u64 = 0x55;
+
+    type Budget = ();
 }
 
 impl Encode<RawData> for Ipld {
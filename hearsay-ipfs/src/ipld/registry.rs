@@ -0,0 +1,206 @@
+//! Multicodec code to codec lookup, used to decode, encode and follow the links of [Block]s.
+//! See <https://github.com/multiformats/multicodec>.
+
+use std::{collections::HashMap, fmt, sync::{Arc, LazyLock}};
+
+use bytes::Bytes;
+use cid::Cid;
+
+use crate::{hasher::HasherRegistry, Block, BlockError, CidPrefix};
+
+use super::{Codec, CodecError, DagCbor, DagJson, DagPb, DecodeLimits, GitRaw, Ipld, PbNode, RawData};
+
+/// A codec for the data of blocks with one multicodec code.
+pub trait BlockCodec: Send + Sync {
+    fn decode(&self, data: &Bytes, limits: DecodeLimits) -> Result<Ipld, CodecError>;
+
+    fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError>;
+
+    /// Links in the data, in order. Defaults to the links of the decoded [Ipld].
+    fn links(&self, data: &Bytes, limits: DecodeLimits) -> Result<Vec<Cid>, CodecError> {
+        Ok(self.decode(data, limits)?.links().copied().collect())
+    }
+}
+
+impl BlockCodec for RawData {
+    fn decode(&self, data: &Bytes, _limits: DecodeLimits) -> Result<Ipld, CodecError> {
+        Ok(Ipld::Bytes(data.clone()))
+    }
+
+    fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError> {
+        match ipld {
+            Ipld::Bytes(b) => Ok(b.clone()),
+            _ => Err(CodecError::MalformedData("raw blocks only hold bytes")),
+        }
+    }
+
+    fn links(&self, _data: &Bytes, _limits: DecodeLimits) -> Result<Vec<Cid>, CodecError> {
+        Ok(vec![])
+    }
+}
+
+impl BlockCodec for DagPb {
+    fn decode(&self, data: &Bytes, limits: DecodeLimits) -> Result<Ipld, CodecError> {
+        DagPb::with_limits(limits).decode_from_slice(data)
+    }
+
+    fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError> {
+        Ok(self.encode_to_vec(ipld)?.into())
+    }
+
+    /// Only the hashes of the links are needed, without building the [Ipld] form.
+    fn links(&self, data: &Bytes, limits: DecodeLimits) -> Result<Vec<Cid>, CodecError> {
+        let node: PbNode = DagPb::with_limits(limits).decode_from_slice(data)?;
+        Ok(node.links.into_iter().map(|link| link.hash).collect())
+    }
+}
+
+impl BlockCodec for DagCbor {
+    /// Decodes in the [DecodeMode](super::DecodeMode) of the registered codec.
+    fn decode(&self, data: &Bytes, limits: DecodeLimits) -> Result<Ipld, CodecError> {
+        DagCbor { limits, ..*self }.decode_from_slice(data)
+    }
+
+    fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError> {
+        Ok(self.encode_to_vec(ipld)?.into())
+    }
+}
+
+impl BlockCodec for GitRaw {
+    fn decode(&self, data: &Bytes, limits: DecodeLimits) -> Result<Ipld, CodecError> {
+        GitRaw::with_limits(limits).decode_from_slice(data)
+    }
+
+    fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError> {
+        Ok(self.encode_to_vec(ipld)?.into())
+    }
+}
+
+impl BlockCodec for DagJson {
+    fn decode(&self, data: &Bytes, limits: DecodeLimits) -> Result<Ipld, CodecError> {
+        DagJson::with_limits(limits).decode_from_slice(data)
+    }
+
+    fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError> {
+        Ok(self.encode_to_vec(ipld)?.into())
+    }
+}
+
+static BUILTIN: LazyLock<CodecRegistry> = LazyLock::new(CodecRegistry::default);
+
+/// Multicodec code to [BlockCodec] lookup.
+/// Starts with raw, DAG-PB, DAG-CBOR, DAG-JSON and git-raw registered.
+#[derive(Clone)]
+pub struct CodecRegistry {
+    codecs: HashMap<u64, Arc<dyn BlockCodec>>,
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        let mut codecs = Self { codecs: HashMap::new() };
+        codecs
+            .register(RawData::CODE, RawData)
+            .register(DagPb::CODE, DagPb::default())
+            .register(DagCbor::CODE, DagCbor::default())
+            .register(DagJson::CODE, DagJson::default())
+            .register(GitRaw::CODE, GitRaw::default());
+        codecs
+    }
+}
+
+impl CodecRegistry {
+    /// Registry with only the built-in codecs.
+    pub fn builtin() -> &'static CodecRegistry {
+        &BUILTIN
+    }
+
+    /// Add a codec for the multicodec code, replacing any built-in one.
+    pub fn register(&mut self, code: u64, codec: impl BlockCodec + 'static) -> &mut Self {
+        self.codecs.insert(code, Arc::new(codec));
+        self
+    }
+
+    pub fn supports(&self, code: u64) -> bool {
+        self.codecs.contains_key(&code)
+    }
+
+    pub fn get(&self, code: u64) -> Result<&dyn BlockCodec, CodecError> {
+        self.codecs.get(&code).map(|codec| codec.as_ref()).ok_or(CodecError::UnsupportedCodec(code))
+    }
+
+    /// Decode a [Block] with the codec of its [Cid], within the [DecodeLimits].
+    pub fn decode(&self, block: &Block, limits: DecodeLimits) -> Result<Ipld, CodecError> {
+        self.get(block.cid().codec())?.decode(block.inner(), limits)
+    }
+
+    /// Links of a [Block], found with the codec of its [Cid].
+    pub fn links(&self, block: &Block, limits: DecodeLimits) -> Result<Vec<Cid>, CodecError> {
+        self.get(block.cid().codec())?.links(block.inner(), limits)
+    }
+
+    /// Encode into a [Block] with the codec and hash of the [CidPrefix].
+    pub fn encode(&self, hashers: &HasherRegistry, prefix: &CidPrefix, ipld: &Ipld) -> Result<Block, BlockError> {
+        let data = self.get(prefix.codec)?.encode(ipld)?;
+        Block::from_data_with(hashers, prefix, data)
+    }
+}
+
+impl fmt::Debug for CodecRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut codes = self.codecs.keys().collect::<Vec<_>>();
+        codes.sort_unstable();
+        f.debug_struct("CodecRegistry")
+            .field("codecs", &codes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use multihash_codetable::Code;
+
+    use crate::ipld;
+
+    use super::*;
+
+    /// Toy codec: one CID per line.
+    pub(crate) struct Lines;
+
+    pub(crate) const LINES: u64 = 0x300001;
+
+    impl BlockCodec for Lines {
+        fn decode(&self, data: &Bytes, _limits: DecodeLimits) -> Result<Ipld, CodecError> {
+            let text = std::str::from_utf8(data).map_err(|_| CodecError::MalformedData("not utf-8"))?;
+            let links = text.lines().map(|line| Cid::try_from(line).map(Ipld::Link));
+            Ok(Ipld::List(links.collect::<Result<_, _>>().map_err(|_| CodecError::MalformedData("invalid cid"))?))
+        }
+
+        fn encode(&self, ipld: &Ipld) -> Result<Bytes, CodecError> {
+            let Ipld::List(list) = ipld else {
+                return Err(CodecError::MalformedData("expected a list"));
+            };
+            let lines = list.iter().map(|link| link.as_link().map(|cid| format!("{cid}\n")));
+            let text = lines.collect::<Option<String>>().ok_or(CodecError::MalformedData("expected links"))?;
+            Ok(text.into())
+        }
+    }
+
+    #[test]
+    fn test_codec_registry() {
+        let mut codecs = CodecRegistry::default();
+        for code in [0x55, 0x70, 0x71, 0x78, 0x0129] {
+            assert!(codecs.supports(code));
+        }
+        let prefix = CidPrefix::v1(LINES, Code::Sha2_256);
+        let leaf = codecs.encode(HasherRegistry::builtin(), &CidPrefix::v1(0x71, Code::Sha2_256), &ipld!("leaf")).unwrap();
+        let node = ipld!([*leaf.cid(), *leaf.cid()]);
+        assert!(matches!(codecs.encode(HasherRegistry::builtin(), &prefix, &node), Err(BlockError::Codec(CodecError::UnsupportedCodec(LINES)))));
+        assert_eq!(CodecError::UnsupportedCodec(LINES).to_string(), "codec id 0x300001 is not supported");
+
+        codecs.register(LINES, Lines);
+        let block = codecs.encode(HasherRegistry::builtin(), &prefix, &node).unwrap();
+        assert_eq!(codecs.decode(&block, DecodeLimits::default()).unwrap(), node);
+        assert_eq!(codecs.links(&block, DecodeLimits::default()).unwrap(), [*leaf.cid(), *leaf.cid()]);
+        assert!(CodecRegistry::builtin().decode(&block, DecodeLimits::default()).is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! [IPLD Schemas](https://ipld.io/docs/schemas/): parse the schema DSL and validate [Ipld] against its types.
+
+use std::{borrow::Cow, collections::{BTreeMap, BTreeSet}};
+
+use thiserror::Error;
+
+use super::{Ipld, IpldError, IpldKind};
+
+mod parse;
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("line {line}: {message}")]
+    Syntax {
+        line: usize,
+        message: &'static str,
+    },
+    #[error("type {0} is defined more than once")]
+    DuplicateType(String),
+    #[error("unknown type {0}")]
+    UnknownType(String),
+    #[error("inline union member {0} is not a struct with map representation")]
+    InvalidInlineMember(String),
+    #[error("type {0} is an alias of itself")]
+    AliasCycle(String),
+}
+
+/// Why a value does not match its type.
+#[derive(Debug, Error)]
+pub enum Invalid {
+    #[error(transparent)]
+    Kind(IpldError),
+    #[error("missing field {0:?}")]
+    MissingField(String),
+    #[error("unknown field {0:?}")]
+    UnknownField(String),
+    #[error("expected {expected} tuple fields, found {found}")]
+    TupleLength {
+        expected: usize,
+        found: usize,
+    },
+    #[error("keyed union must have a single entry, found {0}")]
+    UnionEntries(usize),
+    #[error("no union member for {0:?}")]
+    UnknownMember(String),
+    #[error("no union member of kind {0:?}")]
+    UnknownMemberKind(IpldKind),
+    #[error("{0:?} is not an enum member")]
+    UnknownEnumMember(Box<Ipld>),
+    #[error("unknown type {0}")]
+    UnknownType(String),
+}
+
+/// A value failed validation at `path`, a `/` separated list of map keys and list indexes.
+#[derive(Debug, Error)]
+#[error("invalid value at /{path}: {reason}")]
+pub struct ValidationError {
+    pub path: String,
+    pub reason: Invalid,
+}
+
+/// Type in a schema. See <https://ipld.io/docs/schemas/features/typekinds/>.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    /// Reference to a named type, from the schema or the prelude (`String`, `Int`, ...).
+    Named(String),
+    /// Any value of a data model kind, from `type Foo string`.
+    Kind(IpldKind),
+    Any,
+    /// Link, with the type expected at the other end. That type is not checked.
+    Link(Option<String>),
+    List {
+        value: Box<Type>,
+        nullable: bool,
+    },
+    Map {
+        key: Box<Type>,
+        value: Box<Type>,
+        nullable: bool,
+    },
+    Struct {
+        fields: Vec<Field>,
+        repr: StructRepr,
+    },
+    Union(UnionRepr),
+    /// Member names and their represented values.
+    Enum(Vec<(String, Ipld)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// Map key, differs from `name` when renamed.
+    pub key: String,
+    pub ty: Type,
+    pub optional: bool,
+    pub nullable: bool,
+    /// Value assumed when the key is absent.
+    pub implicit: Option<Ipld>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StructRepr {
+    Map,
+    Tuple,
+}
+
+/// Union members, keyed by their discriminant.
+/// See <https://ipld.io/docs/schemas/features/representation-strategies/#union-representations>.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnionRepr {
+    Keyed(Vec<(String, Type)>),
+    Kinded(Vec<(IpldKind, Type)>),
+    Inline {
+        key: String,
+        members: Vec<(String, Type)>,
+    },
+    Envelope {
+        key: String,
+        content: String,
+        members: Vec<(String, Type)>,
+    },
+}
+
+/// Types available without being defined.
+fn prelude(name: &str) -> Option<Type> {
+    Some(match name {
+        "Null" => Type::Kind(IpldKind::Null),
+        "Bool" => Type::Kind(IpldKind::Bool),
+        "Int" => Type::Kind(IpldKind::Integer),
+        "Float" => Type::Kind(IpldKind::Float),
+        "String" => Type::Kind(IpldKind::String),
+        "Bytes" => Type::Kind(IpldKind::Bytes),
+        "Link" => Type::Link(None),
+        "Any" => Type::Any,
+        _ => return None,
+    })
+}
+
+/// Parsed schema: named types that only reference each other or the prelude.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schema {
+    types: BTreeMap<String, Type>,
+}
+
+impl Schema {
+    pub fn parse(src: &str) -> Result<Self, SchemaError> {
+        let schema = Self {
+            types: parse::parse(src)?,
+        };
+        schema.types.keys().try_for_each(|name| schema.check_alias(name))?;
+        schema.types.values().try_for_each(|ty| schema.check_refs(ty))?;
+        Ok(schema)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Type> {
+        self.types.get(name)
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = (&str, &Type)> {
+        self.types.iter().map(|(name, ty)| (name.as_str(), ty))
+    }
+
+    /// Check the value matches the named type, returning the first mismatch.
+    pub fn validate(&self, name: &str, ipld: &Ipld) -> Result<(), ValidationError> {
+        self.check(&Type::Named(name.to_owned()), ipld, &mut vec![])
+    }
+
+    fn resolve(&self, name: &str) -> Option<Cow<'_, Type>> {
+        self.types.get(name).map(Cow::Borrowed).or_else(|| prelude(name).map(Cow::Owned))
+    }
+
+    /// Check a chain of aliases, such as `type A B`, ends in a type that is not an alias.
+    fn check_alias(&self, name: &str) -> Result<(), SchemaError> {
+        let mut seen = BTreeSet::new();
+        let mut next = name;
+        while let Some(Type::Named(target)) = self.types.get(next) {
+            if !seen.insert(next) {
+                return Err(SchemaError::AliasCycle(name.to_owned()));
+            }
+            next = target;
+        }
+        Ok(())
+    }
+
+    /// Check every [Type::Named] resolves, and inline union members are map structs.
+    fn check_refs(&self, ty: &Type) -> Result<(), SchemaError> {
+        let named = |name: &str| match self.resolve(name) {
+            Some(_) => Ok(()),
+            None => Err(SchemaError::UnknownType(name.to_owned())),
+        };
+        match ty {
+            Type::Named(name) | Type::Link(Some(name)) => named(name),
+            Type::Kind(_) | Type::Any | Type::Link(None) | Type::Enum(_) => Ok(()),
+            Type::List { value, .. } => self.check_refs(value),
+            Type::Map { key, value, .. } => {
+                self.check_refs(key)?;
+                self.check_refs(value)
+            },
+            Type::Struct { fields, .. } => fields.iter().try_for_each(|f| self.check_refs(&f.ty)),
+            Type::Union(UnionRepr::Kinded(members)) => members.iter().try_for_each(|(_, ty)| self.check_refs(ty)),
+            Type::Union(UnionRepr::Keyed(members) | UnionRepr::Envelope { members, .. }) => {
+                members.iter().try_for_each(|(_, ty)| self.check_refs(ty))
+            },
+            Type::Union(UnionRepr::Inline { members, .. }) => members.iter().try_for_each(|(_, ty)| {
+                self.check_refs(ty)?;
+                match self.inline_fields(ty) {
+                    Some(_) => Ok(()),
+                    None => Err(SchemaError::InvalidInlineMember(format!("{ty:?}"))),
+                }
+            }),
+        }
+    }
+
+    /// Fields of an inline union member.
+    fn inline_fields<'a>(&'a self, ty: &'a Type) -> Option<&'a [Field]> {
+        match ty {
+            Type::Named(name) => self.inline_fields(self.types.get(name)?),
+            Type::Struct { fields, repr: StructRepr::Map } => Some(fields),
+            _ => None,
+        }
+    }
+
+    fn check(&self, ty: &Type, ipld: &Ipld, path: &mut Vec<String>) -> Result<(), ValidationError> {
+        let invalid = |path: &[String], reason| ValidationError {
+            path: path.join("/"),
+            reason,
+        };
+        let kind = |expected| match ipld.kind() {
+            found if found == expected => Ok(()),
+            found => Err(invalid(path, Invalid::Kind(IpldError::BadConversion { expected, found }))),
+        };
+        match ty {
+            Type::Named(name) => {
+                let ty = self.resolve(name).ok_or_else(|| invalid(path, Invalid::UnknownType(name.clone())))?;
+                self.check(&ty, ipld, path)
+            },
+            Type::Kind(expected) => kind(*expected),
+            Type::Any => Ok(()),
+            Type::Link(_) => kind(IpldKind::Link),
+            Type::List { value, nullable } => {
+                kind(IpldKind::List)?;
+                let Ipld::List(list) = ipld else { unreachable!() };
+                for (i, item) in list.iter().enumerate() {
+                    path.push(i.to_string());
+                    self.check_nullable(value, *nullable, item, path)?;
+                    path.pop();
+                }
+                Ok(())
+            },
+            Type::Map { key, value, nullable } => {
+                kind(IpldKind::Map)?;
+                let Ipld::Map(map) = ipld else { unreachable!() };
+                for (k, v) in map {
+                    path.push(k.clone());
+                    self.check(key, &Ipld::String(k.clone()), path)?;
+                    self.check_nullable(value, *nullable, v, path)?;
+                    path.pop();
+                }
+                Ok(())
+            },
+            Type::Struct { fields, repr: StructRepr::Map } => {
+                kind(IpldKind::Map)?;
+                let Ipld::Map(map) = ipld else { unreachable!() };
+                self.check_fields(fields, map, None, path)
+            },
+            Type::Struct { fields, repr: StructRepr::Tuple } => {
+                kind(IpldKind::List)?;
+                let Ipld::List(list) = ipld else { unreachable!() };
+                if list.len() != fields.len() {
+                    return Err(invalid(path, Invalid::TupleLength { expected: fields.len(), found: list.len() }));
+                }
+                for (field, item) in fields.iter().zip(list) {
+                    path.push(field.name.clone());
+                    self.check_nullable(&field.ty, field.nullable, item, path)?;
+                    path.pop();
+                }
+                Ok(())
+            },
+            Type::Union(UnionRepr::Keyed(members)) => {
+                kind(IpldKind::Map)?;
+                let Ipld::Map(map) = ipld else { unreachable!() };
+                let Some((key, value)) = map.iter().next().filter(|_| map.len() == 1) else {
+                    return Err(invalid(path, Invalid::UnionEntries(map.len())));
+                };
+                let (_, ty) = members.iter().find(|(k, _)| k == key)
+                    .ok_or_else(|| invalid(path, Invalid::UnknownMember(key.clone())))?;
+                path.push(key.clone());
+                self.check(ty, value, path)?;
+                path.pop();
+                Ok(())
+            },
+            Type::Union(UnionRepr::Kinded(members)) => {
+                let (_, ty) = members.iter().find(|(k, _)| *k == ipld.kind())
+                    .ok_or_else(|| invalid(path, Invalid::UnknownMemberKind(ipld.kind())))?;
+                self.check(ty, ipld, path)
+            },
+            Type::Union(UnionRepr::Inline { key, members }) => {
+                kind(IpldKind::Map)?;
+                let Ipld::Map(map) = ipld else { unreachable!() };
+                let ty = self.discriminant(members, map, key, path)?;
+                let fields = self.inline_fields(ty).expect("checked when parsed");
+                self.check_fields(fields, map, Some(key), path)
+            },
+            Type::Union(UnionRepr::Envelope { key, content, members }) => {
+                kind(IpldKind::Map)?;
+                let Ipld::Map(map) = ipld else { unreachable!() };
+                if let Some(k) = map.keys().find(|k| *k != key && *k != content) {
+                    return Err(invalid(path, Invalid::UnknownField(k.clone())));
+                }
+                let ty = self.discriminant(members, map, key, path)?;
+                let value = map.get(content).ok_or_else(|| invalid(path, Invalid::MissingField(content.clone())))?;
+                path.push(content.clone());
+                self.check(ty, value, path)?;
+                path.pop();
+                Ok(())
+            },
+            Type::Enum(members) => {
+                if let Some((_, value)) = members.first() {
+                    kind(value.kind())?;
+                }
+                match members.iter().any(|(_, value)| value == ipld) {
+                    true => Ok(()),
+                    false => Err(invalid(path, Invalid::UnknownEnumMember(Box::new(ipld.clone())))),
+                }
+            },
+        }
+    }
+
+    fn check_nullable(&self, ty: &Type, nullable: bool, ipld: &Ipld, path: &mut Vec<String>) -> Result<(), ValidationError> {
+        match (nullable, ipld) {
+            (true, Ipld::Null) => Ok(()),
+            _ => self.check(ty, ipld, path),
+        }
+    }
+
+    /// Check a map against struct fields, ignoring the `skip` key.
+    fn check_fields(&self, fields: &[Field], map: &BTreeMap<String, Ipld>, skip: Option<&String>, path: &mut Vec<String>) -> Result<(), ValidationError> {
+        if let Some(key) = map.keys().find(|k| Some(*k) != skip && !fields.iter().any(|f| &f.key == *k)) {
+            return Err(ValidationError {
+                path: path.join("/"),
+                reason: Invalid::UnknownField(key.clone()),
+            });
+        }
+        for field in fields {
+            match map.get(&field.key) {
+                Some(value) => {
+                    path.push(field.key.clone());
+                    self.check_nullable(&field.ty, field.nullable, value, path)?;
+                    path.pop();
+                },
+                None if field.optional || field.implicit.is_some() => {},
+                None => return Err(ValidationError {
+                    path: path.join("/"),
+                    reason: Invalid::MissingField(field.key.clone()),
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Member type named by the string at `key`.
+    fn discriminant<'a>(&self, members: &'a [(String, Type)], map: &BTreeMap<String, Ipld>, key: &String, path: &mut Vec<String>) -> Result<&'a Type, ValidationError> {
+        let invalid = |path: &[String], reason| ValidationError {
+            path: path.join("/"),
+            reason,
+        };
+        let discriminant = match map.get(key) {
+            Some(Ipld::String(s)) => s,
+            Some(other) => {
+                path.push(key.clone());
+                let found = other.kind();
+                return Err(invalid(path, Invalid::Kind(IpldError::BadConversion { expected: IpldKind::String, found })));
+            },
+            None => return Err(invalid(path, Invalid::MissingField(key.clone()))),
+        };
+        members.iter()
+            .find(|(k, _)| k == discriminant)
+            .map(|(_, ty)| ty)
+            .ok_or_else(|| invalid(path, Invalid::UnknownMember(discriminant.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipld;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        type Entry struct {
+            name String
+            hash &Any
+            size optional Int
+            tags [nullable String]
+            kind Kind
+            status Status
+        } representation map {
+            field hash rename "Hash"
+        }
+        type Kind union {
+            | Point "point"
+            | {String:Int} "map"
+        } representation keyed
+        type Point struct { x Int y Int } representation tuple
+        type Status enum { | Ok | Failed ("failed") } representation string
+        type Message union {
+            | Ping "ping"
+            | Data "data"
+        } representation inline { discriminantKey "type" }
+        type Ping struct {}
+        type Data struct { id Int }
+        type Value union {
+            | String string
+            | Point list
+        } representation kinded
+    "#;
+
+    fn invalid(schema: &Schema, name: &str, ipld: &Ipld) -> (String, Invalid) {
+        let err = schema.validate(name, ipld).unwrap_err();
+        (err.path, err.reason)
+    }
+
+    #[test]
+    fn test_schema_validate() {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let cid = cid::Cid::default();
+        let entry = ipld!({
+            "name": "banana",
+            "Hash": cid,
+            "tags": ["yellow", null],
+            "kind": { "point": [1, 2] },
+            "status": "failed",
+        });
+        schema.validate("Entry", &entry).unwrap();
+
+        let bad_tag = ipld!({ "name": "a", "Hash": cid, "tags": [1], "kind": { "map": {} }, "status": "Ok" });
+        let (path, reason) = invalid(&schema, "Entry", &bad_tag);
+        assert_eq!(path, "tags/0");
+        assert!(matches!(reason, Invalid::Kind(IpldError::BadConversion { expected: IpldKind::String, found: IpldKind::Integer })));
+
+        let bad_point = ipld!({ "name": "a", "Hash": cid, "tags": [], "kind": { "point": [1] }, "status": "Ok" });
+        assert!(matches!(invalid(&schema, "Entry", &bad_point), (path, Invalid::TupleLength { expected: 2, found: 1 }) if path == "kind/point"));
+        let missing = ipld!({ "name": "a", "tags": [], "kind": { "point": [1, 2] }, "status": "Ok" });
+        assert!(matches!(invalid(&schema, "Entry", &missing), (_, Invalid::MissingField(f)) if f == "Hash"));
+        let status = ipld!({ "name": "a", "Hash": cid, "tags": [], "kind": { "map": { "a": 1 } }, "status": "Failed" });
+        assert!(matches!(invalid(&schema, "Entry", &status), (path, Invalid::UnknownEnumMember(_)) if path == "status"));
+
+        schema.validate("Message", &ipld!({ "type": "ping" })).unwrap();
+        schema.validate("Message", &ipld!({ "type": "data", "id": 1 })).unwrap();
+        assert!(matches!(invalid(&schema, "Message", &ipld!({ "type": "ping", "id": 1 })), (_, Invalid::UnknownField(_))));
+        assert!(matches!(invalid(&schema, "Message", &ipld!({ "type": "pong" })), (_, Invalid::UnknownMember(_))));
+
+        schema.validate("Value", &ipld!("banana")).unwrap();
+        schema.validate("Value", &ipld!([1, 2])).unwrap();
+        assert!(matches!(invalid(&schema, "Value", &ipld!(1)), (_, Invalid::UnknownMemberKind(IpldKind::Integer))));
+        assert!(matches!(invalid(&schema, "Missing", &ipld!(null)), (_, Invalid::UnknownType(_))));
+    }
+
+    #[test]
+    fn test_schema_refs() {
+        assert!(matches!(Schema::parse("type A [B]"), Err(SchemaError::UnknownType(t)) if t == "B"));
+        assert!(matches!(
+            Schema::parse("type A union { | Int \"i\" } representation inline { discriminantKey \"t\" }"),
+            Err(SchemaError::InvalidInlineMember(_))
+        ));
+        assert!(matches!(Schema::parse("type A B\ntype B A"), Err(SchemaError::AliasCycle(_))));
+        assert!(matches!(
+            Schema::parse("type A B\ntype B A\ntype U union { | A \"a\" } representation inline { discriminantKey \"t\" }"),
+            Err(SchemaError::AliasCycle(_))
+        ));
+        assert!(Schema::parse("type A B\ntype B [A]").is_ok());
+    }
+}
@@ -0,0 +1,428 @@
+//! Parser for the [IPLD Schema DSL](https://ipld.io/docs/schemas/using/authoring-guide/).
+
+use std::collections::BTreeMap;
+
+use crate::ipld::{Ipld, IpldKind};
+
+use super::{Field, SchemaError, StructRepr, Type, UnionRepr};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    /// Names, keywords and bare literals such as `true` or `-1`.
+    Word(&'a str),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token<'_>, usize)>, SchemaError> {
+    let mut tokens = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let syntax = |message| SchemaError::Syntax { line: line_no, message };
+        let mut rest = line.trim_start();
+        while let Some(c) = rest.chars().next() {
+            match c {
+                '#' => break,
+                '{' | '}' | '[' | ']' | '(' | ')' | ':' | '|' | '&' => {
+                    tokens.push((Token::Punct(c), line_no));
+                    rest = &rest[1..];
+                },
+                '"' => {
+                    let end = rest[1..].find('"').ok_or_else(|| syntax("unterminated string"))?;
+                    tokens.push((Token::Str(rest[1..=end].to_owned()), line_no));
+                    rest = &rest[end + 2..];
+                },
+                c if c.is_alphanumeric() || c == '_' || c == '-' => {
+                    let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(rest.len());
+                    tokens.push((Token::Word(&rest[..end]), line_no));
+                    rest = &rest[end..];
+                },
+                _ => return Err(syntax("unexpected character")),
+            }
+            rest = rest.trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse schema source into its named types, without checking references.
+pub(super) fn parse(src: &str) -> Result<BTreeMap<String, Type>, SchemaError> {
+    let mut p = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+    };
+    let mut types = BTreeMap::new();
+    while p.peek().is_some() {
+        p.keyword("type")?;
+        let name = p.word()?;
+        let ty = p.typedef()?;
+        if types.insert(name.clone(), ty).is_some() {
+            return Err(SchemaError::DuplicateType(name));
+        }
+    }
+    Ok(types)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token<'a>, usize)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn error(&self, message: &'static str) -> SchemaError {
+        let line = self.tokens.get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line);
+        SchemaError::Syntax { line, message }
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, SchemaError> {
+        let token = self.peek().cloned().ok_or_else(|| self.error("unexpected end of schema"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn word(&mut self) -> Result<String, SchemaError> {
+        match self.peek() {
+            Some(Token::Word(w)) => {
+                let w = w.to_string();
+                self.pos += 1;
+                Ok(w)
+            },
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, SchemaError> {
+        match self.peek() {
+            Some(Token::Str(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            },
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &'static str) -> Result<(), SchemaError> {
+        match self.eat_keyword(keyword) {
+            true => Ok(()),
+            false => Err(self.error("expected a keyword")),
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek() == Some(&Token::Word(keyword));
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn punct(&mut self, c: char) -> Result<(), SchemaError> {
+        match self.eat_punct(c) {
+            true => Ok(()),
+            false => Err(self.error("unexpected token")),
+        }
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(c));
+        self.pos += usize::from(found);
+        found
+    }
+
+    /// The name after `representation`, if any.
+    fn representation(&mut self) -> Result<Option<String>, SchemaError> {
+        match self.eat_keyword("representation") {
+            true => self.word().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn typedef(&mut self) -> Result<Type, SchemaError> {
+        if self.eat_keyword("struct") {
+            return self.structure();
+        }
+        if self.eat_keyword("union") {
+            return self.union();
+        }
+        if self.eat_keyword("enum") {
+            return self.enumeration();
+        }
+        let ty = self.type_ref()?;
+        match (&ty, self.representation()?.as_deref()) {
+            (_, None) | (Type::Map { .. }, Some("map")) | (Type::List { .. }, Some("list")) => Ok(ty),
+            _ => Err(self.error("unsupported representation")),
+        }
+    }
+
+    /// A type name, or an inline map, list or link type.
+    fn type_ref(&mut self) -> Result<Type, SchemaError> {
+        Ok(match self.next()? {
+            Token::Punct('{') => {
+                let key = self.type_ref()?;
+                self.punct(':')?;
+                let nullable = self.eat_keyword("nullable");
+                let value = self.type_ref()?;
+                self.punct('}')?;
+                Type::Map {
+                    key: Box::new(key),
+                    value: Box::new(value),
+                    nullable,
+                }
+            },
+            Token::Punct('[') => {
+                let nullable = self.eat_keyword("nullable");
+                let value = self.type_ref()?;
+                self.punct(']')?;
+                Type::List {
+                    value: Box::new(value),
+                    nullable,
+                }
+            },
+            Token::Punct('&') => match self.word()?.as_str() {
+                "Any" => Type::Link(None),
+                name => Type::Link(Some(name.to_owned())),
+            },
+            Token::Word(name) => match kind(name) {
+                Some(IpldKind::Link) => Type::Link(None),
+                Some(kind) => Type::Kind(kind),
+                None if name == "any" => Type::Any,
+                None => Type::Named(name.to_owned()),
+            },
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected a type"));
+            },
+        })
+    }
+
+    fn structure(&mut self) -> Result<Type, SchemaError> {
+        self.punct('{')?;
+        let mut fields = vec![];
+        while !self.eat_punct('}') {
+            let name = self.word()?;
+            let optional = self.eat_keyword("optional");
+            let nullable = self.eat_keyword("nullable");
+            let ty = self.type_ref()?;
+            if fields.iter().any(|f: &Field| f.name == name) {
+                return Err(self.error("duplicate field"));
+            }
+            fields.push(Field {
+                key: name.clone(),
+                name,
+                ty,
+                optional,
+                nullable,
+                implicit: None,
+            });
+        }
+        let repr = match self.representation()?.as_deref() {
+            None | Some("map") => {
+                if self.eat_punct('{') {
+                    while !self.eat_punct('}') {
+                        self.keyword("field")?;
+                        let name = self.word()?;
+                        let Some(i) = fields.iter().position(|f| f.name == name) else {
+                            return Err(self.error("unknown field"));
+                        };
+                        loop {
+                            if self.eat_keyword("rename") {
+                                fields[i].key = self.string()?;
+                            } else if self.eat_keyword("implicit") {
+                                fields[i].implicit = Some(self.value()?);
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                }
+                StructRepr::Map
+            },
+            Some("tuple") => {
+                if fields.iter().any(|f| f.optional) {
+                    return Err(self.error("tuple structs can not have optional fields"));
+                }
+                StructRepr::Tuple
+            },
+            _ => return Err(self.error("unsupported representation")),
+        };
+        Ok(Type::Struct { fields, repr })
+    }
+
+    /// An `implicit` value: a string, integer or bool.
+    fn value(&mut self) -> Result<Ipld, SchemaError> {
+        Ok(match self.next()? {
+            Token::Str(s) => Ipld::String(s),
+            Token::Word("true") => Ipld::Bool(true),
+            Token::Word("false") => Ipld::Bool(false),
+            Token::Word(w) => Ipld::Integer(w.parse().map_err(|_| self.error("expected a string, integer or bool"))?),
+            _ => return Err(self.error("expected a string, integer or bool")),
+        })
+    }
+
+    fn union(&mut self) -> Result<Type, SchemaError> {
+        self.punct('{')?;
+        let mut members = vec![];
+        while !self.eat_punct('}') {
+            self.punct('|')?;
+            let ty = self.type_ref()?;
+            members.push((ty, self.next()?));
+        }
+        let keyed = |p: &Self, members: Vec<(Type, Token)>| {
+            members.into_iter()
+                .map(|(ty, key)| match key {
+                    Token::Str(key) => Ok((key, ty)),
+                    _ => Err(p.error("expected a string discriminant")),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let repr = match self.representation()?.as_deref() {
+            Some("keyed") => UnionRepr::Keyed(keyed(self, members)?),
+            Some("kinded") => UnionRepr::Kinded(members.into_iter()
+                .map(|(ty, key)| match key {
+                    Token::Word(k) => kind(k).map(|k| (k, ty)).ok_or_else(|| self.error("expected a kind")),
+                    _ => Err(self.error("expected a kind")),
+                })
+                .collect::<Result<_, _>>()?),
+            Some("inline") => {
+                self.punct('{')?;
+                self.keyword("discriminantKey")?;
+                let key = self.string()?;
+                self.punct('}')?;
+                UnionRepr::Inline {
+                    key,
+                    members: keyed(self, members)?,
+                }
+            },
+            Some("envelope") => {
+                self.punct('{')?;
+                let (mut key, mut content) = (None, None);
+                while !self.eat_punct('}') {
+                    if self.eat_keyword("discriminantKey") {
+                        key = Some(self.string()?);
+                    } else {
+                        self.keyword("contentKey")?;
+                        content = Some(self.string()?);
+                    }
+                }
+                let (Some(key), Some(content)) = (key, content) else {
+                    return Err(self.error("envelope unions need a discriminantKey and contentKey"));
+                };
+                UnionRepr::Envelope {
+                    key,
+                    content,
+                    members: keyed(self, members)?,
+                }
+            },
+            None => return Err(self.error("unions need a representation")),
+            _ => return Err(self.error("unsupported representation")),
+        };
+        Ok(Type::Union(repr))
+    }
+
+    /// Members may give their representation value in parentheses, as in `| Ok ("0")`, which int enums require.
+    fn enumeration(&mut self) -> Result<Type, SchemaError> {
+        self.punct('{')?;
+        let mut members = vec![];
+        while !self.eat_punct('}') {
+            self.punct('|')?;
+            let name = self.word()?;
+            let value = match self.eat_punct('(') {
+                true => {
+                    let value = self.string()?;
+                    self.punct(')')?;
+                    Some(value)
+                },
+                false => None,
+            };
+            members.push((name, value));
+        }
+        let repr = self.representation()?;
+        let members = members.into_iter()
+            .map(|(name, value)| {
+                Ok(match repr.as_deref() {
+                    None | Some("string") => Ipld::String(value.unwrap_or_else(|| name.clone())),
+                    Some("int") => {
+                        let value = value.ok_or_else(|| self.error("int enums need a value for every member"))?;
+                        Ipld::Integer(value.parse().map_err(|_| self.error("expected an integer"))?)
+                    },
+                    _ => return Err(self.error("unsupported representation")),
+                })
+                .map(|value| (name, value))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Type::Enum(members))
+    }
+}
+
+/// Kind named in lowercase, as in `type Foo string` or kinded union members.
+fn kind(name: &str) -> Option<IpldKind> {
+    Some(match name {
+        "null" => IpldKind::Null,
+        "bool" => IpldKind::Bool,
+        "int" => IpldKind::Integer,
+        "float" => IpldKind::Float,
+        "string" => IpldKind::String,
+        "bytes" => IpldKind::Bytes,
+        "list" => IpldKind::List,
+        "map" => IpldKind::Map,
+        "link" => IpldKind::Link,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_parse() {
+        let types = parse(r#"
+            # Comment
+            type Name string
+            type Entry struct {
+                name Name
+                hash &Any
+                size optional Int
+                tags nullable [String]
+            } representation map {
+                field hash rename "Hash"
+                field tags implicit "none"
+            }
+            type Point struct { x Int y Int } representation tuple
+            type Shape union {
+                | Point "point"
+                | {String:nullable Int} "map"
+            } representation keyed
+            type Status enum {
+                | Ok ("0")
+                | Failed ("1")
+            } representation int
+        "#).unwrap();
+
+        assert_eq!(types["Name"], Type::Kind(IpldKind::String));
+        let Type::Struct { fields, repr: StructRepr::Map } = &types["Entry"] else { panic!("expected map struct") };
+        assert_eq!(fields[1].key, "Hash");
+        assert_eq!(fields[1].ty, Type::Link(None));
+        assert!(fields[2].optional);
+        assert_eq!(fields[3].implicit, Some(Ipld::String(String::from("none"))));
+        assert_eq!(fields[3].ty, Type::List { value: Box::new(Type::Named(String::from("String"))), nullable: false });
+        assert!(matches!(types["Point"], Type::Struct { repr: StructRepr::Tuple, .. }));
+        let Type::Union(UnionRepr::Keyed(members)) = &types["Shape"] else { panic!("expected keyed union") };
+        assert_eq!(members[1].0, "map");
+        assert_eq!(types["Status"], Type::Enum(vec![
+            (String::from("Ok"), Ipld::Integer(0)),
+            (String::from("Failed"), Ipld::Integer(1)),
+        ]));
+
+        assert!(matches!(parse("type A struct { a Int"), Err(SchemaError::Syntax { line: 1, .. })));
+        assert!(matches!(parse("type A union { | B \"b\" }"), Err(SchemaError::Syntax { .. })));
+        assert!(matches!(parse("type A enum { | B } representation int"), Err(SchemaError::Syntax { .. })));
+        assert!(matches!(parse("type A int\ntype A string"), Err(SchemaError::DuplicateType(_))));
+        assert!(matches!(parse("type A string\ntype B $"), Err(SchemaError::Syntax { line: 2, .. })));
+    }
+}
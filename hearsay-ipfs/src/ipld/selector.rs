@@ -0,0 +1,328 @@
+//! IPLD Selectors, declarative descriptions of a walk over an [Ipld] graph.
+//! See <https://ipld.io/specs/selectors/>.
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use super::Ipld;
+
+#[derive(Debug, Error)]
+pub enum SelectorError {
+    #[error("invalid selector: {0}")]
+    Invalid(&'static str),
+    #[error("unknown selector {0:?}")]
+    Unknown(String),
+}
+
+/// Bound on how many times an [Selector::ExploreRecursive] repeats its sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecursionLimit {
+    None,
+    Depth(u64),
+}
+
+/// See the [schema](https://ipld.io/specs/selectors/#the-selector-schema).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selector {
+    /// Match the current node.
+    Matcher,
+    /// Explore every child of a map or list.
+    ExploreAll {
+        next: Box<Selector>,
+    },
+    /// Explore the named map fields.
+    ExploreFields {
+        fields: BTreeMap<String, Selector>,
+    },
+    /// Explore one list index.
+    ExploreIndex {
+        index: usize,
+        next: Box<Selector>,
+    },
+    /// Explore list indices in `start..end`.
+    ExploreRange {
+        start: usize,
+        end: usize,
+        next: Box<Selector>,
+    },
+    /// Repeat `sequence`, restarting it at each [Selector::ExploreRecursiveEdge].
+    ExploreRecursive {
+        limit: RecursionLimit,
+        sequence: Box<Selector>,
+        /// Position within `sequence` while walking, `None` before the walk starts.
+        current: Option<Box<Selector>>,
+    },
+    /// Marks where an [Selector::ExploreRecursive] restarts its sequence.
+    ExploreRecursiveEdge,
+    /// Explore with every selector, matching if any of them match.
+    ExploreUnion(Vec<Selector>),
+    /// Explore the node as an Advanced Data Layout.
+    /// No ADLs are implemented, so the node is explored as is.
+    ExploreInterpretAs {
+        adl: String,
+        next: Box<Selector>,
+    },
+}
+
+impl Selector {
+    pub fn explore_recursive(limit: RecursionLimit, sequence: Selector) -> Self {
+        Self::ExploreRecursive {
+            limit,
+            sequence: Box::new(sequence),
+            current: None,
+        }
+    }
+
+    /// Should the node be reported as matched.
+    pub fn decide(&self, node: &Ipld) -> bool {
+        match self {
+            Self::Matcher => true,
+            Self::ExploreRecursive { sequence, current, .. } => current.as_ref().unwrap_or(sequence).decide(node),
+            Self::ExploreUnion(members) => members.iter().any(|s| s.decide(node)),
+            Self::ExploreInterpretAs { next, .. } => next.decide(node),
+            _ => false,
+        }
+    }
+
+    /// Selector to apply to the child of the node at the path segment, if it is to be explored.
+    pub fn explore(&self, node: &Ipld, segment: &str) -> Option<Selector> {
+        let index = || match node {
+            Ipld::List(_) => segment.parse::<usize>().ok(),
+            _ => None,
+        };
+        match self {
+            Self::Matcher | Self::ExploreRecursiveEdge => None,
+            Self::ExploreAll { next } => Some(*next.clone()),
+            Self::ExploreFields { fields } => match node {
+                Ipld::Map(_) => fields.get(segment).cloned(),
+                _ => None,
+            },
+            Self::ExploreIndex { index: i, next } => (index()? == *i).then(|| *next.clone()),
+            Self::ExploreRange { start, end, next } => (start..end).contains(&&index()?).then(|| *next.clone()),
+            Self::ExploreRecursive { limit, sequence, current } => {
+                let next = current.as_ref().unwrap_or(sequence).explore(node, segment)?;
+                if !next.has_edge() {
+                    return Some(Self::ExploreRecursive { limit: *limit, sequence: sequence.clone(), current: Some(Box::new(next)) });
+                }
+                let limit = match limit {
+                    RecursionLimit::Depth(depth) if *depth < 2 => return next.replace_edge(None),
+                    RecursionLimit::Depth(depth) => RecursionLimit::Depth(depth - 1),
+                    RecursionLimit::None => RecursionLimit::None,
+                };
+                let next = next.replace_edge(Some(sequence))?;
+                Some(Self::ExploreRecursive { limit, sequence: sequence.clone(), current: Some(Box::new(next)) })
+            },
+            Self::ExploreUnion(members) => {
+                let mut next = members.iter().filter_map(|s| s.explore(node, segment)).collect::<Vec<_>>();
+                match next.len() {
+                    0 => None,
+                    1 => next.pop(),
+                    _ => Some(Self::ExploreUnion(next)),
+                }
+            },
+            Self::ExploreInterpretAs { next, .. } => next.explore(node, segment),
+        }
+    }
+
+    fn has_edge(&self) -> bool {
+        match self {
+            Self::ExploreRecursiveEdge => true,
+            Self::ExploreUnion(members) => members.iter().any(|s| s.has_edge()),
+            _ => false,
+        }
+    }
+
+    /// Swap recursion edges for the restarted sequence, or drop them when the limit is reached.
+    fn replace_edge(self, sequence: Option<&Selector>) -> Option<Selector> {
+        match self {
+            Self::ExploreRecursiveEdge => sequence.cloned(),
+            Self::ExploreUnion(members) => {
+                let mut members = members.into_iter().filter_map(|s| s.replace_edge(sequence)).collect::<Vec<_>>();
+                match members.len() {
+                    0 => None,
+                    1 => members.pop(),
+                    _ => Some(Self::ExploreUnion(members)),
+                }
+            },
+            other => Some(other),
+        }
+    }
+}
+
+fn map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+    Ipld::Map(entries.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+}
+
+impl From<&Selector> for Ipld {
+    fn from(value: &Selector) -> Self {
+        match value {
+            Selector::Matcher => map([(".", map([]))]),
+            Selector::ExploreAll { next } => map([("a", map([(">", Ipld::from(next.as_ref()))]))]),
+            Selector::ExploreFields { fields } => {
+                let fields = fields.iter().map(|(k, v)| (k.clone(), Ipld::from(v))).collect();
+                map([("f", map([("f>", Ipld::Map(fields))]))])
+            },
+            Selector::ExploreIndex { index, next } => {
+                map([("i", map([("i", Ipld::Integer(*index as i128)), (">", Ipld::from(next.as_ref()))]))])
+            },
+            Selector::ExploreRange { start, end, next } => map([("r", map([
+                ("^", Ipld::Integer(*start as i128)),
+                ("$", Ipld::Integer(*end as i128)),
+                (">", Ipld::from(next.as_ref())),
+            ]))]),
+            Selector::ExploreRecursive { limit, sequence, .. } => {
+                let limit = match limit {
+                    RecursionLimit::None => map([("none", map([]))]),
+                    RecursionLimit::Depth(depth) => map([("depth", Ipld::Integer(*depth as i128))]),
+                };
+                map([("R", map([("l", limit), (":>", Ipld::from(sequence.as_ref()))]))])
+            },
+            Selector::ExploreRecursiveEdge => map([("@", map([]))]),
+            Selector::ExploreUnion(members) => map([("|", Ipld::List(members.iter().map(Ipld::from).collect()))]),
+            Selector::ExploreInterpretAs { adl, next } => {
+                map([("~", map([("as", Ipld::String(adl.clone())), (">", Ipld::from(next.as_ref()))]))])
+            },
+        }
+    }
+}
+
+impl From<Selector> for Ipld {
+    fn from(value: Selector) -> Self {
+        Ipld::from(&value)
+    }
+}
+
+impl TryFrom<&Ipld> for Selector {
+    type Error = SelectorError;
+    fn try_from(value: &Ipld) -> Result<Self, Self::Error> {
+        let Ipld::Map(outer) = value else {
+            return Err(SelectorError::Invalid("selector must be a map"));
+        };
+        let mut entries = outer.iter();
+        let (Some((kind, body)), None) = (entries.next(), entries.next()) else {
+            return Err(SelectorError::Invalid("selector must have exactly one key"));
+        };
+        let field = |name: &'static str| match body {
+            Ipld::Map(m) => m.get(name).ok_or(SelectorError::Invalid("missing selector field")),
+            _ => Err(SelectorError::Invalid("selector body must be a map")),
+        };
+        let next = || Ok::<_, SelectorError>(Box::new(Selector::try_from(field(">")?)?));
+        let int = |name: &'static str| match field(name)? {
+            Ipld::Integer(i) => u64::try_from(*i).map_err(|_| SelectorError::Invalid("selector integer out of range")),
+            _ => Err(SelectorError::Invalid("selector field must be an integer")),
+        };
+        let index = |name: &'static str| usize::try_from(int(name)?).map_err(|_| SelectorError::Invalid("selector integer out of range"));
+        Ok(match kind.as_str() {
+            "." => Selector::Matcher,
+            "a" => Selector::ExploreAll { next: next()? },
+            "f" => match field("f>")? {
+                Ipld::Map(fields) => Selector::ExploreFields {
+                    fields: fields.iter()
+                        .map(|(k, v)| Ok((k.clone(), Selector::try_from(v)?)))
+                        .collect::<Result<_, SelectorError>>()?,
+                },
+                _ => return Err(SelectorError::Invalid("fields must be a map")),
+            },
+            "i" => Selector::ExploreIndex { index: index("i")?, next: next()? },
+            "r" => Selector::ExploreRange { start: index("^")?, end: index("$")?, next: next()? },
+            "R" => {
+                let limit = match field("l")? {
+                    Ipld::Map(l) if l.contains_key("none") => RecursionLimit::None,
+                    Ipld::Map(l) => match l.get("depth") {
+                        Some(Ipld::Integer(depth)) => RecursionLimit::Depth(u64::try_from(*depth).map_err(|_| SelectorError::Invalid("selector integer out of range"))?),
+                        _ => return Err(SelectorError::Invalid("unknown recursion limit")),
+                    },
+                    _ => return Err(SelectorError::Invalid("recursion limit must be a map")),
+                };
+                Selector::explore_recursive(limit, Selector::try_from(field(":>")?)?)
+            },
+            "@" => Selector::ExploreRecursiveEdge,
+            "|" => match body {
+                Ipld::List(members) => Selector::ExploreUnion(members.iter().map(Selector::try_from).collect::<Result<_, _>>()?),
+                _ => return Err(SelectorError::Invalid("union must be a list")),
+            },
+            "~" => match field("as")? {
+                Ipld::String(adl) => Selector::ExploreInterpretAs { adl: adl.clone(), next: next()? },
+                _ => return Err(SelectorError::Invalid("ADL name must be a string")),
+            },
+            other => return Err(SelectorError::Unknown(other.to_owned())),
+        })
+    }
+}
+
+impl TryFrom<Ipld> for Selector {
+    type Error = SelectorError;
+    fn try_from(value: Ipld) -> Result<Self, Self::Error> {
+        Selector::try_from(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipld::{Codec, DagCbor};
+
+    use super::*;
+
+    /// Collect the matched paths of a walk over a single node.
+    fn walk(selector: &Selector, node: &Ipld, path: String, out: &mut Vec<String>) {
+        if selector.decide(node) {
+            out.push(path.clone());
+        }
+        let children: Vec<(String, &Ipld)> = match node {
+            Ipld::Map(m) => m.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            Ipld::List(l) => l.iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+            _ => vec![],
+        };
+        for (segment, child) in children {
+            if let Some(next) = selector.explore(node, &segment) {
+                walk(&next, child, format!("{path}/{segment}"), out);
+            }
+        }
+    }
+
+    #[test]
+    fn test_selector_explore() {
+        let node = Ipld::List(vec![
+            Ipld::Integer(0),
+            Ipld::List(vec![Ipld::Integer(1), Ipld::List(vec![Ipld::Integer(2)])]),
+            Ipld::Integer(3),
+        ]);
+        let mut out = vec![];
+        walk(&Selector::ExploreRange { start: 1, end: 3, next: Box::new(Selector::Matcher) }, &node, String::new(), &mut out);
+        assert_eq!(out, ["/1", "/2"]);
+
+        let recursive = |limit| Selector::explore_recursive(limit, Selector::ExploreUnion(vec![
+            Selector::Matcher,
+            Selector::ExploreAll { next: Box::new(Selector::ExploreRecursiveEdge) },
+        ]));
+        let mut out = vec![];
+        walk(&recursive(RecursionLimit::None), &node, String::new(), &mut out);
+        assert_eq!(out, ["", "/0", "/1", "/1/0", "/1/1", "/1/1/0", "/2"]);
+        let mut out = vec![];
+        walk(&recursive(RecursionLimit::Depth(3)), &node, String::new(), &mut out);
+        assert_eq!(out, ["", "/0", "/1", "/1/0", "/1/1", "/2"]);
+        let mut out = vec![];
+        walk(&recursive(RecursionLimit::Depth(1)), &node, String::new(), &mut out);
+        assert_eq!(out, [""]);
+    }
+
+    #[test]
+    fn test_selector_ipld_roundtrip() {
+        let selector = Selector::explore_recursive(RecursionLimit::Depth(5), Selector::ExploreUnion(vec![
+            Selector::Matcher,
+            Selector::ExploreFields { fields: BTreeMap::from([
+                (String::from("a"), Selector::ExploreIndex { index: 1, next: Box::new(Selector::ExploreRecursiveEdge) }),
+                (String::from("b"), Selector::ExploreRange { start: 0, end: 2, next: Box::new(Selector::Matcher) }),
+                (String::from("c"), Selector::ExploreInterpretAs { adl: String::from("unixfs"), next: Box::new(Selector::ExploreAll { next: Box::new(Selector::Matcher) }) }),
+            ]) },
+        ]));
+        let ipld = Ipld::from(&selector);
+        assert_eq!(Selector::try_from(&ipld).unwrap(), selector);
+
+        let c = DagCbor::default();
+        let bytes = c.encode_to_vec(&ipld).unwrap();
+        assert_eq!(Selector::try_from(c.decode_from_slice::<Ipld>(&bytes).unwrap()).unwrap(), selector);
+        assert!(matches!(Selector::try_from(&Ipld::Null), Err(SelectorError::Invalid(_))));
+    }
+}
@@ -0,0 +1,218 @@
+use std::{collections::BTreeMap, fmt};
+
+use bytes::Bytes;
+use cid::serde::{BytesToCidVisitor, CID_SERDE_PRIVATE_IDENTIFIER};
+use serde::{
+    de::{self, value::{BytesDeserializer, MapDeserializer, SeqDeserializer}, DeserializeSeed, IntoDeserializer, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+
+use crate::ipld::Ipld;
+
+use super::SerdeError;
+
+impl<'de> Deserialize<'de> for Ipld {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(IpldVisitor)
+    }
+}
+
+struct IpldVisitor;
+
+impl<'de> Visitor<'de> for IpldVisitor {
+    type Value = Ipld;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any IPLD data model value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Ipld, E> {
+        Ok(Ipld::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Ipld, E> {
+        Ok(Ipld::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Ipld, D::Error> {
+        Ipld::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Ipld, E> {
+        Ok(Ipld::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Ipld, E> {
+        Ok(Ipld::from(v))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Ipld, E> {
+        Ok(Ipld::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Ipld, E> {
+        Ok(Ipld::from(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Ipld, E> {
+        i128::try_from(v).map(Ipld::from).map_err(|_| E::custom("integer out of range"))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Ipld, E> {
+        Ok(Ipld::from(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Ipld, E> {
+        Ok(Ipld::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Ipld, E> {
+        Ok(Ipld::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Ipld, E> {
+        Ok(Ipld::Bytes(Bytes::copy_from_slice(v)))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Ipld, E> {
+        Ok(Ipld::Bytes(Bytes::from(v)))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Ipld, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or_default().min(1024));
+        while let Some(item) = seq.next_element()? {
+            list.push(item);
+        }
+        Ok(Ipld::List(list))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Ipld, A::Error> {
+        let mut out = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<String, Ipld>()? {
+            out.insert(key, value);
+        }
+        Ok(Ipld::Map(out))
+    }
+
+    /// Deserializers that understand [cid::Cid] hand links over as a newtype around the CID bytes.
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Ipld, D::Error> {
+        deserializer.deserialize_bytes(BytesToCidVisitor).map(Ipld::Link)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for Ipld {
+    type Deserializer = IpldDeserializer;
+
+    fn into_deserializer(self) -> IpldDeserializer {
+        IpldDeserializer(self)
+    }
+}
+
+/// [Deserializer] from owned [Ipld].
+pub struct IpldDeserializer(pub Ipld);
+
+impl<'de> Deserializer<'de> for IpldDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Ipld::Null => visitor.visit_unit(),
+            Ipld::Bool(b) => visitor.visit_bool(b),
+            Ipld::Integer(i) => match (u64::try_from(i), i64::try_from(i)) {
+                (Ok(u), _) => visitor.visit_u64(u),
+                (_, Ok(i)) => visitor.visit_i64(i),
+                _ => visitor.visit_i128(i),
+            },
+            Ipld::Float(f) => visitor.visit_f64(f),
+            Ipld::String(s) => visitor.visit_string(s),
+            Ipld::Bytes(b) => visitor.visit_byte_buf(b.to_vec()),
+            Ipld::List(l) => {
+                let mut seq = SeqDeserializer::new(l.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            },
+            Ipld::Map(m) => {
+                let mut map = MapDeserializer::new(m.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            },
+            Ipld::Link(cid) => visitor.visit_newtype_struct(BytesDeserializer::<SerdeError>::new(&cid.to_bytes())),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Ipld::Null => visitor.visit_none(),
+            ipld => visitor.visit_some(IpldDeserializer(ipld)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Ipld::Link(cid) if name == CID_SERDE_PRIVATE_IDENTIFIER => {
+                visitor.visit_newtype_struct(BytesDeserializer::<SerdeError>::new(&cid.to_bytes()))
+            },
+            ipld if name == CID_SERDE_PRIVATE_IDENTIFIER => {
+                Err(SerdeError::Message(format!("expected link, found {:?}", ipld.kind())))
+            },
+            ipld => visitor.visit_newtype_struct(IpldDeserializer(ipld)),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Ipld::String(variant) => visitor.visit_enum(IntoDeserializer::<SerdeError>::into_deserializer(variant)),
+            Ipld::Map(m) if m.len() == 1 => {
+                let (variant, value) = m.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            },
+            ipld => Err(SerdeError::Message(format!("expected enum, found {:?}", ipld.kind()))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Externally tagged enum, a single entry map from variant name to content.
+struct EnumDeserializer {
+    variant: String,
+    value: Ipld,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = SerdeError;
+    type Variant = IpldDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, IpldDeserializer), SerdeError> {
+        let variant = seed.deserialize(IntoDeserializer::<SerdeError>::into_deserializer(self.variant))?;
+        Ok((variant, IpldDeserializer(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for IpldDeserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.0 {
+            Ipld::Null => Ok(()),
+            ipld => Err(SerdeError::Message(format!("expected unit variant, found {:?}", ipld.kind()))),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_map(visitor)
+    }
+}
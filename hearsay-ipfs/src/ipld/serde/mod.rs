@@ -0,0 +1,132 @@
+//! [serde] bridge between Rust types and the [Ipld] data model.
+//! [Cid]s round-trip as [Ipld::Link] and byte strings (e.g. [bytes::Bytes]) as [Ipld::Bytes].
+//!
+//! [Cid]: cid::Cid
+
+use std::fmt;
+
+use ::serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use super::{Codec, CodecError, Decode, Encode, Ipld};
+
+mod de;
+mod ser;
+
+pub use de::IpldDeserializer;
+pub use ser::IpldSerializer;
+
+#[derive(Debug, Error)]
+pub enum SerdeError {
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error("{0}")]
+    Message(String),
+}
+
+impl ::serde::ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl ::serde::de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+/// Convert a [Serialize] type into [Ipld].
+pub fn to_ipld<T: Serialize + ?Sized>(value: &T) -> Result<Ipld, SerdeError> {
+    value.serialize(IpldSerializer)
+}
+
+/// Convert [Ipld] into a [DeserializeOwned] type.
+pub fn from_ipld<T: DeserializeOwned>(ipld: Ipld) -> Result<T, SerdeError> {
+    T::deserialize(IpldDeserializer(ipld))
+}
+
+/// Encode a [Serialize] type with the [Codec].
+pub fn to_vec<C, T>(c: &C, value: &T) -> Result<Vec<u8>, SerdeError>
+where
+    C: Codec,
+    T: Serialize + ?Sized,
+    Ipld: Encode<C>,
+{
+    Ok(c.encode_to_vec(&to_ipld(value)?)?)
+}
+
+/// Decode a [DeserializeOwned] type with the [Codec].
+pub fn from_slice<C, T>(c: &C, bytes: &[u8]) -> Result<T, SerdeError>
+where
+    C: Codec,
+    T: DeserializeOwned,
+    Ipld: Decode<C>,
+{
+    from_ipld(c.decode_from_slice::<Ipld>(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ::serde::Deserialize;
+    use bytes::Bytes;
+    use cid::Cid;
+
+    use crate::ipld::DagCbor;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        File,
+        Dir { entries: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Node {
+        name: String,
+        size: Option<u64>,
+        data: Bytes,
+        link: Cid,
+        tags: Vec<String>,
+        kind: Kind,
+        meta: Ipld,
+    }
+
+    fn node() -> Node {
+        Node {
+            name: String::from("banana"),
+            size: None,
+            data: Bytes::from_static(b"\x00\x01"),
+            link: Cid::default(),
+            tags: vec![String::from("yellow")],
+            kind: Kind::Dir { entries: 2 },
+            meta: Ipld::List(vec![Ipld::Link(Cid::default()), Ipld::Float(0.5)]),
+        }
+    }
+
+    #[test]
+    fn test_serde_to_ipld() {
+        let ipld = to_ipld(&node()).unwrap();
+        let Ipld::Map(map) = &ipld else { panic!("expected map") };
+        assert_eq!(map["name"], Ipld::String(String::from("banana")));
+        assert_eq!(map["size"], Ipld::Null);
+        assert_eq!(map["data"], Ipld::Bytes(Bytes::from_static(b"\x00\x01")));
+        assert_eq!(map["link"], Ipld::Link(Cid::default()));
+        assert_eq!(map["kind"], Ipld::Map(BTreeMap::from([
+            (String::from("Dir"), Ipld::Map(BTreeMap::from([(String::from("entries"), Ipld::Integer(2))]))),
+        ])));
+        assert_eq!(to_ipld(&Kind::File).unwrap(), Ipld::String(String::from("File")));
+        assert_eq!(from_ipld::<Node>(ipld).unwrap(), node());
+    }
+
+    #[test]
+    fn test_serde_codec_roundtrip() {
+        let c = DagCbor::default();
+        let bytes = to_vec(&c, &node()).unwrap();
+        assert_eq!(from_slice::<_, Node>(&c, &bytes).unwrap(), node());
+        assert!(from_slice::<_, Kind>(&c, &bytes).is_err());
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use cid::{serde::CID_SERDE_PRIVATE_IDENTIFIER, Cid};
+use serde::{ser::{self, Impossible}, Serialize, Serializer};
+
+use crate::ipld::Ipld;
+
+use super::SerdeError;
+
+impl Serialize for Ipld {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_none(),
+            Self::Bool(b) => serializer.serialize_bool(*b),
+            Self::Integer(i) => serializer.serialize_i128(*i),
+            Self::Float(f) => serializer.serialize_f64(*f),
+            Self::String(s) => serializer.serialize_str(s),
+            Self::Bytes(b) => serializer.serialize_bytes(b),
+            Self::List(l) => serializer.collect_seq(l),
+            Self::Map(m) => serializer.collect_map(m),
+            Self::Link(cid) => cid.serialize(serializer),
+        }
+    }
+}
+
+/// [Serializer] into [Ipld].
+pub struct IpldSerializer;
+
+impl Serializer for IpldSerializer {
+    type Ok = Ipld;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Ipld, SerdeError> {
+        let v = i128::try_from(v).map_err(|_| SerdeError::Codec(crate::ipld::CodecError::NumberOutOfBounds))?;
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::from(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::Bytes(Bytes::copy_from_slice(v)))
+    }
+
+    fn serialize_none(self) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Ipld, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<Ipld, SerdeError> {
+        let ipld = value.serialize(self)?;
+        if name != CID_SERDE_PRIVATE_IDENTIFIER {
+            return Ok(ipld);
+        }
+        match ipld {
+            Ipld::Bytes(b) => Cid::try_from(&b[..])
+                .map(Ipld::Link)
+                .map_err(|e| SerdeError::Message(e.to_string())),
+            _ => Err(SerdeError::Message(String::from("CID must serialize to bytes"))),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Ipld, SerdeError> {
+        Ok(Ipld::Map(BTreeMap::from([(variant.to_owned(), value.serialize(self)?)])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, SerdeError> {
+        Ok(SerializeList {
+            variant: None,
+            list: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SerializeList, SerdeError> {
+        Ok(SerializeList {
+            variant: Some(variant),
+            list: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, SerdeError> {
+        Ok(SerializeMap {
+            variant: None,
+            map: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<SerializeMap, SerdeError> {
+        Ok(SerializeMap {
+            variant: Some(variant),
+            map: BTreeMap::new(),
+            key: None,
+        })
+    }
+}
+
+/// Wrap enum variant content in a single entry map, the externally tagged representation.
+fn wrap_variant(variant: Option<&'static str>, ipld: Ipld) -> Ipld {
+    match variant {
+        Some(variant) => Ipld::Map(BTreeMap::from([(variant.to_owned(), ipld)])),
+        None => ipld,
+    }
+}
+
+pub struct SerializeList {
+    variant: Option<&'static str>,
+    list: Vec<Ipld>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.list.push(value.serialize(IpldSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        Ok(wrap_variant(self.variant, Ipld::List(self.list)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeMap {
+    variant: Option<&'static str>,
+    map: BTreeMap<String, Ipld>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().ok_or_else(|| SerdeError::Message(String::from("map value without a key")))?;
+        self.map.insert(key, value.serialize(IpldSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        Ok(wrap_variant(self.variant, Ipld::Map(self.map)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.map.insert(key.to_owned(), value.serialize(IpldSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Ipld;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Ipld, SerdeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Map keys must be strings in the IPLD data model.
+struct KeySerializer;
+
+fn key_error() -> SerdeError {
+    SerdeError::Message(String::from("map keys must be strings"))
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<String, SerdeError>;
+    type SerializeTuple = Impossible<String, SerdeError>;
+    type SerializeTupleStruct = Impossible<String, SerdeError>;
+    type SerializeTupleVariant = Impossible<String, SerdeError>;
+    type SerializeMap = Impossible<String, SerdeError>;
+    type SerializeStruct = Impossible<String, SerdeError>;
+    type SerializeStructVariant = Impossible<String, SerdeError>;
+
+    fn serialize_str(self, v: &str) -> Result<String, SerdeError> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, SerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<String, SerdeError> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<String, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_i8(self, _v: i8) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_i16(self, _v: i16) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_i32(self, _v: i32) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_i64(self, _v: i64) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_u8(self, _v: u8) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_u16(self, _v: u16) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_u32(self, _v: u32) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_u64(self, _v: u64) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_f32(self, _v: f32) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_f64(self, _v: f64) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_none(self) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_unit(self) -> Result<String, SerdeError> { Err(key_error()) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerdeError> { Err(key_error()) }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<String, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(key_error())
+    }
+}
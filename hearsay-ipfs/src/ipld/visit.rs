@@ -0,0 +1,263 @@
+//! Streaming decoding and encoding as a sequence of data model events, without building an [Ipld] tree.
+
+use std::{collections::BTreeMap, io::{Read, Write}};
+
+use cid::Cid;
+
+use super::{Codec, CodecError, Ipld};
+
+/// Receives the data model events of a value, in order.
+///
+/// Lists are a `visit_list_start`, their items and a `visit_list_end`. Maps are a `visit_map_start`,
+/// a `visit_key` before each value and a `visit_map_end`. Keys may arrive in any order. The length
+/// given at the start of a list or map is [None] when the format does not know it upfront.
+pub trait Visitor {
+    fn visit_null(&mut self) -> Result<(), CodecError>;
+    fn visit_bool(&mut self, value: bool) -> Result<(), CodecError>;
+    fn visit_integer(&mut self, value: i128) -> Result<(), CodecError>;
+    fn visit_float(&mut self, value: f64) -> Result<(), CodecError>;
+    fn visit_string(&mut self, value: &str) -> Result<(), CodecError>;
+    fn visit_bytes(&mut self, value: &[u8]) -> Result<(), CodecError>;
+    fn visit_link(&mut self, cid: &Cid) -> Result<(), CodecError>;
+    fn visit_list_start(&mut self, len: Option<usize>) -> Result<(), CodecError>;
+    fn visit_list_end(&mut self) -> Result<(), CodecError>;
+    fn visit_map_start(&mut self, len: Option<usize>) -> Result<(), CodecError>;
+    fn visit_key(&mut self, key: &str) -> Result<(), CodecError>;
+    fn visit_map_end(&mut self) -> Result<(), CodecError>;
+}
+
+/// A [Codec] whose decoder drives a [Visitor], and whose encoder is one.
+pub trait StreamCodec: Codec {
+    type Encoder<'a, W: Write + 'a>: Visitor;
+
+    /// Decode a single value as events.
+    fn decode_visit<R: Read, V: Visitor + ?Sized>(&self, r: &mut R, v: &mut V) -> Result<(), CodecError>;
+
+    /// [Visitor] that encodes the events of a single value.
+    fn encoder<'a, W: Write + 'a>(&self, w: &'a mut W) -> Self::Encoder<'a, W>;
+}
+
+/// Decode a value with one codec and encode it with another, without building an [Ipld] tree.
+pub fn transcode<A, B, R, W>(from: &A, r: &mut R, to: &B, w: &mut W) -> Result<(), CodecError>
+where
+    A: StreamCodec,
+    B: StreamCodec,
+    R: Read,
+    W: Write,
+{
+    from.decode_visit(r, &mut to.encoder(w))
+}
+
+impl Ipld {
+    /// Send the events of this value to the [Visitor].
+    pub fn visit<V: Visitor + ?Sized>(&self, v: &mut V) -> Result<(), CodecError> {
+        match self {
+            Self::Null => v.visit_null(),
+            Self::Bool(b) => v.visit_bool(*b),
+            Self::Integer(i) => v.visit_integer(*i),
+            Self::Float(f) => v.visit_float(*f),
+            Self::String(s) => v.visit_string(s),
+            Self::Bytes(b) => v.visit_bytes(b),
+            Self::Link(cid) => v.visit_link(cid),
+            Self::List(l) => {
+                v.visit_list_start(Some(l.len()))?;
+                for item in l {
+                    item.visit(v)?;
+                }
+                v.visit_list_end()
+            },
+            Self::Map(m) => {
+                v.visit_map_start(Some(m.len()))?;
+                for (key, value) in m {
+                    v.visit_key(key)?;
+                    value.visit(v)?;
+                }
+                v.visit_map_end()
+            },
+        }
+    }
+}
+
+/// [Visitor] that builds the [Ipld] tree of the events.
+#[derive(Debug, Default)]
+pub struct IpldBuilder {
+    stack: Vec<Partial>,
+    done: Option<Ipld>,
+}
+
+#[derive(Debug)]
+enum Partial {
+    List(Vec<Ipld>),
+    Map(BTreeMap<String, Ipld>, Option<String>),
+}
+
+impl IpldBuilder {
+    /// The finished value, once every list and map has ended.
+    pub fn finish(self) -> Result<Ipld, CodecError> {
+        match (self.stack.is_empty(), self.done) {
+            (true, Some(value)) => Ok(value),
+            _ => Err(CodecError::MalformedData("incomplete value")),
+        }
+    }
+
+    fn push(&mut self, value: Ipld) -> Result<(), CodecError> {
+        match self.stack.last_mut() {
+            None if self.done.is_some() => return Err(CodecError::MalformedData("more than one value")),
+            None => self.done = Some(value),
+            Some(Partial::List(l)) => l.push(value),
+            Some(Partial::Map(m, key)) => {
+                let key = key.take().ok_or(CodecError::MalformedData("map value without a key"))?;
+                if m.insert(key, value).is_some() {
+                    return Err(CodecError::MalformedData("duplicate map keys"));
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Visitor for IpldBuilder {
+    fn visit_null(&mut self) -> Result<(), CodecError> {
+        self.push(Ipld::Null)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), CodecError> {
+        self.push(Ipld::Bool(value))
+    }
+
+    fn visit_integer(&mut self, value: i128) -> Result<(), CodecError> {
+        self.push(Ipld::Integer(value))
+    }
+
+    fn visit_float(&mut self, value: f64) -> Result<(), CodecError> {
+        self.push(Ipld::Float(value))
+    }
+
+    fn visit_string(&mut self, value: &str) -> Result<(), CodecError> {
+        self.push(Ipld::String(value.to_owned()))
+    }
+
+    fn visit_bytes(&mut self, value: &[u8]) -> Result<(), CodecError> {
+        self.push(Ipld::Bytes(value.to_vec().into()))
+    }
+
+    fn visit_link(&mut self, cid: &Cid) -> Result<(), CodecError> {
+        self.push(Ipld::Link(*cid))
+    }
+
+    fn visit_list_start(&mut self, len: Option<usize>) -> Result<(), CodecError> {
+        self.stack.push(Partial::List(Vec::with_capacity(len.unwrap_or(0).min(1024))));
+        Ok(())
+    }
+
+    fn visit_list_end(&mut self) -> Result<(), CodecError> {
+        match self.stack.pop() {
+            Some(Partial::List(l)) => self.push(Ipld::List(l)),
+            _ => Err(CodecError::MalformedData("unexpected end of list")),
+        }
+    }
+
+    fn visit_map_start(&mut self, _len: Option<usize>) -> Result<(), CodecError> {
+        self.stack.push(Partial::Map(BTreeMap::new(), None));
+        Ok(())
+    }
+
+    fn visit_key(&mut self, key: &str) -> Result<(), CodecError> {
+        match self.stack.last_mut() {
+            Some(Partial::Map(_, k @ None)) => {
+                *k = Some(key.to_owned());
+                Ok(())
+            },
+            _ => Err(CodecError::MalformedData("unexpected map key")),
+        }
+    }
+
+    fn visit_map_end(&mut self) -> Result<(), CodecError> {
+        match self.stack.pop() {
+            Some(Partial::Map(m, None)) => self.push(Ipld::Map(m)),
+            _ => Err(CodecError::MalformedData("unexpected end of map")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{ipld, ipld::DagCbor};
+
+    use super::*;
+
+    #[test]
+    fn test_visit_build() {
+        let value = ipld!({ "b": [1, -1, 0.5, null, true], "aa": { "x": b"bytes", "link": Cid::default() }, "": "" });
+        let mut builder = IpldBuilder::default();
+        value.visit(&mut builder).unwrap();
+        assert_eq!(builder.finish().unwrap(), value);
+
+        let mut builder = IpldBuilder::default();
+        builder.visit_list_start(Some(1)).unwrap();
+        assert!(builder.visit_map_end().is_err());
+        assert!(IpldBuilder::default().finish().is_err());
+    }
+
+    #[test]
+    fn test_visit_transcode() {
+        let c = DagCbor::default();
+        let value = ipld!({ "b": [1, { "zz": 1, "a": [] }], "aa": { "x": b"bytes", "link": Cid::default() } });
+        let mut streamed = vec![];
+        value.visit(&mut c.encoder(&mut streamed)).unwrap();
+        let encoded = c.encode_to_vec(&value).unwrap();
+        assert_eq!(streamed, encoded);
+
+        let mut transcoded = vec![];
+        transcode(&c, &mut Cursor::new(&encoded), &c, &mut transcoded).unwrap();
+        assert_eq!(transcoded, encoded);
+
+        let mut builder = IpldBuilder::default();
+        c.decode_visit(&mut encoded.as_slice(), &mut builder).unwrap();
+        assert_eq!(builder.finish().unwrap(), value);
+    }
+
+    #[test]
+    fn test_visit_encoder_lengths() {
+        let c = DagCbor::default();
+        let mut out = vec![];
+        let mut e = c.encoder(&mut out);
+        e.visit_list_start(Some(1)).unwrap();
+        e.visit_null().unwrap();
+        assert!(e.visit_null().is_err());
+
+        let mut e = c.encoder(&mut out);
+        e.visit_list_start(Some(2)).unwrap();
+        e.visit_null().unwrap();
+        assert!(e.visit_list_end().is_err());
+
+        let mut e = c.encoder(&mut out);
+        e.visit_map_start(Some(1)).unwrap();
+        e.visit_key("a").unwrap();
+        e.visit_null().unwrap();
+        assert!(e.visit_key("b").is_err());
+
+        let mut e = c.encoder(&mut out);
+        e.visit_map_start(Some(2)).unwrap();
+        e.visit_key("a").unwrap();
+        e.visit_null().unwrap();
+        assert!(e.visit_map_end().is_err());
+
+        // A key needs a value before the next key or the end of the map, and a value needs a key.
+        let mut e = c.encoder(&mut out);
+        e.visit_map_start(None).unwrap();
+        e.visit_key("a").unwrap();
+        assert!(e.visit_key("b").is_err());
+        let mut e = c.encoder(&mut out);
+        e.visit_map_start(None).unwrap();
+        e.visit_key("a").unwrap();
+        assert!(e.visit_map_end().is_err());
+        let mut e = c.encoder(&mut out);
+        e.visit_map_start(None).unwrap();
+        e.visit_key("a").unwrap();
+        e.visit_null().unwrap();
+        assert!(e.visit_null().is_err());
+    }
+}
@@ -1,15 +1,19 @@
+// Lets `#[derive(DagCbor)]` name this crate from inside it.
+extern crate self as hearsay_ipfs;
+
 mod block;
+pub mod car;
 mod config;
-mod ipld;
+mod hasher;
+pub mod ipld;
 mod ipns;
 mod p2p;
 mod path;
-mod repo;
+pub mod repo;
 mod task;
 mod unixfs;
 
 use p2p::IpfsBehaviour;
-use repo::Repository;
 use task::IpfsTask;
 #[cfg(not(target_arch = "wasm32"))]
 pub(crate) use tokio::task::spawn;
@@ -17,7 +21,9 @@ use tokio_util::sync::CancellationToken;
 #[cfg(target_arch = "wasm32")]
 pub(crate) use wasm_bindgen_futures::spawn_local as spawn;
 
-pub use block::Block;
+pub use block::{Block, BlockError, BlockLimits, CidPrefix};
+pub use hasher::{HasherRegistry, MultihashHasher};
+pub use repo::{RepoError, RepoLimits, Repository, RepositoryBuilder};
 use libp2p::{futures::{channel::{mpsc, oneshot}, SinkExt}, identity::Keypair, swarm::{dial_opts::DialOpts, NetworkBehaviour}, PeerId, StreamProtocol, Swarm};
 
 /// IPFS node, built from [config::IpfsConfig].
@@ -59,5 +65,8 @@ async fn test_ipfs_start() {
         bootstrap: vec![],
         kad_config: libp2p::kad::Config::new(StreamProtocol::new("/test")),
         custom: None,
+        limits: Default::default(),
+        hashers: Default::default(),
+        codecs: Default::default(),
     }.start().await.unwrap();
 }
@@ -0,0 +1,180 @@
+use std::{io::SeekFrom, path::Path};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use cid::Cid;
+use tokio::{fs::File, io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt}, sync::Mutex};
+
+use crate::{car::{read_section, read_varint, CarError, CarIndex, CarV2Header, IndexCodec, PRAGMA}, repo::RepoError, Block};
+
+use super::BlockStore;
+
+/// Read-only [BlockStore] over a CARv2, looking blocks up through its index without importing them.
+/// Only the index is held in memory.
+///
+/// ```no_run
+/// # async fn mount() -> Result<(), hearsay_ipfs::RepoError> {
+/// use hearsay_ipfs::{repo::{blockstore::CarBlockStore, keystore::MemKeyStore, pinstore::MemPinStore}, Repository};
+///
+/// let store = CarBlockStore::open("archive.car").await?;
+/// let repo = Repository::new(store, MemPinStore::new(), MemKeyStore::new());
+/// # Ok(())
+/// # }
+/// ```
+pub struct CarBlockStore<R = File> {
+    r: Mutex<R>,
+    header: CarV2Header,
+    index: CarIndex,
+}
+
+impl CarBlockStore {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, RepoError> {
+        Self::new(File::open(path).await?).await
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Send + Unpin> CarBlockStore<R> {
+    /// Read the CARv2 header and index. A CARv2 without an index is rejected.
+    pub async fn new(mut r: R) -> Result<Self, RepoError> {
+        let mut pragma = [0u8; PRAGMA.len()];
+        r.read_exact(&mut pragma).await?;
+        if pragma != PRAGMA {
+            return Err(CarError::Malformed("not a CARv2").into());
+        }
+        let mut bytes = [0u8; CarV2Header::LEN];
+        r.read_exact(&mut bytes).await?;
+        let header = CarV2Header::from_bytes(&bytes);
+        if header.index_offset == 0 {
+            return Err(CarError::Malformed("CARv2 has no index").into());
+        }
+        r.seek(SeekFrom::Start(header.index_offset)).await?;
+        let code = read_varint(&mut r).await?.ok_or(CarError::Malformed("missing index codec"))?;
+        let codec = IndexCodec::from_code(code)?;
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).await?;
+        let index = CarIndex::from_bytes(codec, &buf)?;
+        Ok(Self { r: Mutex::new(r), header, index })
+    }
+
+    pub fn header(&self) -> &CarV2Header {
+        &self.header
+    }
+
+    /// Read the section at an offset in the data payload.
+    async fn read_section(&self, r: &mut R, offset: u64) -> Result<(Cid, Bytes), RepoError> {
+        let remaining = self.header.data_size.checked_sub(offset).ok_or(CarError::Malformed("index offset is outside the data payload"))?;
+        r.seek(SeekFrom::Start(self.header.data_offset + offset)).await?;
+        let buf = read_section(r, remaining).await?.ok_or(CarError::Malformed("index offset is outside the data payload"))?;
+        let mut rest = &buf[..];
+        let cid = Cid::read_bytes(&mut rest).map_err(|e| CarError::Block(e.into()))?;
+        let offset = buf.len() - rest.len();
+        Ok((cid, Bytes::from(buf).slice(offset..)))
+    }
+}
+
+#[async_trait]
+impl<R: AsyncRead + AsyncSeek + Send + Unpin> BlockStore for CarBlockStore<R> {
+    async fn contains(&self, cid: &Cid) -> Result<bool, RepoError> {
+        Ok(self.index.get(cid.hash()).is_some())
+    }
+
+    /// The block is returned under the requested [Cid], as sections are indexed by multihash alone.
+    /// Only the digest of the section is checked, not its data.
+    async fn get(&self, cid: &Cid) -> Result<Block, RepoError> {
+        let offset = self.index.get(cid.hash()).ok_or(RepoError::NotFound)?;
+        let (found, data) = self.read_section(&mut *self.r.lock().await, offset).await?;
+        if found.hash().digest() != cid.hash().digest() {
+            return Err(CarError::Malformed("index points at the wrong section").into());
+        }
+        Ok(Block::new_unchecked(*cid, data))
+    }
+
+    async fn get_many(&self, cids: &[&Cid]) -> Result<Vec<Block>, RepoError> {
+        let mut blocks = vec![];
+        for cid in cids {
+            blocks.push(self.get(cid).await?);
+        }
+        Ok(blocks)
+    }
+
+    async fn put(&self, _block: Block) -> Result<(), RepoError> {
+        Err(RepoError::ReadOnly)
+    }
+
+    async fn remove(&self, _cid: &Cid) -> Result<(), RepoError> {
+        Err(RepoError::ReadOnly)
+    }
+
+    async fn remove_many(&self, _cids: &[&Cid]) -> Result<(), RepoError> {
+        Err(RepoError::ReadOnly)
+    }
+
+    /// Scans the whole data payload.
+    async fn list(&self) -> Result<Vec<Cid>, RepoError> {
+        let r = &mut *self.r.lock().await;
+        r.seek(SeekFrom::Start(self.header.data_offset)).await?;
+        let mut payload = r.take(self.header.data_size);
+        // skip the CARv1 header
+        read_section(&mut payload, self.header.data_size).await?;
+        let mut cids = vec![];
+        while let Some(buf) = read_section(&mut payload, self.header.data_size).await? {
+            cids.push(Cid::read_bytes(&buf[..]).map_err(|e| CarError::Block(e.into()))?);
+        }
+        Ok(cids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use multihash_codetable::Code;
+
+    use crate::{car::{CarHeader, CarReader, CarV2Writer}, ipld::{DagCbor, Ipld}, CidPrefix};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_car_block_store() {
+        let blocks = (0..100)
+            .map(|i| Block::encode(&DagCbor::default(), Code::Sha2_256, &Ipld::Integer(i)).unwrap())
+            .chain([
+                Block::from_data(&CidPrefix::v1(0x55, Code::Sha2_512), Bytes::from_static(b"banana")).unwrap(),
+                Block::from_data(&CidPrefix::identity(0x55), Bytes::from_static(b"inline")).unwrap(),
+            ])
+            .collect::<Vec<_>>();
+        let missing = Block::encode(&DagCbor::default(), Code::Sha2_256, &Ipld::Integer(100)).unwrap();
+        for codec in [IndexCodec::IndexSorted, IndexCodec::MultihashIndexSorted] {
+            let mut car = CarV2Writer::new(&CarHeader::v1(vec![*blocks[0].cid()]), codec, Cursor::new(vec![])).await.unwrap();
+            for block in &blocks {
+                car.write(block).await.unwrap();
+            }
+            let car = car.finish().await.unwrap().into_inner();
+
+            let store = CarBlockStore::new(Cursor::new(car.clone())).await.unwrap();
+            assert!(store.header().fully_indexed);
+            for block in blocks.iter().rev() {
+                assert!(store.contains(block.cid()).await.unwrap());
+                let found = store.get(block.cid()).await.unwrap();
+                assert!(found.verify());
+                assert_eq!(found.inner(), block.inner());
+            }
+            // same multihash under another codec
+            let raw = Cid::new_v1(0x55, *blocks[1].cid().hash());
+            assert_eq!(store.get(&raw).await.unwrap().inner(), blocks[1].inner());
+            assert!(!store.contains(missing.cid()).await.unwrap());
+            assert!(matches!(store.get(missing.cid()).await, Err(RepoError::NotFound)));
+            assert!(matches!(store.put(missing.clone()).await, Err(RepoError::ReadOnly)));
+            assert_eq!(store.list().await.unwrap(), blocks.iter().map(|b| *b.cid()).collect::<Vec<_>>());
+
+            let mut reader = CarReader::new(car.as_slice()).await.unwrap();
+            assert_eq!(reader.roots(), [*blocks[0].cid()]);
+            assert_eq!(reader.v2_header(), Some(store.header()));
+            let mut read = vec![];
+            while let Some(block) = reader.next_block().await.unwrap() {
+                read.push(*block.cid());
+            }
+            assert_eq!(read, blocks.iter().map(|b| *b.cid()).collect::<Vec<_>>());
+        }
+    }
+}
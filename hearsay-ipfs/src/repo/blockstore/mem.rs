@@ -9,6 +9,7 @@ use crate::{repo::RepoError, Block};
 
 use super::BlockStore;
 
+#[derive(Default)]
 pub struct MemBlockStore {
     inner: RwLock<HashMap<Cid, Bytes>>,
 }
@@ -30,8 +31,7 @@ impl BlockStore for MemBlockStore {
     async fn get(&self, cid: &Cid) -> Result<Block, RepoError> {
         let inner = &*self.inner.read().await;
         if let Some(data) = inner.get(cid) {
-            let block = Block::new(*cid, data.clone()).map_err(|_| RepoError::IncorrectCid)?;
-            return Ok(block);
+            return Ok(Block::new_unchecked(*cid, data.clone()));
         }
         Err(RepoError::NotFound)
     }
@@ -41,8 +41,7 @@ impl BlockStore for MemBlockStore {
         let mut blocks = vec![];
         for cid in cids {
             if let Some(data) = inner.get(*cid) {
-                let block = Block::new(**cid, data.clone()).map_err(|_| RepoError::IncorrectCid)?;
-                blocks.push(block);
+                blocks.push(Block::new_unchecked(**cid, data.clone()));
             } else {
                 return Err(RepoError::NotFound);
             }
@@ -5,13 +5,17 @@ use crate::Block;
 
 use super::RepoError;
 
+mod car;
 mod mem;
+pub use car::CarBlockStore;
+pub use mem::MemBlockStore;
 
 #[async_trait]
 pub trait BlockStore: Send + Sync {
 
     async fn contains(&self, cid: &Cid) -> Result<bool, RepoError>;
 
+    /// The [Block] is not verified against its [Cid], see [Repository::get_block](super::Repository::get_block).
     async fn get(&self, cid: &Cid) -> Result<Block, RepoError>;
 
     async fn get_many(&self, cids: &[&Cid]) -> Result<Vec<Block>, RepoError>;
@@ -0,0 +1,157 @@
+//! Import and export of [CARs](crate::car).
+
+use std::collections::HashSet;
+
+use cid::Cid;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{block::is_inline, car::{CarHeader, CarReader, CarWriter}};
+
+use super::{pinstore::PinMode, walk::{WalkOptions, Walker}, RepoError, Repository};
+
+/// How [Repository::import_car] pins the roots named in the CAR header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PinRoots {
+    /// Pin each root recursively, and the blocks under it indirectly.
+    /// Fails with [RepoError::NotFound] if part of a root's DAG is neither in the CAR nor already stored.
+    #[default]
+    Recursive,
+    /// Pin only the root blocks.
+    Direct,
+    /// Leave the imported blocks unpinned.
+    None,
+}
+
+/// Outcome of [Repository::import_car].
+#[derive(Debug, Default)]
+pub struct Imported {
+    /// Roots named in the CAR header.
+    pub roots: Vec<Cid>,
+    /// Blocks stored, in the order they were read.
+    pub blocks: Vec<Cid>,
+}
+
+impl Repository {
+    /// Store every block of a CARv1. Blocks are checked against their [Cid]s and the [BlockLimits](crate::BlockLimits)
+    /// as they are read, and roots are pinned once all blocks are stored.
+    pub async fn import_car<R: AsyncRead + Unpin>(&self, r: R, pin_roots: PinRoots) -> Result<Imported, RepoError> {
+        let mut car = CarReader::new_with(self.hashers().clone(), self.limits().block, r).await?;
+        let mut imported = Imported { roots: car.roots().to_vec(), blocks: vec![] };
+        while let Some(block) = car.next_block().await? {
+            imported.blocks.push(*block.cid());
+            self.store_block(block).await?;
+        }
+        for root in &imported.roots {
+            match pin_roots {
+                PinRoots::Recursive => {
+                    let options = WalkOptions { unique: true, ..Default::default() };
+                    for r in self.walk(root, &options).await?.refs.iter().skip(1) {
+                        self.inner.pin_store.pin(&r.cid, PinMode::Indirect(*root)).await?;
+                    }
+                    self.inner.pin_store.pin(root, PinMode::Recursive).await?;
+                },
+                PinRoots::Direct => self.inner.pin_store.pin(root, PinMode::Direct).await?,
+                PinRoots::None => {},
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Write the DAGs under the roots as a CARv1, with blocks in depth-first order from each root in turn.
+    /// Blocks are written as the walk loads them, each once, and inline blocks are left out as their data is in
+    /// their [Cid]s.
+    pub async fn export_car<W: AsyncWrite + Unpin>(&self, roots: &[Cid], w: W) -> Result<W, RepoError> {
+        let mut car = CarWriter::new(&CarHeader::v1(roots.to_vec()), w).await?;
+        let mut written = HashSet::new();
+        let options = WalkOptions { unique: true, ..Default::default() };
+        for root in roots {
+            let mut walker = Walker::new(self, root, &options);
+            while let Some((r, block)) = walker.next().await? {
+                let Some(block) = block else {
+                    continue;
+                };
+                if is_inline(&r.cid) || !written.insert(r.cid) {
+                    continue;
+                }
+                car.write(&block).await?;
+            }
+        }
+        Ok(car.finish().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use multihash_codetable::Code;
+
+    use crate::{car::CarError, ipld, ipld::{Codec, DagCbor, Ipld}, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::MemPinStore}, Block, CidPrefix};
+
+    use super::*;
+
+    fn mem_repo() -> Repository {
+        Repository::new(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new())
+    }
+
+    #[tokio::test]
+    async fn test_repo_car_roundtrip() {
+        let repo = mem_repo();
+        let c = DagCbor::default();
+        let leaf = Block::encode(&c, Code::Sha2_256, &Ipld::String(String::from("banana"))).unwrap();
+        let inline = Block::from_data(&CidPrefix::identity(0x55), Bytes::from_static(b"inline")).unwrap();
+        let mid = Block::encode(&c, Code::Sha2_256, &ipld!([*leaf.cid(), *inline.cid()])).unwrap();
+        let root = Block::encode(&c, Code::Sha2_256, &ipld!({ "a": *mid.cid(), "b": *leaf.cid() })).unwrap();
+        for block in [&leaf, &mid, &root] {
+            repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        }
+
+        let car = repo.export_car(&[*root.cid()], vec![]).await.unwrap();
+        let mut reader = CarReader::new(car.as_slice()).await.unwrap();
+        assert_eq!(reader.roots(), [*root.cid()]);
+        let mut order = vec![];
+        while let Some(block) = reader.next_block().await.unwrap() {
+            order.push(*block.cid());
+        }
+        assert_eq!(order, [*root.cid(), *mid.cid(), *leaf.cid()]);
+        assert_eq!(repo.export_car(&[*root.cid()], vec![]).await.unwrap(), car);
+
+        let other = mem_repo();
+        let imported = other.import_car(car.as_slice(), PinRoots::Recursive).await.unwrap();
+        assert_eq!(imported.roots, [*root.cid()]);
+        assert_eq!(imported.blocks, order);
+        assert_eq!(other.get_block(leaf.cid()).await.unwrap().inner(), leaf.inner());
+        assert_eq!(other.export_car(&[*root.cid()], vec![]).await.unwrap(), car);
+    }
+
+    #[tokio::test]
+    async fn test_repo_car_invalid() {
+        let repo = mem_repo();
+        let block = Block::encode(&DagCbor::default(), Code::Sha2_256, &Ipld::Integer(1)).unwrap();
+        let mut writer = CarWriter::new(&CarHeader::v1(vec![*block.cid()]), vec![]).await.unwrap();
+        writer.write(&block).await.unwrap();
+        let mut car = writer.finish().await.unwrap();
+        assert!(repo.import_car(car.as_slice(), PinRoots::Direct).await.is_ok());
+
+        // corrupt the block data
+        *car.last_mut().unwrap() ^= 1;
+        assert!(matches!(repo.import_car(car.as_slice(), PinRoots::Direct).await, Err(RepoError::Car(CarError::Block(_)))));
+        // truncated
+        assert!(repo.import_car(&car[..car.len() - 1], PinRoots::Direct).await.is_err());
+        let header = DagCbor::default().encode_to_vec(&ipld!({ "roots": [], "version": 2 })).unwrap();
+        let car = [&[header.len() as u8][..], &header].concat();
+        assert!(matches!(CarReader::new(car.as_slice()).await, Err(CarError::UnsupportedVersion(2))));
+        // a header without roots, as `{"version": 2}` is taken for a CARv2
+        let header = DagCbor::default().encode_to_vec(&ipld!({ "version": 3 })).unwrap();
+        let car = [&[header.len() as u8][..], &header].concat();
+        assert!(matches!(CarReader::new(car.as_slice()).await, Err(CarError::UnsupportedVersion(3))));
+
+        // a partial CAR, missing the block its root links to
+        let root = Block::encode(&DagCbor::default(), Code::Sha2_256, &ipld!([*block.cid()])).unwrap();
+        let mut writer = CarWriter::new(&CarHeader::v1(vec![*root.cid()]), vec![]).await.unwrap();
+        writer.write(&root).await.unwrap();
+        let car = writer.finish().await.unwrap();
+        let repo = mem_repo();
+        assert!(matches!(repo.import_car(car.as_slice(), PinRoots::Recursive).await, Err(RepoError::NotFound)));
+        assert!(repo.import_car(car.as_slice(), PinRoots::Direct).await.is_ok());
+    }
+}
@@ -0,0 +1,109 @@
+//! Import of git objects, addressed by their [GitRaw] [Cid]s.
+
+use std::{io::Read, path::{Path, PathBuf}};
+
+use cid::Cid;
+use flate2::read::ZlibDecoder;
+
+use crate::{ipld::{git_cid, GitRaw}, Block};
+
+use super::{RepoError, Repository};
+
+impl Repository {
+    /// Store the loose objects under a `.git/objects` directory, unpinned, returning their [Cid]s in path order.
+    /// Packed objects are not read, so run `git unpack-objects` on any packs first.
+    /// The import is not atomic: objects stored before an error are left in the repository.
+    pub async fn import_git_objects(&self, objects: impl AsRef<Path>) -> Result<Vec<Cid>, RepoError> {
+        let mut cids = vec![];
+        for (dir, prefix) in hex_entries(objects.as_ref(), 2).await? {
+            for (path, rest) in hex_entries(&dir, 38).await? {
+                let hex = format!("{prefix}{rest}");
+                let mut digest = [0u8; 20];
+                for (byte, i) in digest.iter_mut().zip((0..40).step_by(2)) {
+                    *byte = u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+                }
+                let cid = git_cid(&digest);
+
+                let compressed = tokio::fs::read(&path).await?;
+                // Stop inflating past the block limit rather than trusting the object.
+                let mut data = vec![];
+                ZlibDecoder::new(&compressed[..]).take(self.limits().block.hard as u64 + 1).read_to_end(&mut data)?;
+                self.limits().block.check(data.len())?;
+                let block = Block::from_data_with(self.hashers(), &GitRaw::PREFIX, data)?;
+                if *block.cid() != cid {
+                    return Err(RepoError::IncorrectCid);
+                }
+                self.store_block(block).await?;
+                cids.push(cid);
+            }
+        }
+        Ok(cids)
+    }
+}
+
+/// Entries of a directory named by `len` lowercase hex digits, sorted by name, skipping anything else.
+async fn hex_entries(dir: &Path, len: usize) -> Result<Vec<(PathBuf, String)>, RepoError> {
+    let mut entries = vec![];
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.len() == len && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            entries.push((entry.path(), name));
+        }
+    }
+    entries.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use crate::{ipld::{Codec, Ipld}, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::MemPinStore, RepoLimits}, BlockError, BlockLimits};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_repo_import_git_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let objects = dir.path();
+        let c = GitRaw::default();
+        let blob = c.encode_to_vec(&Ipld::Bytes(b"hello\n"[..].into())).unwrap();
+        let blob_cid = *Block::from_data(&GitRaw::PREFIX, blob.clone()).unwrap().cid();
+        let tree = c.encode_to_vec(&crate::ipld!({ "hello.txt": { "mode": "100644", "hash": blob_cid } })).unwrap();
+        for data in [&blob, &tree] {
+            let hex = Block::from_data(&GitRaw::PREFIX, data.clone()).unwrap().cid().hash().digest()
+                .iter().map(|b| format!("{b:02x}")).collect::<String>();
+            std::fs::create_dir_all(objects.join(&hex[..2])).unwrap();
+            let mut z = ZlibEncoder::new(vec![], Compression::default());
+            z.write_all(data).unwrap();
+            std::fs::write(objects.join(&hex[..2]).join(&hex[2..]), z.finish().unwrap()).unwrap();
+        }
+        std::fs::create_dir_all(objects.join("pack")).unwrap();
+
+        let repo = Repository::new(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new());
+        let cids = repo.import_git_objects(objects).await.unwrap();
+        assert_eq!(cids.len(), 2);
+        let tree_cid = *cids.iter().find(|cid| **cid != blob_cid).unwrap();
+        let resolved = repo.resolve(&tree_cid, "hello.txt/hash").await.unwrap();
+        assert_eq!(resolved.node, Ipld::Bytes(b"hello\n"[..].into()));
+
+        // an object stored under the wrong name
+        let hex = format!("{:040x}", 1);
+        std::fs::create_dir_all(objects.join(&hex[..2])).unwrap();
+        let mut z = ZlibEncoder::new(vec![], Compression::default());
+        z.write_all(&blob).unwrap();
+        std::fs::write(objects.join(&hex[..2]).join(&hex[2..]), z.finish().unwrap()).unwrap();
+        assert!(matches!(repo.import_git_objects(objects).await, Err(RepoError::IncorrectCid)));
+
+        // an object over the block limit
+        let repo = Repository::builder(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new())
+            .with_limits(RepoLimits { block: BlockLimits { soft: 8, hard: 8 }, ..Default::default() })
+            .build();
+        assert!(matches!(repo.import_git_objects(objects).await, Err(RepoError::Block(BlockError::TooLarge { .. }))));
+    }
+}
@@ -10,6 +10,7 @@ use crate::repo::RepoError;
 use super::KeyStore;
 
 /// In memory [KeyStore].
+#[derive(Default)]
 pub struct MemKeyStore {
     inner: RwLock<BTreeMap<String, Vec<u8>>>, // TODO: btree vs hash?
 }
@@ -3,6 +3,7 @@ use async_trait::async_trait;
 use super::RepoError;
 
 mod mem;
+pub use mem::MemKeyStore;
 
 #[async_trait]
 pub trait KeyStore: Send + Sync {
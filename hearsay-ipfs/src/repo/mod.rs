@@ -6,27 +6,54 @@ use blockstore::BlockStore;
 use cid::Cid;
 
 pub mod blockstore;
+pub mod car;
+pub mod git;
 pub mod keystore;
+pub mod patch;
 pub mod pinstore;
+pub mod resolve;
+pub mod select;
+pub mod walk;
 use keystore::KeyStore;
 use pinstore::{PinMode, PinStore};
 use thiserror::Error;
 
-use crate::Block;
+use crate::{block::{is_inline, BlockLimits}, car::CarError, hasher::HasherRegistry, ipld::{patch::PatchError, path::PathError, CodecError, CodecRegistry, DecodeLimits, Ipld}, Block, BlockError, CidPrefix};
 
 #[derive(Debug, Error)]
 pub enum RepoError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Block(#[from] BlockError),
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    #[error(transparent)]
+    Car(#[from] CarError),
     #[error("CID does not match the expected CID")]
     IncorrectCid,
     #[error("requested data not found")]
     NotFound,
+    #[error("store is read-only")]
+    ReadOnly,
+}
+
+/// Resource limits enforced by the [Repository].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RepoLimits {
+    /// Size limits of stored blocks.
+    pub block: BlockLimits,
+    /// Limits for decoding stored blocks.
+    pub decode: DecodeLimits,
 }
 
 /// Wrapper for IPFS's storage needs.
 #[derive(Clone)]
-pub(crate) struct Repository {
+pub struct Repository {
     inner: Arc<RepoInner>,
 }
 
@@ -37,43 +64,134 @@ struct RepoInner {
     pin_store: Box<dyn PinStore>,
     /// Key management store
     key_store: Box<dyn KeyStore>,
+    limits: RepoLimits,
+    /// Hashers used to verify blocks.
+    hashers: HasherRegistry,
+    /// Codecs used to decode blocks and follow their links.
+    codecs: CodecRegistry,
+}
+
+/// Configures a [Repository] before it is built, see [Repository::builder].
+pub struct RepositoryBuilder {
+    inner: RepoInner,
+}
+
+impl RepositoryBuilder {
+    pub fn with_limits(mut self, limits: RepoLimits) -> Self {
+        self.inner.limits = limits;
+        self
+    }
+
+    pub fn with_hashers(mut self, hashers: HasherRegistry) -> Self {
+        self.inner.hashers = hashers;
+        self
+    }
+
+    pub fn with_codecs(mut self, codecs: CodecRegistry) -> Self {
+        self.inner.codecs = codecs;
+        self
+    }
+
+    pub fn build(self) -> Repository {
+        Repository { inner: Arc::new(self.inner) }
+    }
 }
 
 impl Repository {
+    /// Repository with the default [RepoLimits] and the built-in hashers and codecs.
     pub fn new(block_store: impl BlockStore + 'static, pin_store: impl PinStore + 'static, key_store: impl KeyStore + 'static) -> Self {
-        Self { 
-            inner: Arc::new(RepoInner {
+        Self::builder(block_store, pin_store, key_store).build()
+    }
+
+    /// Builder starting from the defaults of [Repository::new].
+    pub fn builder(block_store: impl BlockStore + 'static, pin_store: impl PinStore + 'static, key_store: impl KeyStore + 'static) -> RepositoryBuilder {
+        RepositoryBuilder {
+            inner: RepoInner {
                 block_store: Box::new(block_store),
                 pin_store: Box::new(pin_store),
                 key_store: Box::new(key_store),
-            })
+                limits: RepoLimits::default(),
+                hashers: HasherRegistry::default(),
+                codecs: CodecRegistry::default(),
+            },
         }
     }
 
+    pub fn limits(&self) -> &RepoLimits {
+        &self.inner.limits
+    }
+
+    pub fn hashers(&self) -> &HasherRegistry {
+        &self.inner.hashers
+    }
+
+    pub fn codecs(&self) -> &CodecRegistry {
+        &self.inner.codecs
+    }
+
     /// Graceful shutdown
     pub fn shutdown(&self) {
         todo!()
     }
     
+    /// Inline (identity) [Cid]s are always contained.
     pub async fn contains(&self, cid: &Cid) -> Result<bool, RepoError> {
+        if is_inline(cid) {
+            return Ok(true);
+        }
         self.inner.block_store.contains(cid).await
     }
 
+    /// Inline (identity) [Cid]s are answered from the [Cid] itself.
+    /// Stored blocks are verified against the [HasherRegistry].
     pub async fn get_block(&self, cid: &Cid) -> Result<Block, RepoError> {
-        self.inner.block_store.get(cid).await
+        if is_inline(cid) {
+            return Block::inline(*cid).map_err(|_| RepoError::IncorrectCid);
+        }
+        let block = self.inner.block_store.get(cid).await?;
+        if !block.verify_with(&self.inner.hashers) {
+            return Err(RepoError::IncorrectCid);
+        }
+        Ok(block)
+    }
+
+    /// Decode a [Block] with the [CodecRegistry], within the repository's [DecodeLimits].
+    pub fn decode_block(&self, block: &Block) -> Result<Ipld, RepoError> {
+        Ok(self.inner.codecs.decode(block, self.inner.limits.decode)?)
+    }
+
+    /// Links of a [Block], found with the [CodecRegistry].
+    pub fn block_links(&self, block: &Block) -> Result<Vec<Cid>, RepoError> {
+        Ok(self.inner.codecs.links(block, self.inner.limits.decode)?)
+    }
+
+    /// Encode into a [Block] with the [CodecRegistry] and [HasherRegistry].
+    pub fn encode_block(&self, ipld: &Ipld, prefix: &CidPrefix) -> Result<Block, RepoError> {
+        Ok(self.inner.codecs.encode(&self.inner.hashers, prefix, ipld)?)
     }
 
+    /// Inline (identity) blocks are pinned but not written to the [BlockStore].
+    /// Blocks over the hard [BlockLimits] are rejected.
     pub async fn put_block(&self, block: Block, pin_mode: PinMode) -> Result<(), RepoError> {
         let cid = block.cid().clone();
-        self.inner.block_store
-            .put(block)
-            .await?;
+        self.store_block(block).await?;
         self.inner.pin_store
             .pin(&cid, pin_mode)
             .await?;
         Ok(())
     }
 
+    /// Write a block without pinning it.
+    async fn store_block(&self, block: Block) -> Result<(), RepoError> {
+        self.inner.limits.block.check(block.data().len())?;
+        if !is_inline(block.cid()) {
+            self.inner.block_store
+                .put(block)
+                .await?;
+        }
+        Ok(())
+    }
+
     pub async fn remove_block(&self, cid: &Cid) {
         todo!()
     }
@@ -83,3 +201,80 @@ impl Repository {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use blockstore::MemBlockStore;
+    use bytes::Bytes;
+    use keystore::MemKeyStore;
+    use multihash_codetable::Code;
+    use pinstore::MemPinStore;
+
+    use super::*;
+
+    fn mem_repo(limits: RepoLimits) -> Repository {
+        Repository::builder(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new()).with_limits(limits).build()
+    }
+
+    #[tokio::test]
+    async fn test_repo_inline_block() {
+        let repo = mem_repo(RepoLimits::default());
+        let block = Block::from_data(&CidPrefix::identity(0x55), Bytes::from_static(b"banana")).unwrap();
+        assert!(repo.contains(block.cid()).await.unwrap());
+        assert_eq!(repo.get_block(block.cid()).await.unwrap().inner(), block.inner());
+        repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        assert!(repo.inner.block_store.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repo_block_limits() {
+        let limits = RepoLimits {
+            block: BlockLimits { soft: 4, hard: 8 },
+            ..Default::default()
+        };
+        let repo = mem_repo(limits);
+        let prefix = CidPrefix::v1(0x55, Code::Sha2_256);
+        let block = Block::from_data(&prefix, Bytes::from_static(b"banana")).unwrap();
+        repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        assert!(repo.contains(block.cid()).await.unwrap());
+        let block = Block::from_data(&prefix, Bytes::from_static(b"banana split")).unwrap();
+        assert!(matches!(repo.put_block(block.clone(), PinMode::Direct).await, Err(RepoError::Block(BlockError::TooLarge { .. }))));
+        assert!(!repo.contains(block.cid()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_repo_verify_on_read() {
+        let repo = mem_repo(RepoLimits::default());
+        let block = Block::from_data(&CidPrefix::v1(0x55, Code::Sha2_256), Bytes::from_static(b"banana")).unwrap();
+        repo.inner.block_store.put(Block::new_unchecked(*block.cid(), Bytes::from_static(b"apple"))).await.unwrap();
+        assert!(matches!(repo.get_block(block.cid()).await, Err(RepoError::IncorrectCid)));
+    }
+
+    #[tokio::test]
+    async fn test_repo_custom_hasher() {
+        let mut hashers = HasherRegistry::default();
+        hashers.register(0x300000, crate::hasher::tests::xor_fold);
+        let repo = Repository::builder(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new()).with_hashers(hashers).build();
+        let prefix = CidPrefix { mh_type: 0x300000, ..CidPrefix::v1(0x55, Code::Sha2_256) };
+        let block = Block::from_data_with(repo.hashers(), &prefix, Bytes::from_static(b"banana")).unwrap();
+        repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        assert_eq!(repo.get_block(block.cid()).await.unwrap().inner(), block.inner());
+    }
+
+    #[tokio::test]
+    async fn test_repo_custom_codec() {
+        use crate::ipld::{registry::tests::{Lines, LINES}, DagCbor};
+
+        let mut codecs = CodecRegistry::default();
+        codecs.register(LINES, Lines);
+        let repo = Repository::builder(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new()).with_codecs(codecs).build();
+        let leaf = Block::encode(&DagCbor::default(), Code::Sha2_256, &Ipld::String(String::from("banana"))).unwrap();
+        let root = repo.encode_block(&Ipld::List(vec![Ipld::Link(*leaf.cid())]), &CidPrefix::v1(LINES, Code::Sha2_256)).unwrap();
+        repo.put_block(leaf.clone(), PinMode::Direct).await.unwrap();
+        repo.put_block(root.clone(), PinMode::Direct).await.unwrap();
+        assert_eq!(repo.block_links(&root).unwrap(), [*leaf.cid()]);
+        let walk = repo.walk(root.cid(), &Default::default()).await.unwrap();
+        assert_eq!(walk.refs.iter().map(|r| r.cid).collect::<Vec<_>>(), [*root.cid(), *leaf.cid()]);
+        let resolved = repo.resolve(root.cid(), "0").await.unwrap();
+        assert_eq!((resolved.cid, resolved.node), (*leaf.cid(), Ipld::String(String::from("banana"))));
+    }
+}
@@ -0,0 +1,245 @@
+//! [IPLD Patch](https://ipld.io/specs/patch/) across linked blocks.
+
+use std::{collections::HashMap, mem};
+
+use cid::Cid;
+
+use crate::{ipld::{patch::{self, apply_op, split, Operation, PatchError, Target}, path::PathError, Ipld}, Block, CidPrefix};
+
+use super::{pinstore::PinMode, RepoError, Repository};
+
+/// A loaded block being edited.
+struct Node {
+    /// [Cid] the block was loaded from, whose codec and hash the rewritten block keeps.
+    cid: Cid,
+    ipld: Ipld,
+    /// Loaded blocks linked from this one, by the path of the link within it.
+    children: Vec<(Vec<String>, usize)>,
+    dirty: bool,
+}
+
+/// Blocks loaded along the patched paths, starting with the root.
+struct Patcher<'a> {
+    repo: &'a Repository,
+    nodes: Vec<Node>,
+    /// Rewritten blocks, only written once every operation has applied.
+    blocks: HashMap<Cid, Block>,
+}
+
+enum Located {
+    /// Block, and path within it, of a container.
+    Found(usize, Vec<String>),
+    /// Link to a block that is not loaded yet, from the block and path within it.
+    Unloaded(usize, Vec<String>, Cid),
+}
+
+impl Repository {
+    /// Apply the [Operation]s to the DAG under the root, rewriting only the blocks on the changed paths.
+    /// The new root is pinned with the [PinMode], and nothing is written if an operation fails.
+    pub async fn patch(&self, root: &Cid, ops: &[Operation], pin_mode: PinMode) -> Result<Cid, RepoError> {
+        let ipld = self.decode_block(&self.get_block(root).await?)?;
+        let mut patcher = Patcher {
+            repo: self,
+            nodes: vec![Node { cid: *root, ipld, children: vec![], dirty: false }],
+            blocks: HashMap::new(),
+        };
+        for op in ops {
+            if let Some(from) = op.from() {
+                patcher.load(from).await?;
+            }
+            patcher.load(op.path()).await?;
+            apply_op(&mut patcher, op)?;
+        }
+        let new_root = patcher.flush(0)?;
+
+        // Rewritten blocks that are no longer linked, such as moved out of, are dropped.
+        let mut stack = vec![new_root];
+        let mut reachable = vec![];
+        while let Some(cid) = stack.pop() {
+            if let Some(block) = patcher.blocks.remove(&cid) {
+                stack.extend(self.block_links(&block)?);
+                reachable.push(block);
+            }
+        }
+        // Children first, so the new root is only written once the DAG under it is complete.
+        for block in reachable.into_iter().rev() {
+            let mode = if *block.cid() == new_root { pin_mode } else { PinMode::Indirect(new_root) };
+            self.put_block(block, mode).await?;
+        }
+        Ok(new_root)
+    }
+}
+
+impl Patcher<'_> {
+    /// Find the container at the end of the segments, crossing loaded links.
+    fn locate(&self, parent: &[&str]) -> Result<Located, PathError> {
+        let (mut index, mut local) = (0, vec![]);
+        let mut node = &self.nodes[0].ipld;
+        let mut rest = parent.iter();
+        loop {
+            if let Ipld::Link(cid) = node {
+                match self.nodes[index].children.iter().find(|(path, _)| *path == local) {
+                    Some(&(_, child)) => {
+                        (index, local, node) = (child, vec![], &self.nodes[child].ipld);
+                        continue;
+                    },
+                    None => return Ok(Located::Unloaded(index, local, *cid)),
+                }
+            }
+            let Some(segment) = rest.next() else {
+                return Ok(Located::Found(index, local));
+            };
+            node = node.get_segment(segment)?;
+            local.push((*segment).to_owned());
+        }
+    }
+
+    /// Load the blocks linked along the path to its container. Path errors are left for the operation to report.
+    async fn load(&mut self, path: &str) -> Result<(), RepoError> {
+        let Some((parent, _)) = split(path) else {
+            return Ok(());
+        };
+        while let Ok(Located::Unloaded(index, local, cid)) = self.locate(&parent) {
+            let block = match self.blocks.get(&cid) {
+                Some(block) => block.clone(),
+                None => self.repo.get_block(&cid).await?,
+            };
+            let ipld = self.repo.decode_block(&block)?;
+            self.nodes.push(Node { cid, ipld, children: vec![], dirty: false });
+            let child = self.nodes.len() - 1;
+            self.nodes[index].children.push((local, child));
+        }
+        Ok(())
+    }
+
+    fn container(&self, parent: &[&str]) -> Result<(usize, Vec<String>), PatchError> {
+        match self.locate(parent)? {
+            Located::Found(index, local) => Ok((index, local)),
+            Located::Unloaded(_, _, cid) => Err(PathError::Link(cid).into()),
+        }
+    }
+
+    /// Encode the loaded blocks linked from under the prefix, and link them by their new [Cid]s.
+    fn flush_under(&mut self, index: usize, prefix: &[String]) -> Result<(), PatchError> {
+        let (flushed, kept) = mem::take(&mut self.nodes[index].children)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(path, _)| path.starts_with(prefix));
+        self.nodes[index].children = kept;
+        for (path, child) in flushed {
+            let cid = self.flush(child)?;
+            if cid != self.nodes[child].cid {
+                *self.nodes[index].ipld.get_path_mut(&path.join("/"))? = Ipld::Link(cid);
+                self.nodes[index].dirty = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode a block if it or any block under it changed.
+    fn flush(&mut self, index: usize) -> Result<Cid, PatchError> {
+        self.flush_under(index, &[])?;
+        let node = &self.nodes[index];
+        if !node.dirty {
+            return Ok(node.cid);
+        }
+        let block = self.repo.codecs().encode(self.repo.hashers(), &CidPrefix::from_cid(&node.cid), &node.ipld)?;
+        let cid = *block.cid();
+        self.blocks.insert(cid, block);
+        Ok(cid)
+    }
+
+    /// Run an edit on the container of the path.
+    fn edit<T>(&mut self, parent: &[&str], key: &str, f: impl FnOnce(&mut Ipld, &str) -> Result<T, PathError>) -> Result<T, PatchError> {
+        let (index, local) = self.container(parent)?;
+        // Links under the container may move, so loaded blocks there are encoded first.
+        self.flush_under(index, &local)?;
+        let out = f(self.nodes[index].ipld.get_path_mut(&local.join("/"))?, key)?;
+        self.nodes[index].dirty = true;
+        Ok(out)
+    }
+
+    fn set_root(&mut self, value: Ipld) -> Ipld {
+        let root = &mut self.nodes[0];
+        root.children.clear();
+        root.dirty = true;
+        mem::replace(&mut root.ipld, value)
+    }
+}
+
+impl Target for Patcher<'_> {
+    fn read(&mut self, path: &str) -> Result<Ipld, PatchError> {
+        let Some((parent, key)) = split(path) else {
+            self.flush_under(0, &[])?;
+            return Ok(self.nodes[0].ipld.clone());
+        };
+        let (index, mut local) = self.container(&parent)?;
+        local.push(key.to_owned());
+        self.flush_under(index, &local)?;
+        Ok(self.nodes[index].ipld.get_path(&local.join("/"))?.clone())
+    }
+
+    fn add(&mut self, path: &str, value: Ipld) -> Result<(), PatchError> {
+        match split(path) {
+            Some((parent, key)) => self.edit(&parent, key, |c, key| patch::add(c, key, value)),
+            None => {
+                self.set_root(value);
+                Ok(())
+            },
+        }
+    }
+
+    fn remove(&mut self, path: &str) -> Result<Ipld, PatchError> {
+        let (parent, key) = split(path).ok_or(PatchError::RemoveRoot)?;
+        self.edit(&parent, key, patch::remove)
+    }
+
+    fn replace(&mut self, path: &str, value: Ipld) -> Result<Ipld, PatchError> {
+        match split(path) {
+            Some((parent, key)) => self.edit(&parent, key, |c, key| patch::replace(c, key, value)),
+            None => Ok(self.set_root(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use multihash_codetable::Code;
+
+    use crate::{ipld, ipld::DagCbor, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::MemPinStore}};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_repo_patch() {
+        let repo = Repository::new(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new());
+        let c = DagCbor::default();
+        let sibling = Block::encode(&c, Code::Sha2_256, &ipld!({ "untouched": true })).unwrap();
+        let leaf = Block::encode(&c, Code::Sha2_256, &ipld!({ "name": "banana", "tags": [] })).unwrap();
+        let mid = Block::encode(&c, Code::Sha2_256, &ipld!({ "leaf": *leaf.cid(), "list": [1, 2] })).unwrap();
+        let root = Block::encode(&c, Code::Sha2_256, &ipld!({ "mid": *mid.cid(), "sibling": *sibling.cid() })).unwrap();
+        for block in [&sibling, &leaf, &mid, &root] {
+            repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        }
+
+        let ops = patch::parse(&ipld!([
+            { "op": "replace", "path": "mid/leaf/name", "value": "apple" },
+            { "op": "add", "path": "mid/leaf/tags/-", "value": "fruit" },
+            { "op": "remove", "path": "mid/list/0" },
+            { "op": "copy", "from": "mid/leaf", "path": "copy" },
+            { "op": "test", "path": "mid/leaf/tags/0", "value": "fruit" },
+        ])).unwrap();
+        let new_root = repo.patch(root.cid(), &ops, PinMode::Recursive).await.unwrap();
+        assert_eq!(repo.resolve(&new_root, "mid/leaf/name").await.unwrap().node, ipld!("apple"));
+        assert_eq!(repo.resolve(&new_root, "copy/tags").await.unwrap().node, ipld!(["fruit"]));
+        assert_eq!(repo.resolve(&new_root, "mid/list").await.unwrap().node, ipld!([2]));
+
+        // Only the blocks on the changed paths are rewritten.
+        let new = repo.decode_block(&repo.get_block(&new_root).await.unwrap()).unwrap();
+        assert_eq!(new["sibling"], ipld!(*sibling.cid()));
+        assert_eq!(new["copy"], repo.decode_block(&repo.get_block(new["mid"].as_link().unwrap()).await.unwrap()).unwrap()["leaf"]);
+        assert_ne!(new["mid"], ipld!(*mid.cid()));
+
+        let failing = [Operation::Remove { path: String::from("mid/leaf/missing") }];
+        assert!(matches!(repo.patch(root.cid(), &failing, PinMode::Recursive).await, Err(RepoError::Patch(PatchError::Path(_)))));
+    }
+}
@@ -4,10 +4,20 @@ use cid::Cid;
 use crate::repo::RepoError;
 use super::{PinInfo, PinMode, PinStore};
 
-struct MemPinStore {
+/// In memory [PinStore].
+#[derive(Default)]
+pub struct MemPinStore {
     inner: tokio::sync::RwLock<BTreeMap<Cid, PinInfo>>,
 }
 
+impl MemPinStore {
+    pub fn new() -> Self {
+        Self {
+            inner: tokio::sync::RwLock::new(BTreeMap::default()),
+        }
+    }
+}
+
 #[async_trait]
 impl PinStore for MemPinStore {
     async fn is_pinned(&self, cid: &Cid) -> Result<bool, RepoError> {
@@ -4,6 +4,7 @@ use cid::Cid;
 use super::RepoError;
 
 mod mem;
+pub use mem::MemPinStore;
 
 /// Keeps track of which [Cid]s must remain pinned by the [Repo].
 #[async_trait]
@@ -21,7 +22,7 @@ pub struct PinInfo {
     recursive: u64,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PinMode {
     Direct,
     Indirect(Cid),
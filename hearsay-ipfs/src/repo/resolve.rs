@@ -0,0 +1,85 @@
+//! Path resolution across linked blocks.
+
+use cid::Cid;
+
+use crate::ipld::{path::segments, Ipld};
+
+use super::{RepoError, Repository};
+
+/// Result of resolving a path from a root [Cid].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resolved {
+    /// Node the path resolved to.
+    pub node: Ipld,
+    /// [Cid] of the last block crossed, which contains `node`.
+    pub cid: Cid,
+    /// Path left over when resolution reached a node that is neither a map, list nor link.
+    pub remaining: String,
+}
+
+impl Repository {
+    /// Follow a path such as `foo/0/bar` from the root block, loading linked blocks as they are crossed.
+    /// A link at the end of the path is also followed, so `node` is never a [Ipld::Link].
+    pub async fn resolve(&self, root: &Cid, path: &str) -> Result<Resolved, RepoError> {
+        let segments = segments(path).collect::<Vec<_>>();
+        let mut cid = *root;
+        let mut block = self.decode_block(&self.get_block(&cid).await?)?;
+        let mut i = 0;
+        loop {
+            let mut node = &block;
+            while let (Some(segment), Ipld::Map(_) | Ipld::List(_)) = (segments.get(i), node) {
+                node = node.get_segment(segment)?;
+                i += 1;
+            }
+            match node {
+                Ipld::Link(next) => cid = *next,
+                _ => return Ok(Resolved {
+                    node: node.clone(),
+                    cid,
+                    remaining: segments[i..].join("/"),
+                }),
+            }
+            block = self.decode_block(&self.get_block(&cid).await?)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bytes::Bytes;
+    use multihash_codetable::Code;
+
+    use crate::{ipld::DagCbor, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::{MemPinStore, PinMode}}, Block, CidPrefix};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_repo_resolve() {
+        let repo = Repository::new(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new());
+        let raw = Block::from_data(&CidPrefix::v1(0x55, Code::Sha2_256), Bytes::from_static(b"banana")).unwrap();
+        let leaf = Ipld::Map(BTreeMap::from([
+            (String::from("bar"), Ipld::Integer(7)),
+            (String::from("raw"), Ipld::Link(*raw.cid())),
+        ]));
+        let leaf = Block::encode(&DagCbor::default(), Code::Sha2_256, &leaf).unwrap();
+        let root = Ipld::Map(BTreeMap::from([
+            (String::from("foo"), Ipld::List(vec![Ipld::Link(*leaf.cid())])),
+        ]));
+        let root = Block::encode(&DagCbor::default(), Code::Sha2_256, &root).unwrap();
+        for block in [&raw, &leaf, &root] {
+            repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        }
+
+        let resolved = repo.resolve(root.cid(), "foo/0/bar").await.unwrap();
+        assert_eq!(resolved, Resolved { node: Ipld::Integer(7), cid: *leaf.cid(), remaining: String::new() });
+
+        let resolved = repo.resolve(root.cid(), "/foo/0/raw/a/b").await.unwrap();
+        assert_eq!(resolved.node, Ipld::Bytes(Bytes::from_static(b"banana")));
+        assert_eq!(resolved.cid, *raw.cid());
+        assert_eq!(resolved.remaining, "a/b");
+
+        assert!(matches!(repo.resolve(root.cid(), "foo/1").await, Err(RepoError::Path(_))));
+    }
+}
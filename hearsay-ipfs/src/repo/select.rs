@@ -0,0 +1,123 @@
+//! Selector traversal across linked blocks.
+
+use std::collections::HashSet;
+
+use cid::Cid;
+
+use crate::ipld::{selector::Selector, Ipld};
+
+use super::{RepoError, Repository};
+
+/// Outcome of walking a DAG with a [Selector].
+#[derive(Debug, Default)]
+pub struct Selection {
+    /// Matched nodes, with their path from the root.
+    pub matches: Vec<(String, Ipld)>,
+    /// Blocks loaded by the walk, in the order they were first crossed.
+    pub blocks: Vec<Cid>,
+}
+
+/// Walk of a single block, in order.
+enum Step {
+    Match(String, Ipld),
+    /// Link to load, with the selector to continue with.
+    Link(String, Cid, Selector),
+}
+
+impl Repository {
+    /// Walk the DAG from the root block in depth-first order, following links the [Selector] explores.
+    pub async fn select(&self, root: &Cid, selector: &Selector) -> Result<Selection, RepoError> {
+        let mut selection = Selection::default();
+        let mut seen = HashSet::new();
+        let mut stack = vec![vec![Step::Link(String::new(), *root, selector.clone())].into_iter()];
+        while let Some(steps) = stack.last_mut() {
+            match steps.next() {
+                Some(Step::Match(path, node)) => selection.matches.push((path, node)),
+                Some(Step::Link(path, cid, selector)) => {
+                    let node = self.decode_block(&self.get_block(&cid).await?)?;
+                    if seen.insert(cid) {
+                        selection.blocks.push(cid);
+                    }
+                    let mut steps = vec![];
+                    visit(&node, path, &selector, &mut steps);
+                    stack.push(steps.into_iter());
+                },
+                None => {
+                    stack.pop();
+                },
+            }
+        }
+        Ok(selection)
+    }
+}
+
+/// Walk a single decoded block, stopping at links.
+fn visit(node: &Ipld, path: String, selector: &Selector, steps: &mut Vec<Step>) {
+    if let Ipld::Link(cid) = node {
+        steps.push(Step::Link(path, *cid, selector.clone()));
+        return;
+    }
+    if selector.decide(node) {
+        steps.push(Step::Match(path.clone(), node.clone()));
+    }
+    let join = |segment: &str| if path.is_empty() { segment.to_owned() } else { format!("{path}/{segment}") };
+    match node {
+        Ipld::Map(m) => for (k, v) in m {
+            if let Some(next) = selector.explore(node, k) {
+                visit(v, join(k), &next, steps);
+            }
+        },
+        Ipld::List(l) => for (i, v) in l.iter().enumerate() {
+            let segment = i.to_string();
+            if let Some(next) = selector.explore(node, &segment) {
+                visit(v, join(&segment), &next, steps);
+            }
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use multihash_codetable::Code;
+
+    use crate::{ipld::{selector::RecursionLimit, DagCbor}, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::{MemPinStore, PinMode}}, Block};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_repo_select() {
+        let repo = Repository::new(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new());
+        let c = DagCbor::default();
+        let leaf = Block::encode(&c, Code::Sha2_256, &Ipld::String(String::from("banana"))).unwrap();
+        let skipped = Block::encode(&c, Code::Sha2_256, &Ipld::String(String::from("apple"))).unwrap();
+        let root = Ipld::Map(BTreeMap::from([
+            (String::from("a"), Ipld::List(vec![Ipld::Link(*leaf.cid()), Ipld::Integer(1)])),
+            (String::from("b"), Ipld::Link(*skipped.cid())),
+        ]));
+        let root = Block::encode(&c, Code::Sha2_256, &root).unwrap();
+        for block in [&leaf, &skipped, &root] {
+            repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        }
+
+        let selector = Selector::ExploreFields { fields: BTreeMap::from([
+            (String::from("a"), Selector::ExploreAll { next: Box::new(Selector::Matcher) }),
+        ]) };
+        let selection = repo.select(root.cid(), &selector).await.unwrap();
+        assert_eq!(selection.matches, [
+            (String::from("a/0"), Ipld::String(String::from("banana"))),
+            (String::from("a/1"), Ipld::Integer(1)),
+        ]);
+        assert_eq!(selection.blocks, [*root.cid(), *leaf.cid()]);
+
+        let all = Selector::explore_recursive(RecursionLimit::None, Selector::ExploreUnion(vec![
+            Selector::Matcher,
+            Selector::ExploreAll { next: Box::new(Selector::ExploreRecursiveEdge) },
+        ]));
+        let selection = repo.select(root.cid(), &all).await.unwrap();
+        assert_eq!(selection.matches.len(), 5);
+        assert_eq!(selection.blocks, [*root.cid(), *leaf.cid(), *skipped.cid()]);
+    }
+}
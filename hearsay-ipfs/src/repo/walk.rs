@@ -0,0 +1,163 @@
+//! Link walking across blocks, the basis of `refs`, recursive pinning, garbage collection and export.
+
+use std::collections::{HashSet, VecDeque};
+
+use cid::Cid;
+
+use crate::Block;
+
+use super::{RepoError, Repository};
+
+/// Order blocks are visited in by [Repository::walk].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WalkOrder {
+    /// Pre-order, following links in the order they appear in a block.
+    #[default]
+    DepthFirst,
+    BreadthFirst,
+}
+
+/// What [Repository::walk] does when a block is not in the repository.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingBlock {
+    /// Fail with [RepoError::NotFound].
+    #[default]
+    Stop,
+    /// Record the block in [Walk::missing] and carry on without its links.
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WalkOptions {
+    pub order: WalkOrder,
+    /// Links deeper than this are not followed, with the root at depth 0.
+    /// Blocks at the maximum depth are listed without being loaded.
+    pub max_depth: Option<usize>,
+    /// Visit each block once, instead of once per link to it.
+    pub unique: bool,
+    pub missing: MissingBlock,
+}
+
+/// A block reached by [Repository::walk].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ref {
+    /// Block that links to this one, [None] for the root.
+    pub parent: Option<Cid>,
+    pub cid: Cid,
+    pub depth: usize,
+}
+
+/// Outcome of [Repository::walk].
+#[derive(Debug, Default)]
+pub struct Walk {
+    /// Blocks in the order they were visited, starting with the root.
+    pub refs: Vec<Ref>,
+    /// Blocks that were referenced but not found, with [MissingBlock::Skip].
+    pub missing: Vec<Cid>,
+}
+
+impl Repository {
+    /// Walk every link reachable from the root block.
+    pub async fn walk(&self, root: &Cid, options: &WalkOptions) -> Result<Walk, RepoError> {
+        let mut walker = Walker::new(self, root, options);
+        let mut refs = vec![];
+        while let Some((r, _)) = walker.next().await? {
+            refs.push(r);
+        }
+        Ok(Walk { refs, missing: walker.missing })
+    }
+}
+
+/// Step-by-step [Repository::walk], handing out each block as it is loaded.
+pub(super) struct Walker<'a> {
+    repo: &'a Repository,
+    options: &'a WalkOptions,
+    seen: HashSet<Cid>,
+    queue: VecDeque<Ref>,
+    missing: Vec<Cid>,
+}
+
+impl<'a> Walker<'a> {
+    pub(super) fn new(repo: &'a Repository, root: &Cid, options: &'a WalkOptions) -> Self {
+        let queue = VecDeque::from([Ref { parent: None, cid: *root, depth: 0 }]);
+        Self { repo, options, seen: HashSet::new(), queue, missing: vec![] }
+    }
+
+    /// The next block visited, or `None` once the walk is done.
+    /// The block is [None] if it is at the maximum depth or missing.
+    pub(super) async fn next(&mut self) -> Result<Option<(Ref, Option<Block>)>, RepoError> {
+        loop {
+            let next = match self.options.order {
+                WalkOrder::DepthFirst => self.queue.pop_back(),
+                WalkOrder::BreadthFirst => self.queue.pop_front(),
+            };
+            let Some(next) = next else {
+                return Ok(None);
+            };
+            if self.options.unique && !self.seen.insert(next.cid) {
+                continue;
+            }
+            if self.options.max_depth.is_some_and(|max| next.depth >= max) {
+                return Ok(Some((next, None)));
+            }
+            let block = match self.repo.get_block(&next.cid).await {
+                Ok(block) => block,
+                Err(RepoError::NotFound) if self.options.missing == MissingBlock::Skip => {
+                    self.missing.push(next.cid);
+                    return Ok(Some((next, None)));
+                },
+                Err(e) => return Err(e),
+            };
+            let links = self.repo.block_links(&block)?;
+            let children = links.into_iter().map(|cid| Ref { parent: Some(next.cid), cid, depth: next.depth + 1 });
+            match self.options.order {
+                // Pushed in reverse so the first link is popped first.
+                WalkOrder::DepthFirst => self.queue.extend(children.collect::<Vec<_>>().into_iter().rev()),
+                WalkOrder::BreadthFirst => self.queue.extend(children),
+            }
+            return Ok(Some((next, Some(block))));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use multihash_codetable::Code;
+
+    use crate::{ipld, ipld::DagCbor, repo::{blockstore::MemBlockStore, keystore::MemKeyStore, pinstore::{MemPinStore, PinMode}}, Block, CidPrefix};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_repo_walk() {
+        let repo = Repository::new(MemBlockStore::new(), MemPinStore::new(), MemKeyStore::new());
+        let c = DagCbor::default();
+        let leaf = Block::from_data(&CidPrefix::v1(0x55, Code::Sha2_256), Bytes::from_static(b"banana")).unwrap();
+        let missing = Block::from_data(&CidPrefix::v1(0x55, Code::Sha2_256), Bytes::from_static(b"apple")).unwrap();
+        let a = Block::encode(&c, Code::Sha2_256, &ipld!([*leaf.cid(), *missing.cid()])).unwrap();
+        let b = Block::encode(&c, Code::Sha2_256, &ipld!({ "leaf": *leaf.cid() })).unwrap();
+        let root = Block::encode(&c, Code::Sha2_256, &ipld!([*a.cid(), *b.cid()])).unwrap();
+        for block in [&leaf, &a, &b, &root] {
+            repo.put_block(block.clone(), PinMode::Direct).await.unwrap();
+        }
+        let cids = |walk: &Walk| walk.refs.iter().map(|r| r.cid).collect::<Vec<_>>();
+
+        assert!(matches!(repo.walk(root.cid(), &WalkOptions::default()).await, Err(RepoError::NotFound)));
+
+        let options = WalkOptions { missing: MissingBlock::Skip, ..Default::default() };
+        let walk = repo.walk(root.cid(), &options).await.unwrap();
+        assert_eq!(cids(&walk), [*root.cid(), *a.cid(), *leaf.cid(), *missing.cid(), *b.cid(), *leaf.cid()]);
+        assert_eq!(walk.missing, [*missing.cid()]);
+        assert_eq!(walk.refs[4], Ref { parent: Some(*root.cid()), cid: *b.cid(), depth: 1 });
+
+        let options = WalkOptions { order: WalkOrder::BreadthFirst, unique: true, missing: MissingBlock::Skip, ..Default::default() };
+        let walk = repo.walk(root.cid(), &options).await.unwrap();
+        assert_eq!(cids(&walk), [*root.cid(), *a.cid(), *b.cid(), *leaf.cid(), *missing.cid()]);
+
+        let options = WalkOptions { max_depth: Some(1), ..Default::default() };
+        let walk = repo.walk(root.cid(), &options).await.unwrap();
+        assert_eq!(cids(&walk), [*root.cid(), *a.cid(), *b.cid()]);
+        assert!(walk.missing.is_empty());
+    }
+}
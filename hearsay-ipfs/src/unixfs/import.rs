@@ -1,5 +1,9 @@
 //! <https://github.com/ipfs/specs/blob/main/UNIXFS.md#importing>
 
+use bytes::Bytes;
+
+use crate::{block::{BlockError, CidPrefix, MAX_INLINE_SIZE}, hasher::HasherRegistry, Block};
+
 const DEFAULT_MAX_WIDTH: usize = 174;
 /// Default number of bytes per chunk. See <https://ipfs-search.readthedocs.io/en/latest/ipfs_datatypes.html#chunked-unixfs-protobuf>.
 const DEFAULT_CHUNK_SIZE: usize = 262144;
@@ -9,9 +13,42 @@ pub(crate) struct Importer {
     max_width: usize,
     chunker: ChunkStrat,
     layout: LayoutStrat,
+    /// Payloads up to this many bytes are inlined into identity [cid::Cid]s.
+    inline_limit: Option<usize>,
+    hashers: HasherRegistry,
 }
 
 impl Importer {
+    pub fn new() -> Self {
+        Self {
+            max_width: DEFAULT_MAX_WIDTH,
+            chunker: ChunkStrat::default(),
+            layout: LayoutStrat::default(),
+            inline_limit: None,
+            hashers: HasherRegistry::default(),
+        }
+    }
+
+    /// Hash blocks with the [HasherRegistry].
+    pub fn with_hashers(mut self, hashers: HasherRegistry) -> Self {
+        self.hashers = hashers;
+        self
+    }
+
+    /// Inline payloads of up to `limit` bytes, capped at [MAX_INLINE_SIZE].
+    pub fn with_inline_limit(mut self, limit: usize) -> Self {
+        self.inline_limit = Some(limit.min(MAX_INLINE_SIZE));
+        self
+    }
+
+    /// Build the [Block] for a node, inlining it if it is small enough.
+    fn make_block(&self, prefix: &CidPrefix, data: Bytes) -> Result<Block, BlockError> {
+        match self.inline_limit {
+            Some(limit) if data.len() <= limit => Block::from_data_with(&self.hashers, &CidPrefix::identity(prefix.codec), data),
+            _ => Block::from_data_with(&self.hashers, prefix, data),
+        }
+    }
+
     // TODO: impl UnixFS Importer
 }
 
@@ -36,3 +73,31 @@ enum LayoutStrat {
     Balanced,
     Trickle,
 }
+
+#[cfg(test)]
+mod tests {
+    use multihash_codetable::Code;
+
+    use crate::block::is_inline;
+
+    use super::*;
+
+    #[test]
+    fn test_importer_inline() {
+        let prefix = CidPrefix::v1(0x55, Code::Sha2_256);
+        let importer = Importer::new().with_inline_limit(8);
+        assert!(is_inline(importer.make_block(&prefix, Bytes::from_static(b"banana")).unwrap().cid()));
+        assert!(!is_inline(importer.make_block(&prefix, Bytes::from_static(b"banana split")).unwrap().cid()));
+        assert!(!is_inline(Importer::new().make_block(&prefix, Bytes::from_static(b"banana")).unwrap().cid()));
+    }
+
+    #[test]
+    fn test_importer_hashers() {
+        let mut hashers = HasherRegistry::default();
+        hashers.register(0x300000, crate::hasher::tests::xor_fold);
+        let prefix = CidPrefix { mh_type: 0x300000, ..CidPrefix::v1(0x55, Code::Sha2_256) };
+        assert!(Importer::new().make_block(&prefix, Bytes::from_static(b"banana")).is_err());
+        let block = Importer::new().with_hashers(hashers).make_block(&prefix, Bytes::from_static(b"banana")).unwrap();
+        assert_eq!(block.cid().hash().code(), 0x300000);
+    }
+}